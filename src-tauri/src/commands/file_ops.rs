@@ -1,10 +1,23 @@
+use crate::commands::settings::load_categories;
+use crate::commands::trash::trash_target;
 use crate::error::AppError;
-use crate::models::{Operation, OperationStatus, OperationType, PlannedOperation};
+use crate::models::{
+    BatchSource, DuplicateAction, DuplicateGroup, DuplicateOptions, FileFingerprint, KeepStrategy,
+    Operation, OperationBatch, OperationStatus, OperationType, PlannedOperation,
+};
+use crate::services::template::Template;
+use crate::services::{archive, duplicates, file_type, trash};
+use crate::storage::files::{self as file_index, FileStamp};
+use crate::storage::{batches, operations, Storage};
 use chrono::Utc;
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use tauri::ipc::Channel;
-use tauri::Manager;
+use tauri::State;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize)]
@@ -14,126 +27,402 @@ pub struct OperationProgress {
     pub completed_count: usize,
     pub total_count: usize,
     pub percentage: f32,
+    /// Set for tasks that can be cancelled, such as `find_duplicates`
+    pub task_id: Option<String>,
+    /// Set for tasks that read file contents; `percentage` then follows the
+    /// bytes rather than the files
+    pub processed_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
 }
 
-fn get_db_path(app: &tauri::AppHandle) -> PathBuf {
-    app.path()
-        .app_data_dir()
-        .unwrap_or_else(|_| PathBuf::from("."))
-        .join("fileog.db")
+impl OperationProgress {
+    fn new(event: &str, completed_count: usize, total_count: usize) -> Self {
+        Self {
+            event: event.to_string(),
+            current_file: None,
+            completed_count,
+            total_count,
+            percentage: percentage(completed_count as u64, total_count as u64),
+            task_id: None,
+            processed_bytes: None,
+            total_bytes: None,
+        }
+    }
+}
+
+fn percentage(done: u64, total: u64) -> f32 {
+    if total == 0 {
+        100.0
+    } else {
+        (done as f32 / total as f32) * 100.0
+    }
+}
+
+/// Duplicate searches currently running, so `cancel_find_duplicates` can
+/// reach them
+#[derive(Default)]
+pub struct DuplicateSearches {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
 }
 
-fn get_trash_dir(app: &tauri::AppHandle) -> PathBuf {
-    let trash_dir = app
-        .path()
-        .app_data_dir()
-        .unwrap_or_else(|_| PathBuf::from("."))
-        .join("trash");
+/// Files up to this size get a content hash in their fingerprint
+const FINGERPRINT_HASH_LIMIT: u64 = 64 * 1024 * 1024;
 
-    // Ensure trash directory exists
-    if !trash_dir.exists() {
-        let _ = std::fs::create_dir_all(&trash_dir);
+pub(crate) fn hash_file(path: &Path) -> std::io::Result<String> {
+    use sha2::{Digest, Sha256};
+    use std::io::Read;
+
+    let mut f = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 8192];
+
+    loop {
+        match f.read(&mut buffer)? {
+            0 => break,
+            n => hasher.update(&buffer[..n]),
+        }
     }
 
-    trash_dir
+    Ok(format!("{:x}", hasher.finalize()))
 }
 
-fn save_operation_to_db(app: &tauri::AppHandle, operation: &Operation) -> Result<(), AppError> {
-    use rusqlite::Connection;
+/// Fingerprint of what an operation produced. Only a copy gets a content
+/// hash, since undo deletes it only while the hash still matches; size and
+/// mtime are enough for the rest and keep large moves from re-reading
+/// every file.
+pub(crate) fn result_fingerprint(operation: &Operation) -> Option<FileFingerprint> {
+    let path = operation.result_path()?;
+    fingerprint_file(path, operation.operation_type == OperationType::Copy)
+}
 
-    let db_path = get_db_path(app);
-    let conn = Connection::open(&db_path)?;
+/// Captures size, mtime and, with `with_hash` for small files, hash of a
+/// file. A folder, such as an extracted archive, gets the total size and
+/// latest mtime of everything in it.
+pub(crate) fn fingerprint_file(path: &Path, with_hash: bool) -> Option<FileFingerprint> {
+    let metadata = std::fs::metadata(path).ok()?;
 
-    let op_type = match operation.operation_type {
-        OperationType::Move => "move",
-        OperationType::Copy => "copy",
-        OperationType::Rename => "rename",
-        OperationType::Delete => "delete",
+    if metadata.is_dir() {
+        return fingerprint_dir(path, &metadata);
+    }
+    if !metadata.is_file() {
+        return None;
+    }
+
+    let size = metadata.len();
+    let hash = if with_hash && size <= FINGERPRINT_HASH_LIMIT {
+        hash_file(path).ok()
+    } else {
+        None
     };
 
-    let status = match &operation.status {
-        OperationStatus::Completed => "completed".to_string(),
-        OperationStatus::Failed(e) => format!("failed:{}", e),
-        OperationStatus::Pending => "pending".to_string(),
-        OperationStatus::InProgress => "in_progress".to_string(),
-        OperationStatus::Undone => "undone".to_string(),
+    Some(FileFingerprint {
+        size,
+        modified_at: modified_secs(&metadata),
+        hash,
+    })
+}
+
+/// Adding, removing or editing anything inside a folder bumps the mtime of
+/// the file or of its parent folder
+fn fingerprint_dir(path: &Path, metadata: &std::fs::Metadata) -> Option<FileFingerprint> {
+    let mut size = 0;
+    let mut modified_at = modified_secs(metadata);
+    let mut pending = vec![path.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir).ok()? {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            modified_at = modified_at.max(modified_secs(&metadata));
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                size += metadata.len();
+            }
+        }
+    }
+
+    Some(FileFingerprint {
+        size,
+        modified_at,
+        hash: None,
+    })
+}
+
+fn modified_secs(metadata: &std::fs::Metadata) -> i64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Performs the filesystem side of an operation; also used to redo undone ones
+pub(crate) fn apply_operation(operation: &Operation) -> Result<(), AppError> {
+    let destination = || {
+        operation.destination_path.as_ref().ok_or_else(|| {
+            AppError::OperationFailed("Operation has no destination path".to_string())
+        })
     };
 
-    conn.execute(
-        "INSERT INTO operations (id, batch_id, operation_type, source_path, destination_path, original_name, new_name, timestamp, status, backup_path)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-        (
-            &operation.id,
-            &operation.batch_id,
-            op_type,
-            operation.source_path.to_string_lossy().to_string(),
-            operation.destination_path.as_ref().map(|p| p.to_string_lossy().to_string()),
-            &operation.original_name,
-            &operation.new_name,
-            operation.timestamp,
-            status,
-            operation.backup_path.as_ref().map(|p| p.to_string_lossy().to_string()),
-        ),
-    )?;
+    // Planned destinations may be folders that don't exist yet
+    if let Some(parent) = operation.destination_path.as_ref().and_then(|d| d.parent()) {
+        if operation.operation_type != OperationType::Delete
+            && !operation.operation_type.replaces_with_link()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+
+    match operation.operation_type {
+        OperationType::Move | OperationType::Rename => {
+            std::fs::rename(&operation.source_path, destination()?).map_err(AppError::Io)
+        }
+        OperationType::Copy => {
+            copy_entry(&operation.source_path, destination()?).map_err(AppError::Io)
+        }
+        OperationType::Delete => {
+            // Move to trash instead of permanent delete
+            if let Some(backup) = &operation.backup_path {
+                trash::move_to_trash(&operation.source_path, backup).map_err(AppError::Io)
+            } else {
+                std::fs::remove_file(&operation.source_path).map_err(AppError::Io)
+            }
+        }
+        OperationType::Extract => {
+            archive::extract(&operation.source_path, destination()?).map_err(AppError::Io)
+        }
+        OperationType::Hardlink | OperationType::Symlink => {
+            let backup = operation.backup_path.as_ref().ok_or_else(|| {
+                AppError::OperationFailed("Operation has no backup path".to_string())
+            })?;
+            replace_with_link(operation, destination()?, backup).map_err(AppError::Io)
+        }
+    }
+}
+
+/// Moves the source to the trash and puts a link to `target` in its place.
+/// If the link can't be made, for instance a hard link across filesystems,
+/// the file is put back.
+fn replace_with_link(operation: &Operation, target: &Path, backup: &Path) -> std::io::Result<()> {
+    let source = &operation.source_path;
+    if !target.is_file() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Link target is not a file: {}", target.display()),
+        ));
+    }
+
+    trash::move_to_trash(source, backup)?;
+
+    let linked = match operation.operation_type {
+        OperationType::Hardlink => std::fs::hard_link(target, source),
+        _ => symlink_file(target, source),
+    };
+    if let Err(e) = linked {
+        let _ = trash::restore_from_trash(backup, source);
+        return Err(e);
+    }
 
     Ok(())
 }
 
+#[cfg(unix)]
+fn symlink_file(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink_file(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+/// Whether `path` is still the link a hardlink or symlink operation put
+/// there, pointing at `target`
+pub(crate) fn is_link_to(path: &Path, target: &Path, operation_type: &OperationType) -> bool {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return false;
+    };
+
+    match operation_type {
+        OperationType::Symlink => {
+            metadata.file_type().is_symlink()
+                && std::fs::read_link(path).is_ok_and(|link| link == target)
+        }
+        _ => {
+            metadata.is_file()
+                && std::fs::metadata(target)
+                    .is_ok_and(|target| duplicates::same_file(&metadata, &target))
+        }
+    }
+}
+
+/// Copies a file. A symlink is copied as a new link to the same target, never
+/// as a copy of what it points at; relative targets are resolved first so the
+/// new link still points at the same place.
+fn copy_entry(source: &Path, destination: &Path) -> std::io::Result<()> {
+    if !std::fs::symlink_metadata(source)?.file_type().is_symlink() {
+        return std::fs::copy(source, destination).map(|_| ());
+    }
+
+    let mut target = std::fs::read_link(source)?;
+    if target.is_relative() {
+        if let Some(parent) = source.parent() {
+            target = parent.join(target);
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&target, destination)
+    }
+
+    #[cfg(windows)]
+    {
+        if target.is_dir() {
+            std::os::windows::fs::symlink_dir(&target, destination)
+        } else {
+            std::os::windows::fs::symlink_file(&target, destination)
+        }
+    }
+}
+
+/// Deepest directory that contains every source file
+fn common_root(operations: &[PlannedOperation]) -> Option<PathBuf> {
+    let mut parents = operations.iter().filter_map(|op| op.source.parent());
+    let mut root = parents.next()?.to_path_buf();
+
+    for parent in parents {
+        while !parent.starts_with(&root) {
+            if !root.pop() {
+                return None;
+            }
+        }
+    }
+
+    Some(root)
+}
+
+/// Fallback description such as "3 move, 1 delete"
+fn describe_operations(operations: &[PlannedOperation]) -> String {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+
+    for op in operations {
+        let name = op.operation_type.as_str();
+        match counts.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((name, 1)),
+        }
+    }
+
+    counts
+        .iter()
+        .map(|(name, count)| format!("{} {}", count, name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[tauri::command]
 pub async fn execute_operations(
     app: tauri::AppHandle,
+    storage: State<'_, Storage>,
     operations: Vec<PlannedOperation>,
+    description: Option<String>,
+    source: Option<BatchSource>,
     on_progress: Channel<OperationProgress>,
 ) -> Result<Vec<Operation>, AppError> {
+    let storage = storage.inner().clone();
+    let trash_target = trash_target(&app, &storage);
+
+    tokio::task::spawn_blocking(move || {
+        run_operations(
+            &storage,
+            &trash_target,
+            operations,
+            description,
+            source,
+            &on_progress,
+        )
+    })
+    .await
+    .map_err(|e| AppError::OperationFailed(e.to_string()))?
+}
+
+/// Moves the index record of a file that `op` moved or renamed from `from`
+/// to `to`, so the file keeps its ID
+pub(crate) fn follow_in_index(conn: &Connection, op: &Operation, from: &Path, to: &Path) {
+    if !matches!(
+        op.operation_type,
+        OperationType::Move | OperationType::Rename
+    ) {
+        return;
+    }
+    let Ok(metadata) = std::fs::symlink_metadata(to) else {
+        return;
+    };
+
+    if let Err(e) = file_index::rename(conn, from, to, &FileStamp::of(&metadata)) {
+        eprintln!("Failed to update the file index: {:?}", e);
+    }
+}
+
+/// Journals and applies a batch, one operation after the other
+fn run_operations(
+    storage: &Storage,
+    trash_target: &trash::TrashTarget,
+    operations: Vec<PlannedOperation>,
+    description: Option<String>,
+    source: Option<BatchSource>,
+    on_progress: &Channel<OperationProgress>,
+) -> Result<Vec<Operation>, AppError> {
+    let conn = storage.conn()?;
     let total = operations.len();
     let mut results = Vec::new();
     let batch_id = Uuid::new_v4().to_string();
 
+    let batch = OperationBatch {
+        id: batch_id.clone(),
+        operations: Vec::new(),
+        created_at: Utc::now().timestamp(),
+        description: description
+            .filter(|d| !d.trim().is_empty())
+            .unwrap_or_else(|| describe_operations(&operations)),
+        source: source.unwrap_or_default(),
+        root_folder: common_root(&operations),
+        operation_count: 0,
+        failed_count: 0,
+        total_bytes: operations
+            .iter()
+            .filter_map(|op| std::fs::metadata(&op.source).ok())
+            .map(|m| m.len())
+            .sum(),
+    };
+
+    if let Err(e) = batches::insert(&conn, &batch) {
+        eprintln!("Failed to save batch to database: {:?}", e);
+    }
+
     for (index, planned) in operations.into_iter().enumerate() {
-        let _ = on_progress.send(OperationProgress {
-            event: "processing".to_string(),
-            current_file: Some(planned.file_name.clone()),
-            completed_count: index,
-            total_count: total,
-            percentage: (index as f32 / total as f32) * 100.0,
-        });
+        let mut event = OperationProgress::new("processing", index, total);
+        event.current_file = Some(planned.file_name.clone());
+        let _ = on_progress.send(event);
 
-        // For delete operations, create backup first
-        let backup_path = if matches!(planned.operation_type, OperationType::Delete) {
-            let trash_dir = get_trash_dir(&app);
-            let timestamp = Utc::now().timestamp_millis();
-            let backup_name = format!("{}_{}", timestamp, planned.file_name);
-            Some(trash_dir.join(backup_name))
+        // Deleted and replaced files go to the trash, so they can be restored
+        let backup_path = if planned.operation_type == OperationType::Delete
+            || planned.operation_type.replaces_with_link()
+        {
+            Some(trash_target.backup_path_for(&planned.source))
         } else {
             None
         };
 
-        let result = match planned.operation_type {
-            OperationType::Move => {
-                std::fs::rename(&planned.source, &planned.destination).map_err(AppError::Io)
-            }
-            OperationType::Copy => std::fs::copy(&planned.source, &planned.destination)
-                .map(|_| ())
-                .map_err(AppError::Io),
-            OperationType::Rename => {
-                std::fs::rename(&planned.source, &planned.destination).map_err(AppError::Io)
-            }
-            OperationType::Delete => {
-                // Move to trash instead of permanent delete
-                if let Some(ref backup) = backup_path {
-                    std::fs::rename(&planned.source, backup).map_err(AppError::Io)
-                } else {
-                    std::fs::remove_file(&planned.source).map_err(AppError::Io)
-                }
-            }
-        };
-
         let destination_path = match planned.operation_type {
             OperationType::Delete => None,
             _ => Some(planned.destination),
         };
 
-        let operation = Operation {
+        let mut operation = Operation {
             id: Uuid::new_v4().to_string(),
             operation_type: planned.operation_type,
             source_path: planned.source,
@@ -141,96 +430,426 @@ pub async fn execute_operations(
             original_name: Some(planned.file_name.clone()),
             new_name: None,
             timestamp: Utc::now().timestamp(),
-            status: match &result {
-                Ok(_) => OperationStatus::Completed,
-                Err(e) => OperationStatus::Failed(e.to_string()),
-            },
+            status: OperationStatus::InProgress,
             batch_id: Some(batch_id.clone()),
             backup_path,
+            fingerprint: None,
+            error_code: None,
+            purged: false,
+        };
+
+        // Write-ahead: the journal entry must exist before the filesystem is
+        // touched, otherwise a crash would leave a change that can't be undone
+        if let Err(e) = operations::insert(&conn, &operation) {
+            eprintln!("Failed to journal operation, skipping: {:?}", e);
+            operation.status = OperationStatus::Failed(format!("Journal write failed: {}", e));
+            operation.error_code = Some("journal".to_string());
+            results.push(operation);
+            continue;
+        }
+
+        operation.status = match apply_operation(&operation) {
+            Ok(_) => {
+                operation.fingerprint = result_fingerprint(&operation);
+                if let Some(destination) = &operation.destination_path {
+                    follow_in_index(&conn, &operation, &operation.source_path, destination);
+                }
+                OperationStatus::Completed
+            }
+            Err(e) => {
+                operation.error_code = Some(e.code().to_string());
+                OperationStatus::Failed(e.to_string())
+            }
         };
 
-        // Save to database
-        if let Err(e) = save_operation_to_db(&app, &operation) {
+        // Finalize the journal entry
+        if let Err(e) = operations::update_result(&conn, &operation) {
             eprintln!("Failed to save operation to database: {:?}", e);
         }
 
         results.push(operation);
     }
 
-    let _ = on_progress.send(OperationProgress {
-        event: "completed".to_string(),
-        current_file: None,
-        completed_count: total,
-        total_count: total,
-        percentage: 100.0,
-    });
+    let failed_count = results
+        .iter()
+        .filter(|op| matches!(op.status, OperationStatus::Failed(_)))
+        .count();
+    if let Err(e) = batches::update_counts(&conn, &batch_id, results.len(), failed_count) {
+        eprintln!("Failed to update batch counts: {:?}", e);
+    }
 
-    Ok(results)
-}
+    // A new batch goes on top of the undo stack and invalidates pending redos
+    if results
+        .iter()
+        .any(|op| op.status == OperationStatus::Completed)
+    {
+        if let Err(e) = batches::push_undo_stack(&conn, &batch_id) {
+            eprintln!("Failed to update undo stack: {:?}", e);
+        }
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DuplicateGroup {
-    pub hash: String,
-    pub files: Vec<PathBuf>,
-    pub size: u64,
+    let _ = on_progress.send(OperationProgress::new("completed", total, total));
+
+    Ok(results)
 }
 
+/// Groups files with identical content, reading them on several threads.
+/// Hashes already in the file index are reused, and new ones are saved to it,
+/// so they show up in `FileItem::hash` on later scans. Progress events carry
+/// the `task_id` for `cancel_find_duplicates`; a cancelled search fails, but
+/// keeps the hashes it finished.
 #[tauri::command]
 pub async fn find_duplicates(
+    searches: State<'_, DuplicateSearches>,
+    storage: State<'_, Storage>,
     files: Vec<crate::models::FileItem>,
+    options: Option<DuplicateOptions>,
     on_progress: Channel<OperationProgress>,
 ) -> Result<Vec<DuplicateGroup>, AppError> {
-    use sha2::{Digest, Sha256};
-    use std::collections::HashMap;
-    use std::io::Read;
+    let options = options.unwrap_or_default();
+    let storage = storage.inner().clone();
+    let task_id = Uuid::new_v4().to_string();
+    let cancel = Arc::new(AtomicBool::new(false));
+
+    searches
+        .running
+        .lock()
+        .unwrap()
+        .insert(task_id.clone(), cancel.clone());
+
+    let mut started = OperationProgress::new("started", 0, files.len());
+    started.task_id = Some(task_id.clone());
+    let _ = on_progress.send(started);
+
+    let id = task_id.clone();
+    let result = tokio::task::spawn_blocking(move || -> Result<_, AppError> {
+        let paths: Vec<PathBuf> = files.into_iter().map(|f| f.path).collect();
+        let mut candidates = duplicates::candidates(&paths);
+
+        let mut conn = storage.conn()?;
+        for candidate in &mut candidates {
+            candidate.hash = file_index::stored_hash(&conn, &candidate.path, &candidate.stamp)?;
+        }
+
+        let found = duplicates::find(candidates, &options, &cancel, |progress| {
+            let mut event = OperationProgress::new(
+                progress.stage.as_str(),
+                progress.files_done,
+                progress.files_total,
+            );
+            event.current_file = progress
+                .current_file
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().to_string());
+            event.percentage = percentage(progress.bytes_done, progress.bytes_total);
+            event.task_id = Some(id.clone());
+            event.processed_bytes = Some(progress.bytes_done);
+            event.total_bytes = Some(progress.bytes_total);
+            let _ = on_progress.send(event);
+        });
+
+        let hashes: Vec<(&Path, FileStamp, &str)> = found
+            .hashed
+            .iter()
+            .filter_map(|c| Some((c.path.as_path(), c.stamp, c.hash.as_deref()?)))
+            .collect();
+        file_index::save_hashes(&mut conn, &hashes)?;
+
+        if found.cancelled {
+            let mut event = OperationProgress::new("cancelled", 0, paths.len());
+            event.task_id = Some(id.clone());
+            let _ = on_progress.send(event);
+            return Err(AppError::OperationFailed(format!(
+                "Duplicate search {} was cancelled",
+                id
+            )));
+        }
+
+        let mut event = OperationProgress::new("completed", paths.len(), paths.len());
+        event.task_id = Some(id);
+        let _ = on_progress.send(event);
+
+        Ok(found.groups)
+    })
+    .await;
+
+    searches.running.lock().unwrap().remove(&task_id);
+
+    result.map_err(|e| AppError::OperationFailed(e.to_string()))?
+}
+
+/// Stops a running duplicate search; returns false if no search with that
+/// ID is running
+#[tauri::command]
+pub async fn cancel_find_duplicates(
+    searches: State<'_, DuplicateSearches>,
+    task_id: String,
+) -> Result<bool, AppError> {
+    match searches.running.lock().unwrap().get(&task_id) {
+        Some(cancel) => {
+            cancel.store(true, Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Plans keeping one copy in each duplicate group, chosen by `strategy`, and
+/// deleting the others or replacing them with hard or symbolic links to it.
+/// Copies that are gone, aren't regular files any more or no longer hash to
+/// the group's hash are left alone, and so are groups where the strategy
+/// finds no copy to keep. Run through `execute_operations`, the replaced
+/// copies go to the trash and every step can be undone.
+#[tauri::command]
+pub async fn resolve_duplicates(
+    app: tauri::AppHandle,
+    storage: State<'_, Storage>,
+    groups: Vec<DuplicateGroup>,
+    strategy: KeepStrategy,
+    action: DuplicateAction,
+) -> Result<Vec<PlannedOperation>, AppError> {
+    let category_folders: Vec<PathBuf> = match strategy {
+        KeepStrategy::CategoryFolder => load_categories(&app, &storage)?
+            .into_iter()
+            .map(|category| category.target_folder)
+            .collect(),
+        _ => Vec::new(),
+    };
+    let storage = storage.inner().clone();
+
+    tokio::task::spawn_blocking(move || {
+        plan_resolutions(&storage, groups, &strategy, &action, &category_folders)
+    })
+    .await
+    .map_err(|e| AppError::OperationFailed(e.to_string()))?
+}
 
-    let total = files.len();
-    let mut hash_map: HashMap<String, Vec<PathBuf>> = HashMap::new();
+fn plan_resolutions(
+    storage: &Storage,
+    groups: Vec<DuplicateGroup>,
+    strategy: &KeepStrategy,
+    action: &DuplicateAction,
+    category_folders: &[PathBuf],
+) -> Result<Vec<PlannedOperation>, AppError> {
+    let conn = storage.conn()?;
+    let mut planned = Vec::new();
 
-    for (index, file) in files.iter().enumerate() {
-        let _ = on_progress.send(OperationProgress {
-            event: "hashing".to_string(),
-            current_file: Some(file.name.clone()),
-            completed_count: index,
-            total_count: total,
-            percentage: (index as f32 / total as f32) * 100.0,
+    for group in groups {
+        let mut copies = Vec::new();
+        for copy in duplicates::candidates(&group.files) {
+            if copy.stamp.size != group.size {
+                continue;
+            }
+            let hash = match file_index::stored_hash(&conn, &copy.path, &copy.stamp)? {
+                Some(hash) => Some(hash),
+                // Not indexed, or changed since it was hashed
+                None => duplicates::full_hash(&copy.path, &copy.stamp)
+                    .ok()
+                    .flatten(),
+            };
+            if hash.as_deref() == Some(group.hash.as_str()) {
+                copies.push(copy);
+            }
+        }
+        if copies.len() < 2 {
+            continue;
+        }
+        let Some(keep) = duplicates::keeper(&copies, strategy, category_folders) else {
+            continue;
+        };
+
+        for (index, copy) in copies.iter().enumerate() {
+            if index == keep {
+                continue;
+            }
+
+            let indexed = file_index::find(&conn, &copy.path)?;
+            planned.push(PlannedOperation {
+                file_id: indexed
+                    .as_ref()
+                    .map(|file| file.item.id.clone())
+                    .unwrap_or_else(|| Uuid::new_v4().to_string()),
+                file_name: copy
+                    .path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                operation_type: action.operation_type(),
+                source: copy.path.clone(),
+                destination: copies[keep].path.clone(),
+                category: indexed.and_then(|file| file.item.category),
+            });
+        }
+    }
+
+    Ok(planned)
+}
+
+/// Renames giving each file its `suggested_extension`, or dropping the wrong
+/// extension when the name already ends in the right one before it. Files
+/// whose new name is already taken, on disk or by another file in the plan,
+/// are left out.
+#[tauri::command]
+pub async fn plan_extension_fixes(
+    files: Vec<crate::models::FileItem>,
+) -> Result<Vec<PlannedOperation>, AppError> {
+    use std::collections::HashSet;
+
+    let mut taken = HashSet::new();
+    let mut planned = Vec::new();
+
+    for file in files {
+        let Some(extension) = &file.suggested_extension else {
+            continue;
+        };
+
+        let destination = file_type::fixed_path(&file.path, extension);
+        if destination.exists() || !taken.insert(destination.clone()) {
+            continue;
+        }
+
+        planned.push(PlannedOperation {
+            file_id: file.id,
+            file_name: file.name,
+            operation_type: OperationType::Rename,
+            source: file.path,
+            destination,
+            category: file.category,
         });
+    }
+
+    Ok(planned)
+}
 
-        // Calculate hash
-        if let Ok(mut f) = std::fs::File::open(&file.path) {
-            let mut hasher = Sha256::new();
-            let mut buffer = [0u8; 8192];
+/// Plans moving (or copying) `files` into `root` along a destination
+/// template such as `Photos/{year}/{month}`. Files keep their names unless
+/// the template's last part names them, as in `{name}.{ext}`. Renames apply
+/// the template within each file's own folder, so it must name the file.
+/// Extractions unpack each archive into a folder named after it, inside the
+/// template's folders under `root` or else beside the archive; files that
+/// aren't archives that can be listed are left out. A destination that's
+/// already taken gets a ` (2)`, ` (3)`... suffix.
+#[tauri::command]
+pub async fn plan_organize(
+    files: Vec<crate::models::FileItem>,
+    root: Option<PathBuf>,
+    template: String,
+    operation_type: Option<OperationType>,
+) -> Result<Vec<PlannedOperation>, AppError> {
+    use std::collections::HashSet;
+
+    let operation_type = operation_type.unwrap_or(OperationType::Move);
+    let template = Template::parse(&template)?;
+
+    let root = match (&operation_type, root) {
+        (OperationType::Rename, _) if !template.names_file() => {
+            return Err(AppError::Config(
+                "A rename template must name the file, e.g. {title}.{ext}".to_string(),
+            ));
+        }
+        (OperationType::Rename, _) => None,
+        (OperationType::Move | OperationType::Copy, Some(root)) => Some(root),
+        (OperationType::Move | OperationType::Copy, None) => {
+            return Err(AppError::Config(
+                "A destination folder is required".to_string(),
+            ));
+        }
+        (OperationType::Extract, root) => root,
+        (OperationType::Delete | OperationType::Hardlink | OperationType::Symlink, _) => {
+            return Err(AppError::OperationFailed(format!(
+                "Can't organize files with a {} operation",
+                operation_type.as_str()
+            )));
+        }
+    };
+
+    let mut taken = HashSet::new();
+    let mut planned = Vec::new();
 
-            loop {
-                match f.read(&mut buffer) {
-                    Ok(0) => break,
-                    Ok(n) => hasher.update(&buffer[..n]),
-                    Err(_) => break,
+    for file in files {
+        let relative = template.render(&file);
+        let destination = match &root {
+            _ if operation_type == OperationType::Extract => {
+                let is_archive = match &file.metadata.archive {
+                    Some(_) => true,
+                    // Without extracted metadata, the content is sniffed
+                    None => {
+                        file.metadata.mime_type.is_none() && archive::detect(&file.path).is_some()
+                    }
+                };
+                if !is_archive {
+                    continue;
                 }
+                let base = root.as_deref().or(file.path.parent());
+                base.unwrap_or(Path::new(""))
+                    .join(relative)
+                    .join(extraction_folder_name(&file.name))
             }
+            Some(root) if template.names_file() => root.join(relative),
+            Some(root) => root.join(relative).join(&file.name),
+            None => file.path.parent().unwrap_or(Path::new("")).join(relative),
+        };
+
+        // Already where the template puts it
+        if destination == file.path {
+            continue;
+        }
 
-            let hash = format!("{:x}", hasher.finalize());
-            hash_map.entry(hash).or_default().push(file.path.clone());
+        let destination = available_path(destination, &taken);
+        taken.insert(destination.clone());
+
+        planned.push(PlannedOperation {
+            file_id: file.id,
+            file_name: file.name,
+            operation_type: operation_type.clone(),
+            source: file.path,
+            destination,
+            category: file.category,
+        });
+    }
+
+    Ok(planned)
+}
+
+/// The archive's name without its extension, `.tar.gz` and the like
+/// included
+fn extraction_folder_name(archive_name: &str) -> String {
+    for suffix in [".tar.gz", ".tar.bz2", ".tar.xz"] {
+        let split = archive_name.len().saturating_sub(suffix.len());
+        if let (Some(stem), Some(end)) = (archive_name.get(..split), archive_name.get(split..)) {
+            if !stem.is_empty() && end.eq_ignore_ascii_case(suffix) {
+                return stem.to_string();
+            }
         }
     }
 
-    // Filter to only groups with duplicates
-    let duplicates: Vec<DuplicateGroup> = hash_map
-        .into_iter()
-        .filter(|(_, paths)| paths.len() > 1)
-        .map(|(hash, files)| {
-            let size = std::fs::metadata(&files[0]).map(|m| m.len()).unwrap_or(0);
-            DuplicateGroup { hash, files, size }
-        })
-        .collect();
+    Path::new(archive_name)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| archive_name.to_string())
+}
 
-    let _ = on_progress.send(OperationProgress {
-        event: "completed".to_string(),
-        current_file: None,
-        completed_count: total,
-        total_count: total,
-        percentage: 100.0,
-    });
+/// `path`, or the first of `name (2).ext`, `name (3).ext`... that is neither
+/// on disk nor in `taken`
+fn available_path(path: PathBuf, taken: &std::collections::HashSet<PathBuf>) -> PathBuf {
+    if !path.exists() && !taken.contains(&path) {
+        return path;
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().to_string());
 
-    Ok(duplicates)
+    (2..)
+        .map(|n| {
+            let name = match &extension {
+                Some(ext) => format!("{} ({}).{}", stem, n, ext),
+                None => format!("{} ({})", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !candidate.exists() && !taken.contains(candidate))
+        .expect("an unused name")
 }
@@ -1,234 +1,911 @@
+use crate::commands::file_ops;
+use crate::commands::trash::trash_target;
 use crate::error::AppError;
-use crate::models::{Operation, OperationBatch, OperationStatus, OperationType};
-use std::path::PathBuf;
-use tauri::Manager;
-
-fn get_db_path(app: &tauri::AppHandle) -> PathBuf {
-    app.path()
-        .app_data_dir()
-        .unwrap_or_else(|_| PathBuf::from("."))
-        .join("fileog.db")
-}
+use crate::models::{
+    FileFingerprint, HistoryFilter, Operation, OperationBatch, OperationStatus, OperationType,
+};
+use crate::services::archive;
+use crate::services::history_export::{self, ExportFormat, PathRemap};
+use crate::services::trash::{self, TrashTarget};
+use crate::storage::trashed_copies::{self, TrashedCopy};
+use crate::storage::{batches, operations, Storage};
+use chrono::Utc;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::State;
 
 #[tauri::command]
 pub async fn get_operation_history(
-    app: tauri::AppHandle,
+    storage: State<'_, Storage>,
     limit: Option<usize>,
+    offset: Option<usize>,
+    filter: Option<HistoryFilter>,
 ) -> Result<Vec<OperationBatch>, AppError> {
-    use rusqlite::Connection;
-
-    let db_path = get_db_path(&app);
-
-    if !db_path.exists() {
-        return Ok(Vec::new());
-    }
-
-    let conn = Connection::open(&db_path)?;
+    let conn = storage.conn()?;
 
     let limit = limit.unwrap_or(50);
+    let offset = offset.unwrap_or(0);
+    let filter = filter.unwrap_or_default();
 
-    let mut stmt = conn.prepare(
-        "SELECT id, batch_id, operation_type, source_path, destination_path, 
-                original_name, new_name, timestamp, status, backup_path 
-         FROM operations 
-         ORDER BY timestamp DESC 
-         LIMIT ?",
-    )?;
-
-    let operations: Vec<Operation> = stmt
-        .query_map([limit], |row| {
-            Ok(Operation {
-                id: row.get(0)?,
-                operation_type: match row.get::<_, String>(2)?.as_str() {
-                    "move" => OperationType::Move,
-                    "copy" => OperationType::Copy,
-                    "rename" => OperationType::Rename,
-                    "delete" => OperationType::Delete,
-                    _ => OperationType::Move,
-                },
-                source_path: PathBuf::from(row.get::<_, String>(3)?),
-                destination_path: row.get::<_, Option<String>>(4)?.map(PathBuf::from),
-                original_name: row.get(5)?,
-                new_name: row.get(6)?,
-                timestamp: row.get(7)?,
-                status: OperationStatus::Completed,
-                batch_id: row.get(1)?,
-                backup_path: row.get::<_, Option<String>>(9)?.map(PathBuf::from),
-            })
-        })?
-        .filter_map(|r| r.ok())
-        .collect();
-
-    // Group by batch_id
-    use std::collections::HashMap;
-    let mut batches: HashMap<String, Vec<Operation>> = HashMap::new();
-
-    for op in operations {
-        let batch_id = op.batch_id.clone().unwrap_or_else(|| op.id.clone());
-        batches.entry(batch_id).or_default().push(op);
-    }
-
-    let result: Vec<OperationBatch> = batches
-        .into_iter()
-        .map(|(id, ops)| {
-            let timestamp = ops.first().map(|o| o.timestamp).unwrap_or(0);
-            OperationBatch {
-                id,
-                operations: ops,
-                created_at: timestamp,
-                description: String::new(),
-            }
-        })
-        .collect();
+    let mut batches = batches::query(&conn, &filter, limit, offset)?;
+
+    for batch in &mut batches {
+        batch.operations = operations::list_by_batch(&conn, &batch.id, None, false)?;
+    }
 
-    Ok(result)
+    Ok(batches)
 }
 
 #[tauri::command]
 pub async fn undo_operations(
     app: tauri::AppHandle,
+    storage: State<'_, Storage>,
     steps: usize,
-) -> Result<Vec<Operation>, AppError> {
-    use rusqlite::Connection;
+) -> Result<Vec<OperationUndoResult>, AppError> {
+    let conn = storage.conn()?;
+    let target = trash_target(&app, &storage);
 
-    let db_path = get_db_path(&app);
+    // Get the most recent completed operations to undo
+    let mut results = Vec::new();
 
-    if !db_path.exists() {
-        return Ok(Vec::new());
+    for op in operations::list_recent_completed(&conn, steps)? {
+        let batch_id = op.batch_id.clone();
+        results.push(undo_operation(&conn, &target, op)?);
+        if let Some(batch_id) = batch_id {
+            settle_undone_batch(&conn, &batch_id)?;
+        }
     }
 
-    let conn = Connection::open(&db_path)?;
+    Ok(results)
+}
+
+/// Validates and undoes a single operation, marking it undone on success
+pub(crate) fn undo_operation(
+    conn: &Connection,
+    trash_target: &TrashTarget,
+    mut op: Operation,
+) -> Result<OperationUndoResult, AppError> {
+    if op.purged {
+        let error = "The trashed original was permanently deleted".to_string();
+        return Ok(OperationUndoResult::failed(op, error));
+    }
 
-    // Get the most recent completed operations to undo
-    let mut stmt = conn.prepare(
-        "SELECT id, batch_id, operation_type, source_path, destination_path,
-                original_name, new_name, timestamp, status, backup_path
-         FROM operations
-         WHERE status = 'completed'
-         ORDER BY timestamp DESC
-         LIMIT ?",
-    )?;
-
-    let operations: Vec<Operation> = stmt
-        .query_map([steps], |row| {
-            Ok(Operation {
-                id: row.get(0)?,
-                operation_type: match row.get::<_, String>(2)?.as_str() {
-                    "move" => OperationType::Move,
-                    "copy" => OperationType::Copy,
-                    "rename" => OperationType::Rename,
-                    "delete" => OperationType::Delete,
-                    _ => OperationType::Move,
-                },
-                source_path: PathBuf::from(row.get::<_, String>(3)?),
-                destination_path: row.get::<_, Option<String>>(4)?.map(PathBuf::from),
-                original_name: row.get(5)?,
-                new_name: row.get(6)?,
-                timestamp: row.get(7)?,
-                status: OperationStatus::Completed,
-                batch_id: row.get(1)?,
-                backup_path: row.get::<_, Option<String>>(9)?.map(PathBuf::from),
+    if let Some(later) = operations::find_dependent(conn, &op)? {
+        let error = format!(
+            "File was changed again by batch {}, undo that batch first",
+            later.batch_id.unwrap_or(later.id)
+        );
+        return Ok(OperationUndoResult::failed(op, error));
+    }
+
+    if let Some(conflict) = check_undo_conflict(&op) {
+        return Ok(OperationUndoResult {
+            operation: op,
+            success: false,
+            error: Some(conflict.message.clone()),
+            conflict: Some(conflict),
+            copy_action: None,
+        });
+    }
+
+    let copy_action = match revert_operation(&op, trash_target) {
+        Ok(action) => action,
+        Err(e) => return Ok(OperationUndoResult::failed(op, e.to_string())),
+    };
+
+    // Keep track of a trashed copy so it shows up in the trash and can be restored
+    if let (Some(CopyUndoAction::Trashed { trash_path, .. }), Some(destination)) =
+        (&copy_action, &op.destination_path)
+    {
+        trashed_copies::insert(
+            conn,
+            &TrashedCopy {
+                trash_path: trash_path.clone(),
+                operation_id: op.id.clone(),
+                original_path: destination.clone(),
+                trashed_at: Utc::now().timestamp(),
+            },
+        )?;
+    }
+
+    if let Some(destination) = &op.destination_path {
+        file_ops::follow_in_index(conn, &op, destination, &op.source_path);
+    }
+
+    // Mark as undone in database
+    op.status = OperationStatus::Undone;
+    operations::set_status(conn, &op.id, &op.status)?;
+
+    Ok(OperationUndoResult {
+        operation: op,
+        success: true,
+        error: None,
+        conflict: None,
+        copy_action,
+    })
+}
+
+/// Checks that undoing `op` won't clobber anything: the file it produced must
+/// be unchanged since, and its original location must still be free
+fn check_undo_conflict(op: &Operation) -> Option<UndoConflict> {
+    if op.operation_type.replaces_with_link() {
+        return check_link_conflict(op);
+    }
+
+    let result_path = match op.result_path() {
+        Some(path) => path,
+        None if op.operation_type == OperationType::Delete => {
+            return Some(UndoConflict {
+                kind: ConflictKind::BackupMissing,
+                path: op.source_path.clone(),
+                message: "Deleted file has no backup".to_string(),
+                expected: None,
+                actual: None,
             })
-        })?
-        .filter_map(|r| r.ok())
-        .collect();
-
-    let mut undone = Vec::new();
-
-    for op in operations {
-        let result = match op.operation_type {
-            OperationType::Move => {
-                // Move back: dest -> source
-                if let Some(dest) = &op.destination_path {
-                    std::fs::rename(dest, &op.source_path)
-                } else {
-                    continue;
-                }
+        }
+        None => return None,
+    };
+
+    if !result_path.exists() {
+        let kind = match op.operation_type {
+            OperationType::Delete => ConflictKind::BackupMissing,
+            _ => ConflictKind::DestinationMissing,
+        };
+        return Some(UndoConflict {
+            kind,
+            path: result_path.clone(),
+            message: format!("File no longer exists: {}", result_path.display()),
+            expected: op.fingerprint.clone(),
+            actual: None,
+        });
+    }
+
+    // Undoing a copy or an extraction never writes to the source
+    if !op.operation_type.keeps_source() && op.source_path.exists() {
+        return Some(UndoConflict {
+            kind: ConflictKind::SourceOccupied,
+            path: op.source_path.clone(),
+            message: format!(
+                "Original location is occupied: {}",
+                op.source_path.display()
+            ),
+            expected: None,
+            actual: None,
+        });
+    }
+
+    // An edited copy or extraction isn't a conflict: undo moves it to the
+    // trash instead
+    if let Some(expected) = op
+        .fingerprint
+        .as_ref()
+        .filter(|_| !op.operation_type.keeps_source())
+    {
+        let actual = file_ops::fingerprint_file(result_path, expected.hash.is_some());
+        if !fingerprint_matches(expected, actual.as_ref()) {
+            return Some(UndoConflict {
+                kind: ConflictKind::DestinationModified,
+                path: result_path.clone(),
+                message: format!(
+                    "File was modified after the operation: {}",
+                    result_path.display()
+                ),
+                expected: Some(expected.clone()),
+                actual,
+            });
+        }
+    }
+
+    None
+}
+
+/// A file replaced by a link can go back while its trash copy is there and
+/// its original location holds either nothing or the same link. Edits made
+/// through the link went to the kept file, so they aren't a conflict.
+fn check_link_conflict(op: &Operation) -> Option<UndoConflict> {
+    let Some(backup) = op.backup_path.as_ref().filter(|b| b.exists()) else {
+        let path = op.backup_path.as_ref().unwrap_or(&op.source_path);
+        return Some(UndoConflict {
+            kind: ConflictKind::BackupMissing,
+            path: path.clone(),
+            message: format!(
+                "Replaced file is no longer in the trash: {}",
+                path.display()
+            ),
+            expected: None,
+            actual: None,
+        });
+    };
+
+    let linked = op
+        .destination_path
+        .as_ref()
+        .is_some_and(|target| file_ops::is_link_to(&op.source_path, target, &op.operation_type));
+    if !linked && std::fs::symlink_metadata(&op.source_path).is_ok() {
+        return Some(UndoConflict {
+            kind: ConflictKind::SourceOccupied,
+            path: op.source_path.clone(),
+            message: format!(
+                "Original location no longer holds the link: {}",
+                op.source_path.display()
+            ),
+            expected: None,
+            actual: file_ops::fingerprint_file(backup, false),
+        });
+    }
+
+    None
+}
+
+/// Checks that redoing `op` finds things the way its undo left them: the
+/// source unchanged since and the destination still free, or for a link
+/// still there to link to
+fn check_redo_conflict(op: &Operation) -> Option<UndoConflict> {
+    if std::fs::symlink_metadata(&op.source_path).is_err() {
+        return Some(UndoConflict {
+            kind: ConflictKind::SourceMissing,
+            path: op.source_path.clone(),
+            message: format!("File no longer exists: {}", op.source_path.display()),
+            expected: op.fingerprint.clone(),
+            actual: None,
+        });
+    }
+
+    if let Some(destination) = &op.destination_path {
+        let exists = std::fs::symlink_metadata(destination).is_ok();
+        if op.operation_type.replaces_with_link() && !exists {
+            return Some(UndoConflict {
+                kind: ConflictKind::DestinationMissing,
+                path: destination.clone(),
+                message: format!("Link target no longer exists: {}", destination.display()),
+                expected: None,
+                actual: None,
+            });
+        }
+        if !op.operation_type.replaces_with_link() && exists {
+            return Some(UndoConflict {
+                kind: ConflictKind::DestinationOccupied,
+                path: destination.clone(),
+                message: format!("Destination is occupied: {}", destination.display()),
+                expected: None,
+                actual: file_ops::fingerprint_file(destination, false),
+            });
+        }
+    }
+
+    // A link's source is compared with the file it will link to, since its
+    // recorded fingerprint is that file's, and a copy got an mtime of its
+    // own, so both only go by content. An archive can't be compared with the
+    // folder it was extracted to.
+    let (expected, content_only) = match op.operation_type {
+        OperationType::Extract => return None,
+        OperationType::Hardlink | OperationType::Symlink => (
+            op.destination_path
+                .as_ref()
+                .and_then(|target| file_ops::fingerprint_file(target, true)),
+            true,
+        ),
+        OperationType::Copy => (op.fingerprint.clone(), true),
+        _ => (op.fingerprint.clone(), false),
+    };
+    let expected = expected?;
+    let actual = file_ops::fingerprint_file(&op.source_path, expected.hash.is_some());
+    let matches = match &actual {
+        Some(actual) if content_only => {
+            actual.size == expected.size
+                && (expected.hash.is_none()
+                    || actual.hash.is_none()
+                    || actual.hash == expected.hash)
+        }
+        _ => fingerprint_matches(&expected, actual.as_ref()),
+    };
+    if matches {
+        return None;
+    }
+
+    Some(UndoConflict {
+        kind: ConflictKind::SourceModified,
+        path: op.source_path.clone(),
+        message: format!(
+            "File was modified after the undo: {}",
+            op.source_path.display()
+        ),
+        expected: Some(expected),
+        actual,
+    })
+}
+
+/// A touched mtime alone doesn't count as a modification when the content
+/// hash still matches
+fn fingerprint_matches(expected: &FileFingerprint, actual: Option<&FileFingerprint>) -> bool {
+    let Some(actual) = actual else {
+        return false;
+    };
+
+    if expected.size != actual.size {
+        return false;
+    }
+
+    match (&expected.hash, &actual.hash) {
+        (Some(expected_hash), Some(actual_hash)) => expected_hash == actual_hash,
+        _ => expected.modified_at == actual.modified_at,
+    }
+}
+
+/// Reverses the filesystem side of a completed operation
+fn revert_operation(
+    op: &Operation,
+    trash_target: &TrashTarget,
+) -> Result<Option<CopyUndoAction>, AppError> {
+    let destination = || {
+        op.destination_path.as_ref().ok_or_else(|| {
+            AppError::OperationFailed("Operation has no destination path".to_string())
+        })
+    };
+
+    match op.operation_type {
+        // Move back: dest -> source
+        OperationType::Move | OperationType::Rename => {
+            std::fs::rename(destination()?, &op.source_path)?
+        }
+        OperationType::Copy => return undo_copy(op, destination()?, trash_target).map(Some),
+        OperationType::Extract => return undo_extract(op, destination()?, trash_target).map(Some),
+        OperationType::Hardlink | OperationType::Symlink => {
+            let backup = op.backup_path.as_ref().ok_or_else(|| {
+                AppError::OperationFailed("Replaced file has no backup".to_string())
+            })?;
+            if std::fs::symlink_metadata(&op.source_path).is_ok() {
+                std::fs::remove_file(&op.source_path)?;
             }
-            OperationType::Copy => {
-                // Delete the copy
-                if let Some(dest) = &op.destination_path {
-                    std::fs::remove_file(dest)
-                } else {
-                    continue;
-                }
+            trash::restore_from_trash(backup, &op.source_path)?
+        }
+        // Restore from backup if available
+        OperationType::Delete => match &op.backup_path {
+            Some(backup) if backup.exists() => trash::restore_from_trash(backup, &op.source_path)?,
+            Some(backup) => {
+                return Err(AppError::FileNotFound(backup.to_string_lossy().to_string()))
             }
-            OperationType::Rename => {
-                // Rename back: dest -> source
-                if let Some(dest) = &op.destination_path {
-                    std::fs::rename(dest, &op.source_path)
-                } else {
-                    continue;
-                }
+            None => {
+                return Err(AppError::OperationFailed(
+                    "Deleted file has no backup".to_string(),
+                ))
             }
-            OperationType::Delete => {
-                // Restore from backup if available
-                if let Some(backup) = &op.backup_path {
-                    if backup.exists() {
-                        std::fs::rename(backup, &op.source_path)
-                    } else {
-                        continue;
-                    }
-                } else {
-                    continue;
-                }
+        },
+    }
+
+    Ok(None)
+}
+
+/// Deletes a copy only while it is byte-identical to what was copied.
+/// Anything else, including a copy too large to have been hashed, goes to
+/// the trash.
+fn undo_copy(
+    op: &Operation,
+    copy: &Path,
+    trash_target: &TrashTarget,
+) -> Result<CopyUndoAction, AppError> {
+    let recorded_hash = op.fingerprint.as_ref().and_then(|f| f.hash.as_deref());
+
+    let reason = match recorded_hash {
+        Some(expected) => match file_ops::hash_file(copy) {
+            Ok(actual) if actual == expected => {
+                std::fs::remove_file(copy)?;
+                return Ok(CopyUndoAction::Deleted);
             }
-        };
+            Ok(_) => "Copy was modified after the operation",
+            Err(_) => "Copy could not be read to verify it",
+        },
+        None => "No content hash was recorded for the copy",
+    };
+
+    let trash_path = trash_target.backup_path_for(copy);
+    trash::move_to_trash(copy, &trash_path)?;
+
+    Ok(CopyUndoAction::Trashed {
+        trash_path,
+        reason: reason.to_string(),
+    })
+}
+
+/// Deletes an extracted folder only while nothing in it has changed since the
+/// extraction; otherwise it goes to the trash
+fn undo_extract(
+    op: &Operation,
+    folder: &Path,
+    trash_target: &TrashTarget,
+) -> Result<CopyUndoAction, AppError> {
+    let actual = file_ops::fingerprint_file(folder, false);
+    let reason = match &op.fingerprint {
+        Some(expected) if fingerprint_matches(expected, actual.as_ref()) => {
+            std::fs::remove_dir_all(folder)?;
+            return Ok(CopyUndoAction::Deleted);
+        }
+        Some(_) => "Extracted files were changed after the operation",
+        None => "No fingerprint was recorded for the extracted files",
+    };
+
+    let trash_path = trash_target.backup_path_for(folder);
+    trash::move_to_trash(folder, &trash_path)?;
+
+    Ok(CopyUndoAction::Trashed {
+        trash_path,
+        reason: reason.to_string(),
+    })
+}
+
+/// What undoing a copy or an extraction did with what it created
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum CopyUndoAction {
+    /// Still matched the recorded hash, so it was deleted
+    Deleted,
+    /// Edited or unverifiable, so it was kept in the trash
+    Trashed { trash_path: PathBuf, reason: String },
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// The moved/copied file is gone
+    DestinationMissing,
+    /// The file was edited since the operation
+    DestinationModified,
+    /// Something new sits where the file would be restored
+    SourceOccupied,
+    /// The trash copy of a deleted file is gone
+    BackupMissing,
+    /// Redoing would overwrite something new at the destination
+    DestinationOccupied,
+    /// The file to redo the operation on is gone
+    SourceMissing,
+    /// The file to redo the operation on was edited since the undo
+    SourceModified,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UndoConflict {
+    pub kind: ConflictKind,
+    pub path: PathBuf,
+    pub message: String,
+    pub expected: Option<FileFingerprint>,
+    pub actual: Option<FileFingerprint>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OperationUndoResult {
+    pub operation: Operation,
+    pub success: bool,
+    pub error: Option<String>,
+    pub conflict: Option<UndoConflict>,
+    /// Set when undoing a copy or an extraction, saying whether the result
+    /// was deleted or trashed
+    pub copy_action: Option<CopyUndoAction>,
+}
 
-        if result.is_ok() {
-            // Mark as undone in database
-            let _ = conn.execute(
-                "UPDATE operations SET status = 'undone' WHERE id = ?",
-                [&op.id],
-            );
-            undone.push(op);
+impl OperationUndoResult {
+    fn failed(operation: Operation, error: String) -> Self {
+        Self {
+            operation,
+            success: false,
+            error: Some(error),
+            conflict: None,
+            copy_action: None,
         }
     }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchUndoResult {
+    pub batch_id: String,
+    pub results: Vec<OperationUndoResult>,
+}
 
-    Ok(undone)
+/// Batch ids on the undo and redo stacks, most recent first
+#[derive(Debug, Clone, Serialize)]
+pub struct UndoStack {
+    pub undo: Vec<String>,
+    pub redo: Vec<String>,
 }
 
 #[tauri::command]
-pub async fn clear_history(app: tauri::AppHandle) -> Result<(), AppError> {
-    use rusqlite::Connection;
+pub async fn undo_batch(
+    app: tauri::AppHandle,
+    storage: State<'_, Storage>,
+    batch_id: String,
+) -> Result<BatchUndoResult, AppError> {
+    let conn = storage.conn()?;
+    let target = trash_target(&app, &storage);
+
+    // Batches are undone in stack order. One that was never on the stack,
+    // such as an imported batch, relies on the per-operation checks alone.
+    if batches::stack_state(&conn, &batch_id)?.as_deref() == Some(batches::STACK_DONE) {
+        check_stack_top(&conn, batches::STACK_DONE, &batch_id, "undone")?;
+    }
+
+    // Newest first, so a rename followed by a move of the same file unwinds
+    // through the intermediate name
+    let pending: Vec<Operation> =
+        operations::list_by_batch(&conn, &batch_id, Some(&OperationStatus::Completed), true)?
+            .into_iter()
+            .filter(|op| !op.purged)
+            .collect();
+
+    if pending.is_empty() {
+        settle_undone_batch(&conn, &batch_id)?;
+        return Err(AppError::OperationFailed(format!(
+            "Batch {} has nothing to undo",
+            batch_id
+        )));
+    }
 
-    let db_path = get_db_path(&app);
+    let mut results = Vec::new();
 
-    if db_path.exists() {
-        let conn = Connection::open(&db_path)?;
-        conn.execute("DELETE FROM operations", [])?;
+    for op in pending {
+        results.push(undo_operation(&conn, &target, op)?);
     }
 
-    Ok(())
+    // A partly undone batch stays on the undo stack so the rest can be
+    // retried, or skipped with `skip_batch`
+    settle_undone_batch(&conn, &batch_id)?;
+
+    Ok(BatchUndoResult { batch_id, results })
 }
 
-pub fn init_database(app: &tauri::AppHandle) -> Result<(), AppError> {
-    use rusqlite::Connection;
+/// Moves a batch on the undo stack to the redo stack once none of its
+/// operations is left to undo, or off the stacks if none can be redone
+/// either, so it doesn't hold up the batches below it
+pub(crate) fn settle_undone_batch(conn: &Connection, batch_id: &str) -> Result<(), AppError> {
+    if batches::stack_state(conn, batch_id)?.as_deref() != Some(batches::STACK_DONE) {
+        return Ok(());
+    }
 
-    let db_path = get_db_path(app);
+    let remaining = |status| operations::list_by_batch(conn, batch_id, Some(&status), false);
+    if remaining(OperationStatus::Completed)?
+        .iter()
+        .any(|op| !op.purged)
+    {
+        return Ok(());
+    }
 
-    // Ensure parent directory exists
-    if let Some(parent) = db_path.parent() {
-        std::fs::create_dir_all(parent)?;
+    if remaining(OperationStatus::Undone)?.is_empty() {
+        batches::remove_from_stack(conn, batch_id)
+    } else {
+        batches::set_stack_state(conn, batch_id, batches::STACK_UNDONE)
     }
+}
 
-    let conn = Connection::open(&db_path)?;
+/// Takes a batch off the undo or redo stack without undoing or redoing it,
+/// so the batches below it can be reached when one of its operations keeps
+/// conflicting. Its remaining operations can still be undone on their own,
+/// like an imported batch's.
+#[tauri::command]
+pub async fn skip_batch(storage: State<'_, Storage>, batch_id: String) -> Result<(), AppError> {
+    let conn = storage.conn()?;
+
+    if batches::stack_state(&conn, &batch_id)?.is_none() {
+        return Err(AppError::OperationFailed(format!(
+            "Batch {} is not on the undo or redo stack",
+            batch_id
+        )));
+    }
+
+    batches::remove_from_stack(&conn, &batch_id)
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS operations (
-            id TEXT PRIMARY KEY,
-            batch_id TEXT,
-            operation_type TEXT NOT NULL,
-            source_path TEXT NOT NULL,
-            destination_path TEXT,
-            original_name TEXT,
-            new_name TEXT,
-            timestamp INTEGER NOT NULL,
-            status TEXT NOT NULL,
-            backup_path TEXT
-        )",
-        [],
-    )?;
+#[tauri::command]
+pub async fn redo_batch(
+    storage: State<'_, Storage>,
+    batch_id: String,
+) -> Result<BatchUndoResult, AppError> {
+    let conn = storage.conn()?;
+
+    if batches::stack_state(&conn, &batch_id)?.as_deref() != Some(batches::STACK_UNDONE) {
+        return Err(AppError::OperationFailed(format!(
+            "Batch {} is not on the redo stack",
+            batch_id
+        )));
+    }
+    check_stack_top(&conn, batches::STACK_UNDONE, &batch_id, "redone")?;
+
+    // Replay in original order
+    let undone =
+        operations::list_by_batch(&conn, &batch_id, Some(&OperationStatus::Undone), false)?;
+
+    let mut results = Vec::new();
+
+    for mut op in undone {
+        if let Some(conflict) = check_redo_conflict(&op) {
+            results.push(OperationUndoResult {
+                operation: op,
+                success: false,
+                error: Some(conflict.message.clone()),
+                conflict: Some(conflict),
+                copy_action: None,
+            });
+            continue;
+        }
+
+        if let Err(e) = file_ops::apply_operation(&op) {
+            results.push(OperationUndoResult::failed(op, e.to_string()));
+            continue;
+        }
+
+        if let Some(destination) = &op.destination_path {
+            file_ops::follow_in_index(&conn, &op, &op.source_path, destination);
+        }
+
+        // The file is new again, so later undo checks compare against this state
+        op.fingerprint = file_ops::result_fingerprint(&op);
+        op.status = OperationStatus::Completed;
+        operations::update_result(&conn, &op)?;
+
+        results.push(OperationUndoResult {
+            operation: op,
+            success: true,
+            error: None,
+            conflict: None,
+            copy_action: None,
+        });
+    }
+
+    if results.iter().all(|r| r.success) {
+        batches::set_stack_state(&conn, &batch_id, batches::STACK_DONE)?;
+    }
+
+    Ok(BatchUndoResult { batch_id, results })
+}
+
+/// Fails unless `batch_id` is on top of the `state` stack, naming the batch
+/// that has to be `action` first
+fn check_stack_top(
+    conn: &Connection,
+    state: &str,
+    batch_id: &str,
+    action: &str,
+) -> Result<(), AppError> {
+    match batches::list_stack(conn, state)?.first() {
+        Some(top) if top != batch_id => Err(AppError::OperationFailed(format!(
+            "Batch {} must be {} before batch {}",
+            top, action, batch_id
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[tauri::command]
+pub async fn get_undo_stack(storage: State<'_, Storage>) -> Result<UndoStack, AppError> {
+    let conn = storage.conn()?;
+
+    Ok(UndoStack {
+        undo: batches::list_stack(&conn, batches::STACK_DONE)?,
+        redo: batches::list_stack(&conn, batches::STACK_UNDONE)?,
+    })
+}
+
+#[tauri::command]
+pub async fn clear_history(storage: State<'_, Storage>) -> Result<(), AppError> {
+    let conn = storage.conn()?;
+
+    operations::clear(&conn)?;
+    batches::clear(&conn)?;
+    trashed_copies::clear(&conn)?;
 
     Ok(())
 }
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportSummary {
+    pub path: PathBuf,
+    pub batch_count: usize,
+    pub operation_count: usize,
+}
+
+/// Writes every batch matching `filter` to `path` as CSV, JSON Lines or an
+/// HTML report
+#[tauri::command]
+pub async fn export_history(
+    storage: State<'_, Storage>,
+    path: PathBuf,
+    format: ExportFormat,
+    filter: Option<HistoryFilter>,
+) -> Result<ExportSummary, AppError> {
+    let conn = storage.conn()?;
+    let filter = filter.unwrap_or_default();
+
+    let mut batches = batches::query_all(&conn, &filter)?;
+    for batch in &mut batches {
+        batch.operations = operations::list_by_batch(&conn, &batch.id, None, false)?;
+    }
+
+    std::fs::write(&path, history_export::render(format, &batches)?)?;
+
+    Ok(ExportSummary {
+        path,
+        batch_count: batches.len(),
+        operation_count: batches.iter().map(|b| b.operations.len()).sum(),
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportSummary {
+    pub batches_imported: usize,
+    /// Batches already in history, left untouched
+    pub batches_skipped: usize,
+    pub operations_imported: usize,
+    /// Operations already in history under another batch, left untouched
+    pub operations_skipped: usize,
+    /// Trash copies referenced by imported operations that aren't on disk
+    pub missing_backups: Vec<PathBuf>,
+}
+
+/// Imports a JSON Lines export, rewriting path prefixes with `remaps`.
+/// Imported batches can be undone from history but aren't put on the undo
+/// stack.
+#[tauri::command]
+pub async fn import_history(
+    storage: State<'_, Storage>,
+    path: PathBuf,
+    remaps: Option<Vec<PathRemap>>,
+) -> Result<ImportSummary, AppError> {
+    let remaps = remaps.unwrap_or_default();
+    let imported = history_export::parse_jsonl(&std::fs::read_to_string(&path)?)?;
+
+    let mut conn = storage.conn()?;
+    let tx = conn.transaction()?;
+
+    let mut summary = ImportSummary {
+        batches_imported: 0,
+        batches_skipped: 0,
+        operations_imported: 0,
+        operations_skipped: 0,
+        missing_backups: Vec::new(),
+    };
+
+    for mut batch in imported {
+        if batches::exists(&tx, &batch.id)? {
+            summary.batches_skipped += 1;
+            continue;
+        }
+
+        batch.root_folder = batch
+            .root_folder
+            .map(|p| history_export::remap_path(&p, &remaps));
+        batches::insert(&tx, &batch)?;
+
+        for mut op in std::mem::take(&mut batch.operations) {
+            if operations::exists(&tx, &op.id)? {
+                summary.operations_skipped += 1;
+                continue;
+            }
+
+            op.batch_id = Some(batch.id.clone());
+            op.source_path = history_export::remap_path(&op.source_path, &remaps);
+            op.destination_path = op
+                .destination_path
+                .map(|p| history_export::remap_path(&p, &remaps));
+            op.backup_path = op
+                .backup_path
+                .map(|p| history_export::remap_path(&p, &remaps));
+
+            if let Some(backup) = &op.backup_path {
+                if !backup.exists() {
+                    summary.missing_backups.push(backup.clone());
+                }
+            }
+
+            operations::insert(&tx, &op)?;
+            operations::update_result(&tx, &op)?;
+            summary.operations_imported += 1;
+        }
+
+        summary.batches_imported += 1;
+    }
+
+    tx.commit()?;
+
+    Ok(summary)
+}
+
+/// Reconciles journal entries left `pending`/`in_progress` by a crash against
+/// the filesystem, so every interrupted operation ends up either completed
+/// (and undoable) or failed.
+pub fn recover_interrupted_operations(storage: &Storage) -> Result<Vec<Operation>, AppError> {
+    let conn = storage.conn()?;
+
+    let mut recovered = Vec::new();
+
+    for mut op in operations::list_incomplete(&conn)? {
+        op.status = reconcile_operation(&op);
+        if op.status == OperationStatus::Completed {
+            op.fingerprint = file_ops::result_fingerprint(&op);
+        } else {
+            op.error_code = Some("interrupted".to_string());
+            // An unfinished extraction leaves its temporary folder behind
+            if let (OperationType::Extract, Some(destination)) =
+                (&op.operation_type, &op.destination_path)
+            {
+                let _ = std::fs::remove_dir_all(archive::partial_path(destination));
+            }
+        }
+
+        operations::update_result(&conn, &op)?;
+
+        recovered.push(op);
+    }
+
+    Ok(recovered)
+}
+
+/// Decides from what is on disk whether an interrupted operation took effect
+fn reconcile_operation(op: &Operation) -> OperationStatus {
+    let source_exists = op.source_path.exists();
+
+    match op.operation_type {
+        OperationType::Move | OperationType::Rename => {
+            let dest_exists = op.destination_path.as_ref().is_some_and(|d| d.exists());
+            match (source_exists, dest_exists) {
+                (false, true) => OperationStatus::Completed,
+                (true, false) => {
+                    OperationStatus::Failed("Interrupted before the file was moved".to_string())
+                }
+                (true, true) => OperationStatus::Failed(
+                    "Interrupted: both source and destination exist".to_string(),
+                ),
+                (false, false) => OperationStatus::Failed(
+                    "Interrupted: file not found at source or destination".to_string(),
+                ),
+            }
+        }
+        OperationType::Copy => {
+            let source_size = std::fs::metadata(&op.source_path).map(|m| m.len()).ok();
+            let dest_size = op
+                .destination_path
+                .as_ref()
+                .and_then(|d| std::fs::metadata(d).ok())
+                .map(|m| m.len());
+            match (source_size, dest_size) {
+                (Some(src), Some(dest)) if src == dest => OperationStatus::Completed,
+                (_, Some(_)) => OperationStatus::Failed(
+                    "Interrupted: destination holds an incomplete copy".to_string(),
+                ),
+                (_, None) => {
+                    OperationStatus::Failed("Interrupted before the file was copied".to_string())
+                }
+            }
+        }
+        // Extraction only renames its folder into place once complete
+        OperationType::Extract => {
+            if op.destination_path.as_ref().is_some_and(|d| d.exists()) {
+                OperationStatus::Completed
+            } else {
+                OperationStatus::Failed("Interrupted before the archive was extracted".to_string())
+            }
+        }
+        // The file is trashed before the link is made; once it's in the
+        // trash, undo can put it back whether or not the link followed
+        OperationType::Hardlink | OperationType::Symlink => {
+            if op.backup_path.as_ref().is_some_and(|b| b.exists()) {
+                OperationStatus::Completed
+            } else if source_exists {
+                OperationStatus::Failed("Interrupted before the file was replaced".to_string())
+            } else {
+                OperationStatus::Failed(
+                    "Interrupted: file not found at source or in trash".to_string(),
+                )
+            }
+        }
+        OperationType::Delete => {
+            let backup_exists = op.backup_path.as_ref().is_some_and(|b| b.exists());
+            match (source_exists, backup_exists) {
+                (false, true) => OperationStatus::Completed,
+                (true, _) => {
+                    OperationStatus::Failed("Interrupted before the file was deleted".to_string())
+                }
+                (false, false) => OperationStatus::Failed(
+                    "Interrupted: file not found at source or in trash".to_string(),
+                ),
+            }
+        }
+    }
+}
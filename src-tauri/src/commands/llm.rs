@@ -1,7 +1,9 @@
+use crate::commands::settings::{load_categories, load_settings};
 use crate::error::AppError;
-use crate::models::AppSettings;
 use crate::services::llm::{ClassificationResult, LlmService};
-use tauri::Manager;
+use crate::storage::{files, Storage};
+use std::path::Path;
+use tauri::State;
 
 #[derive(serde::Deserialize)]
 pub struct ClassifyRequest {
@@ -14,30 +16,18 @@ pub struct FileToClassify {
     pub name: String,
     pub extension: String,
     pub size: u64,
-}
-
-fn read_settings(app: &tauri::AppHandle) -> Result<AppSettings, AppError> {
-    let path = app
-        .path()
-        .app_config_dir()
-        .unwrap_or_else(|_| std::path::PathBuf::from("."))
-        .join("settings.json");
-
-    if path.exists() {
-        let content = std::fs::read_to_string(&path)?;
-        let settings: AppSettings = serde_json::from_str(&content)?;
-        Ok(settings)
-    } else {
-        Ok(AppSettings::default())
-    }
+    /// The start of the file's text, from `FileMetadata::preview_text`
+    #[serde(default)]
+    pub preview_text: Option<String>,
 }
 
 #[tauri::command]
 pub async fn classify_files(
     app: tauri::AppHandle,
+    storage: State<'_, Storage>,
     request: ClassifyRequest,
 ) -> Result<Vec<ClassificationResult>, AppError> {
-    let settings = read_settings(&app)?;
+    let settings = load_settings(&app, &storage)?;
 
     if !settings.llm.enabled {
         return Err(AppError::LlmError("LLM is not enabled".to_string()));
@@ -49,13 +39,14 @@ pub async fn classify_files(
         return Err(AppError::LlmError("API key is not configured".to_string()));
     }
 
-    let categories = get_category_names(&app)?;
+    let categories = get_category_names(&app, &storage)?;
     let service = LlmService::new(config);
 
-    let files: Vec<(String, String, u64)> = request
+    let paths: Vec<String> = request.files.iter().map(|f| f.path.clone()).collect();
+    let files: Vec<(String, String, u64, Option<String>)> = request
         .files
         .into_iter()
-        .map(|f| (f.name, f.extension, f.size))
+        .map(|f| (f.name, f.extension, f.size, f.preview_text))
         .collect();
 
     let custom_prompt = if !settings.prompts.filename_prompt.is_empty() {
@@ -68,15 +59,24 @@ pub async fn classify_files(
         .classify_files_batch(files, &categories, custom_prompt)
         .await?;
 
+    // Results come back in request order; failed ones have no confidence
+    let conn = storage.conn()?;
+    for (path, result) in paths.iter().zip(&results) {
+        if result.confidence > 0.0 {
+            files::set_category(&conn, Path::new(path), &result.suggested_category)?;
+        }
+    }
+
     Ok(results)
 }
 
 #[tauri::command]
 pub async fn classify_single_file(
     app: tauri::AppHandle,
+    storage: State<'_, Storage>,
     file: FileToClassify,
 ) -> Result<ClassificationResult, AppError> {
-    let settings = read_settings(&app)?;
+    let settings = load_settings(&app, &storage)?;
 
     if !settings.llm.enabled {
         return Err(AppError::LlmError("LLM is not enabled".to_string()));
@@ -88,7 +88,7 @@ pub async fn classify_single_file(
         return Err(AppError::LlmError("API key is not configured".to_string()));
     }
 
-    let categories = get_category_names(&app)?;
+    let categories = get_category_names(&app, &storage)?;
     let service = LlmService::new(config);
 
     let custom_prompt = if !settings.prompts.filename_prompt.is_empty() {
@@ -97,51 +97,34 @@ pub async fn classify_single_file(
         None
     };
 
-    service
+    let result = service
         .classify_file(
             &file.name,
             &file.extension,
             file.size,
+            file.preview_text.as_deref(),
             &categories,
             custom_prompt,
         )
-        .await
-}
-
-fn get_category_names(app: &tauri::AppHandle) -> Result<Vec<String>, AppError> {
-    let config_dir = app
-        .path()
-        .app_config_dir()
-        .map_err(|e| AppError::Config(e.to_string()))?;
-
-    let categories_path = config_dir.join("categories.json");
+        .await?;
 
-    if categories_path.exists() {
-        let content = std::fs::read_to_string(&categories_path)?;
+    let conn = storage.conn()?;
+    files::set_category(&conn, Path::new(&file.path), &result.suggested_category)?;
 
-        #[derive(serde::Deserialize)]
-        struct Category {
-            name: String,
-        }
+    Ok(result)
+}
 
-        let categories: Vec<Category> = serde_json::from_str(&content)?;
-        Ok(categories.into_iter().map(|c| c.name).collect())
-    } else {
-        Ok(vec![
-            "文档".to_string(),
-            "图片".to_string(),
-            "视频".to_string(),
-            "音乐".to_string(),
-            "代码".to_string(),
-            "压缩包".to_string(),
-            "其他".to_string(),
-        ])
-    }
+fn get_category_names(app: &tauri::AppHandle, storage: &Storage) -> Result<Vec<String>, AppError> {
+    let categories = load_categories(app, storage)?;
+    Ok(categories.into_iter().map(|c| c.name).collect())
 }
 
 #[tauri::command]
-pub async fn test_llm_connection(app: tauri::AppHandle) -> Result<String, AppError> {
-    let settings = read_settings(&app)?;
+pub async fn test_llm_connection(
+    app: tauri::AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<String, AppError> {
+    let settings = load_settings(&app, &storage)?;
     let config = settings.llm.config;
 
     let service = LlmService::new(config);
@@ -151,6 +134,7 @@ pub async fn test_llm_connection(app: tauri::AppHandle) -> Result<String, AppErr
             "test.txt",
             "txt",
             1024,
+            None,
             &["documents".to_string(), "others".to_string()],
             None,
         )
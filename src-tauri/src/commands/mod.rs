@@ -3,3 +3,4 @@ pub mod history;
 pub mod llm;
 pub mod scan;
 pub mod settings;
+pub mod trash;
@@ -1,122 +1,315 @@
 use crate::error::AppError;
-use crate::models::{FileItem, FileMetadata, FileType};
-use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use crate::models::{FileItem, ScanCheckpoint, ScanError, ScanOptions, ScanReport};
+use crate::services::scanner::{self, ScanJob, ScanUpdate};
+use crate::storage::files::{self, IndexedFile};
+use crate::storage::{scans, Storage};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tauri::ipc::Channel;
+use tauri::State;
 use uuid::Uuid;
-use walkdir::WalkDir;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ScanOptions {
-    pub path: PathBuf,
-    pub recursive: bool,
-    pub include_hidden: bool,
-}
+/// Minimum time between "scanning" progress events
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Found files are written to the checkpoint in batches of this size, or
+/// after `CHECKPOINT_INTERVAL`, whichever comes first
+const CHECKPOINT_BATCH: usize = 1000;
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(2);
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ScanProgress {
+    pub scan_id: String,
     pub event: String,
     pub current_file: Option<String>,
     pub scanned_count: usize,
     pub total_count: Option<usize>,
+    /// Set on "error" events
+    pub error: Option<ScanError>,
+}
+
+impl ScanProgress {
+    fn new(scan_id: &str, event: &str, scanned_count: usize) -> Self {
+        Self {
+            scan_id: scan_id.to_string(),
+            event: event.to_string(),
+            current_file: None,
+            scanned_count,
+            total_count: None,
+            error: None,
+        }
+    }
+}
+
+/// Scans currently running, so `cancel_scan` can reach them
+#[derive(Default)]
+pub struct ScanRegistry {
+    running: Mutex<HashMap<String, Arc<ScanJob>>>,
+}
+
+impl ScanRegistry {
+    fn get(&self, scan_id: &str) -> Option<Arc<ScanJob>> {
+        self.running.lock().unwrap().get(scan_id).cloned()
+    }
+
+    fn insert(&self, job: Arc<ScanJob>) {
+        self.running.lock().unwrap().insert(job.id.clone(), job);
+    }
+
+    fn remove(&self, scan_id: &str) {
+        self.running.lock().unwrap().remove(scan_id);
+    }
 }
 
 #[tauri::command]
 pub async fn scan_directory(
+    registry: State<'_, ScanRegistry>,
+    storage: State<'_, Storage>,
     options: ScanOptions,
     on_progress: Channel<ScanProgress>,
-) -> Result<Vec<FileItem>, AppError> {
-    let mut files = Vec::new();
-    let mut count = 0;
+) -> Result<ScanReport, AppError> {
+    // Fail fast on a bad folder or pattern rather than leaving a checkpoint
+    // behind
+    scanner::check_options(&options)?;
 
-    // Send start event
-    let _ = on_progress.send(ScanProgress {
-        event: "started".to_string(),
-        current_file: None,
-        scanned_count: 0,
-        total_count: None,
-    });
-
-    let walker = if options.recursive {
-        WalkDir::new(&options.path)
-    } else {
-        WalkDir::new(&options.path).max_depth(1)
-    };
+    let scan_id = Uuid::new_v4().to_string();
+    let conn = storage.conn()?;
+    scans::insert(&conn, &scan_id, &options)?;
+    let index = files::load_under(&conn, &options.path)?;
+    drop(conn);
+
+    let job = ScanJob::new(scan_id, Default::default(), index);
+    run_scan(&registry, &storage, job, options, Vec::new(), on_progress).await
+}
+
+/// Continues a cancelled or interrupted scan. Folders are listed again, but
+/// files found by the earlier run are reused instead of being read again.
+#[tauri::command]
+pub async fn resume_scan(
+    registry: State<'_, ScanRegistry>,
+    storage: State<'_, Storage>,
+    scan_id: String,
+    on_progress: Channel<ScanProgress>,
+) -> Result<ScanReport, AppError> {
+    if registry.get(&scan_id).is_some() {
+        return Err(AppError::OperationFailed(format!(
+            "Scan {} is already running",
+            scan_id
+        )));
+    }
+
+    let conn = storage.conn()?;
+    let checkpoint = scans::get(&conn, &scan_id)?
+        .ok_or_else(|| AppError::OperationFailed(format!("No checkpoint for scan {}", scan_id)))?;
+    let previous = scans::load_files(&conn, &scan_id)?;
+    scans::set_status(&conn, &scan_id, scans::STATUS_RUNNING)?;
+    let index = files::load_under(&conn, &checkpoint.options.path)?;
+    drop(conn);
 
-    for entry in walker.into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
+    let already_scanned = previous.iter().map(|f| f.path.clone()).collect();
+    let job = ScanJob::new(scan_id, already_scanned, index);
+    run_scan(
+        &registry,
+        &storage,
+        job,
+        checkpoint.options,
+        previous,
+        on_progress,
+    )
+    .await
+}
 
-        // Skip directories
-        if path.is_dir() {
-            continue;
+/// Stops a running scan; returns false if no scan with that ID is running
+#[tauri::command]
+pub async fn cancel_scan(
+    registry: State<'_, ScanRegistry>,
+    scan_id: String,
+) -> Result<bool, AppError> {
+    match registry.get(&scan_id) {
+        Some(job) => {
+            job.cancel();
+            Ok(true)
         }
+        None => Ok(false),
+    }
+}
+
+/// Scans that can be resumed, including any cut off by a crash
+#[tauri::command]
+pub async fn list_scans(
+    registry: State<'_, ScanRegistry>,
+    storage: State<'_, Storage>,
+) -> Result<Vec<ScanCheckpoint>, AppError> {
+    let conn = storage.conn()?;
+
+    Ok(scans::list(&conn)?
+        .into_iter()
+        .filter(|c| registry.get(&c.scan_id).is_none())
+        .collect())
+}
 
-        // Skip hidden files if not included
-        if !options.include_hidden {
-            if let Some(name) = path.file_name() {
-                if name.to_string_lossy().starts_with('.') {
-                    continue;
+/// Drops the checkpoint of a scan that won't be resumed
+#[tauri::command]
+pub async fn discard_scan(
+    registry: State<'_, ScanRegistry>,
+    storage: State<'_, Storage>,
+    scan_id: String,
+) -> Result<(), AppError> {
+    if registry.get(&scan_id).is_some() {
+        return Err(AppError::OperationFailed(format!(
+            "Scan {} is still running, cancel it first",
+            scan_id
+        )));
+    }
+
+    let conn = storage.conn()?;
+    scans::delete(&conn, &scan_id)
+}
+
+/// Runs the walk off the async runtime, checkpointing found files and
+/// recording new and changed ones in the file index as it goes. A finished
+/// scan's checkpoint is dropped; a cancelled one is kept for `resume_scan`.
+async fn run_scan(
+    registry: &ScanRegistry,
+    storage: &Storage,
+    job: ScanJob,
+    options: ScanOptions,
+    previous: Vec<FileItem>,
+    on_progress: Channel<ScanProgress>,
+) -> Result<ScanReport, AppError> {
+    let scan_id = job.id.clone();
+    let resumed_count = previous.len();
+
+    // Send start event
+    let _ = on_progress.send(ScanProgress::new(&scan_id, "started", resumed_count));
+
+    let job = Arc::new(job);
+    registry.insert(job.clone());
+
+    let progress = on_progress.clone();
+    let checkpoint_storage = storage.clone();
+    let scan_job = job.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let job = scan_job;
+        let mut conn = checkpoint_storage.conn()?;
+        let mut pending: Vec<FileItem> = Vec::new();
+        let mut pending_index: Vec<IndexedFile> = Vec::new();
+        let mut last_sent = Instant::now();
+        let mut last_checkpoint = Instant::now();
+        let mut scanned = resumed_count;
+
+        let report = scanner::scan(&options, &job, |update| match update {
+            ScanUpdate::File {
+                count,
+                file,
+                changed,
+            } => {
+                scanned = resumed_count + count;
+                let item = &file.item;
+
+                pending.push(item.clone());
+                if changed {
+                    pending_index.push(file.clone());
+                }
+                if pending.len() >= CHECKPOINT_BATCH
+                    || last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL
+                {
+                    if let Err(e) = scans::save_files(&mut conn, &job.id, &pending) {
+                        eprintln!("Failed to checkpoint scan {}: {}", job.id, e);
+                    }
+                    if let Err(e) = files::save(&mut conn, &job.id, &pending_index) {
+                        eprintln!("Failed to index files of scan {}: {}", job.id, e);
+                    }
+                    pending.clear();
+                    pending_index.clear();
+                    last_checkpoint = Instant::now();
+                }
+
+                if last_sent.elapsed() >= PROGRESS_INTERVAL {
+                    last_sent = Instant::now();
+                    let mut event = ScanProgress::new(&job.id, "scanning", scanned);
+                    event.current_file = Some(item.path.to_string_lossy().to_string());
+                    let _ = progress.send(event);
                 }
             }
-        }
+            ScanUpdate::Error(error) => {
+                let mut event = ScanProgress::new(&job.id, "error", scanned);
+                event.current_file = error.path.as_ref().map(|p| p.to_string_lossy().to_string());
+                event.error = Some(error.clone());
+                let _ = progress.send(event);
+            }
+        });
 
-        let metadata = std::fs::metadata(path)?;
-        let name = path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_default();
-        let extension = path.extension().map(|e| e.to_string_lossy().to_string());
-
-        let file_type = extension
-            .as_ref()
-            .map(|e| FileType::from_extension(e))
-            .unwrap_or(FileType::Other);
-
-        let file_item = FileItem {
-            id: Uuid::new_v4().to_string(),
-            path: path.to_path_buf(),
-            name,
-            extension,
-            size: metadata.len(),
-            file_type,
-            hash: None,
-            created_at: metadata
-                .created()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0),
-            modified_at: metadata
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs() as i64)
-                .unwrap_or(0),
-            category: None,
-            metadata: FileMetadata::default(),
-        };
-
-        files.push(file_item);
-        count += 1;
-
-        // Send progress every 10 files
-        if count % 10 == 0 {
-            let _ = on_progress.send(ScanProgress {
-                event: "scanning".to_string(),
-                current_file: Some(path.to_string_lossy().to_string()),
-                scanned_count: count,
-                total_count: None,
-            });
+        if !pending.is_empty() {
+            scans::save_files(&mut conn, &job.id, &pending)?;
         }
+        if !pending_index.is_empty() {
+            files::save(&mut conn, &job.id, &pending_index)?;
+        }
+
+        report
+    })
+    .await;
+
+    registry.remove(&scan_id);
+
+    let mut report = result.map_err(|e| AppError::OperationFailed(e.to_string()))??;
+    report.files.extend(previous);
+    report.files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut conn = storage.conn()?;
+    if report.cancelled {
+        scans::set_status(&conn, &scan_id, scans::STATUS_CANCELLED)?;
+    } else {
+        let mut removed = removed_files(&job.index, &report.files);
+        removed.sort_by(|a, b| a.item.path.cmp(&b.item.path));
+        let paths: Vec<&Path> = removed.iter().map(|f| f.item.path.as_path()).collect();
+        files::delete(&mut conn, &paths)?;
+        report.changes.removed = removed.iter().map(|f| f.item.id.clone()).collect();
+
+        scans::delete(&conn, &scan_id)?;
     }
 
+    let (added, modified) = files::changes(&conn, &scan_id)?;
+    report.changes.unchanged = report
+        .files
+        .len()
+        .saturating_sub(added.len() + modified.len());
+    report.changes.added = added;
+    report.changes.modified = modified;
+
     // Send completion event
-    let _ = on_progress.send(ScanProgress {
-        event: "completed".to_string(),
-        current_file: None,
-        scanned_count: count,
-        total_count: Some(count),
-    });
-
-    Ok(files)
+    let event = if report.cancelled {
+        "cancelled"
+    } else {
+        "completed"
+    };
+    let mut done = ScanProgress::new(&scan_id, event, report.files.len());
+    done.total_count = Some(report.files.len());
+    let _ = on_progress.send(done);
+
+    Ok(report)
+}
+
+/// Indexed files the scan didn't find and that are gone. Ones that are still
+/// there were only left out by its filters or couldn't be read.
+fn removed_files<'a>(
+    index: &'a HashMap<PathBuf, IndexedFile>,
+    found: &[FileItem],
+) -> Vec<&'a IndexedFile> {
+    let found: HashSet<&Path> = found.iter().map(|f| f.path.as_path()).collect();
+
+    index
+        .iter()
+        .filter(|(path, _)| !found.contains(path.as_path()))
+        .filter(|(path, _)| match std::fs::symlink_metadata(path) {
+            Ok(metadata) => metadata.is_dir(),
+            Err(e) => e.kind() == ErrorKind::NotFound,
+        })
+        .map(|(_, file)| file)
+        .collect()
 }
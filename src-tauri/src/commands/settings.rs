@@ -1,90 +1,103 @@
 use crate::error::AppError;
 use crate::models::{default_categories, AppSettings, Category};
+use crate::storage::{settings, Storage};
+use serde::de::DeserializeOwned;
 use std::path::PathBuf;
-use tauri::Manager;
+use tauri::{Manager, State};
 
-fn get_config_path(app: &tauri::AppHandle) -> PathBuf {
+fn get_legacy_path(app: &tauri::AppHandle, file_name: &str) -> PathBuf {
     app.path()
         .app_config_dir()
         .unwrap_or_else(|_| PathBuf::from("."))
-        .join("settings.json")
+        .join(file_name)
 }
 
-fn get_categories_path(app: &tauri::AppHandle) -> PathBuf {
-    app.path()
-        .app_config_dir()
-        .unwrap_or_else(|_| PathBuf::from("."))
-        .join("categories.json")
-}
+/// Reads `key` from the settings table. Installs that predate the table kept
+/// it in a JSON file in the config dir, which is imported on first read.
+fn load_or_import<T, F>(
+    app: &tauri::AppHandle,
+    storage: &Storage,
+    key: &str,
+    legacy_file: &str,
+    default: F,
+) -> Result<T, AppError>
+where
+    T: DeserializeOwned + serde::Serialize,
+    F: FnOnce() -> T,
+{
+    let conn = storage.conn()?;
+
+    if let Some(value) = settings::get(&conn, key)? {
+        return Ok(value);
+    }
 
-/// Internal non-async version for use by other modules
-pub fn get_settings_internal(app: &tauri::AppHandle) -> Result<AppSettings, AppError> {
-    let path = get_config_path(app);
+    let legacy_path = get_legacy_path(app, legacy_file);
 
-    if path.exists() {
-        let content = std::fs::read_to_string(&path)?;
-        let settings: AppSettings = serde_json::from_str(&content)?;
-        Ok(settings)
+    if legacy_path.exists() {
+        let content = std::fs::read_to_string(&legacy_path)?;
+        let value: T = serde_json::from_str(&content)?;
+        settings::set(&conn, key, &value)?;
+        Ok(value)
     } else {
-        Ok(AppSettings::default())
+        Ok(default())
     }
 }
 
-#[tauri::command]
-pub async fn get_settings(app: tauri::AppHandle) -> Result<AppSettings, AppError> {
-    let path = get_config_path(&app);
+/// Internal non-async version for use by other modules
+pub fn load_settings(app: &tauri::AppHandle, storage: &Storage) -> Result<AppSettings, AppError> {
+    load_or_import(
+        app,
+        storage,
+        settings::APP_SETTINGS,
+        "settings.json",
+        AppSettings::default,
+    )
+}
 
-    if path.exists() {
-        let content = std::fs::read_to_string(&path)?;
-        let settings: AppSettings = serde_json::from_str(&content)?;
-        Ok(settings)
-    } else {
-        Ok(AppSettings::default())
-    }
+/// Internal non-async version for use by other modules
+pub fn load_categories(
+    app: &tauri::AppHandle,
+    storage: &Storage,
+) -> Result<Vec<Category>, AppError> {
+    load_or_import(
+        app,
+        storage,
+        settings::CATEGORIES,
+        "categories.json",
+        default_categories,
+    )
 }
 
 #[tauri::command]
-pub async fn save_settings(app: tauri::AppHandle, settings: AppSettings) -> Result<(), AppError> {
-    let path = get_config_path(&app);
-
-    // Ensure parent directory exists
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent)?;
-    }
-
-    let content = serde_json::to_string_pretty(&settings)?;
-    std::fs::write(&path, content)?;
-
-    Ok(())
+pub async fn get_settings(
+    app: tauri::AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<AppSettings, AppError> {
+    load_settings(&app, &storage)
 }
 
 #[tauri::command]
-pub async fn get_categories(app: tauri::AppHandle) -> Result<Vec<Category>, AppError> {
-    let path = get_categories_path(&app);
+pub async fn save_settings(
+    storage: State<'_, Storage>,
+    settings: AppSettings,
+) -> Result<(), AppError> {
+    let conn = storage.conn()?;
+    settings::set(&conn, settings::APP_SETTINGS, &settings)
+}
 
-    if path.exists() {
-        let content = std::fs::read_to_string(&path)?;
-        let categories: Vec<Category> = serde_json::from_str(&content)?;
-        Ok(categories)
-    } else {
-        Ok(default_categories())
-    }
+#[tauri::command]
+pub async fn get_categories(
+    app: tauri::AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<Vec<Category>, AppError> {
+    load_categories(&app, &storage)
 }
 
 #[tauri::command]
 pub async fn save_categories(
-    app: tauri::AppHandle,
+    storage: State<'_, Storage>,
     categories: Vec<Category>,
 ) -> Result<(), AppError> {
-    let path = get_categories_path(&app);
-
-    // Ensure parent directory exists
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent)?;
-    }
-
-    let content = serde_json::to_string_pretty(&categories)?;
-    std::fs::write(&path, content)?;
-
-    Ok(())
+    let conn = storage.conn()?;
+    settings::set(&conn, settings::CATEGORIES, &categories)
 }
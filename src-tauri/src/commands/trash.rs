@@ -0,0 +1,515 @@
+use crate::commands::history::{self, ConflictKind, UndoConflict};
+use crate::commands::settings::load_settings;
+use crate::error::AppError;
+use crate::models::TrashSettings;
+use crate::services::trash;
+use crate::storage::trashed_copies::{self, TrashedCopy};
+use crate::storage::{self, operations, Storage};
+use chrono::Utc;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tauri::State;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashEntry {
+    /// Where the file currently sits in the trash
+    pub path: PathBuf,
+    pub name: String,
+    /// Where it was deleted from, when the delete was recorded in history
+    pub original_path: Option<PathBuf>,
+    pub size: u64,
+    pub deleted_at: i64,
+    pub operation_id: Option<String>,
+    pub batch_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashActionResult {
+    pub path: PathBuf,
+    pub success: bool,
+    pub error: Option<String>,
+    pub conflict: Option<UndoConflict>,
+}
+
+impl TrashActionResult {
+    fn ok(path: PathBuf) -> Self {
+        Self {
+            path,
+            success: true,
+            error: None,
+            conflict: None,
+        }
+    }
+
+    fn failed(path: PathBuf, error: String) -> Self {
+        Self {
+            path,
+            success: false,
+            error: Some(error),
+            conflict: None,
+        }
+    }
+}
+
+pub fn get_trash_dir(app: &tauri::AppHandle) -> PathBuf {
+    let trash_dir = storage::app_data_dir(app).join("trash");
+
+    // Ensure trash directory exists
+    if !trash_dir.exists() {
+        let _ = std::fs::create_dir_all(&trash_dir);
+    }
+
+    trash_dir
+}
+
+/// The trash configured in settings, falling back to the app trash folder
+pub fn trash_target(app: &tauri::AppHandle, storage: &Storage) -> trash::TrashTarget {
+    trash::TrashTarget {
+        app_dir: get_trash_dir(app),
+        use_system_trash: load_settings(app, storage)
+            .map(|s| s.trash.use_system_trash)
+            .unwrap_or_default(),
+    }
+}
+
+/// Everything in the app trash folder, plus files this app moved into the
+/// system trash that are still there
+fn collect_entries(app: &tauri::AppHandle, conn: &Connection) -> Result<Vec<TrashEntry>, AppError> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(get_trash_dir(app))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect();
+
+    let known: HashSet<PathBuf> = paths.iter().cloned().collect();
+    let mut recorded = operations::list_backup_paths(conn)?;
+    recorded.extend(trashed_copies::list_paths(conn)?);
+    paths.extend(
+        recorded
+            .into_iter()
+            .filter(|p| !known.contains(p) && p.exists()),
+    );
+
+    let mut entries = Vec::new();
+
+    for path in paths {
+        let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        let trashed_copy = trashed_copies::find(conn, &path)?;
+        let operation = match &trashed_copy {
+            Some(copy) => operations::get(conn, &copy.operation_id)?,
+            None => operations::find_by_backup_path(conn, &path)?,
+        };
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        // App trash names are `<millis>_<original name>`
+        let (prefix_millis, stripped_name) = match file_name.split_once('_') {
+            Some((millis, rest)) if millis.parse::<i64>().is_ok() => {
+                (millis.parse::<i64>().ok(), rest.to_string())
+            }
+            _ => (None, file_name.clone()),
+        };
+
+        let deleted_at = trashed_copy
+            .as_ref()
+            .map(|copy| copy.trashed_at)
+            .or(operation.as_ref().map(|op| op.timestamp))
+            .or(prefix_millis.map(|m| m / 1000))
+            .or_else(|| {
+                metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64)
+            })
+            .unwrap_or(0);
+
+        entries.push(TrashEntry {
+            name: operation
+                .as_ref()
+                .and_then(|op| op.original_name.clone())
+                .unwrap_or(stripped_name),
+            // An edited copy or extraction goes back where it was made
+            original_path: match trashed_copy {
+                Some(copy) => Some(copy.original_path),
+                None => operation.as_ref().map(|op| op.source_path.clone()),
+            },
+            size: trash::entry_size(&path, &metadata),
+            deleted_at,
+            operation_id: operation.as_ref().map(|op| op.id.clone()),
+            batch_id: operation.and_then(|op| op.batch_id),
+            path,
+        });
+    }
+
+    entries.sort_by_key(|e| std::cmp::Reverse(e.deleted_at));
+
+    Ok(entries)
+}
+
+#[tauri::command]
+pub async fn list_trash(
+    app: tauri::AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<Vec<TrashEntry>, AppError> {
+    let conn = storage.conn()?;
+    collect_entries(&app, &conn)
+}
+
+/// Puts trashed files back where they were deleted from. Goes through the
+/// same checks as undo, so an occupied original location is reported rather
+/// than overwritten.
+#[tauri::command]
+pub async fn restore_trash(
+    app: tauri::AppHandle,
+    storage: State<'_, Storage>,
+    paths: Vec<PathBuf>,
+) -> Result<Vec<TrashActionResult>, AppError> {
+    let conn = storage.conn()?;
+    let entries = collect_entries(&app, &conn)?;
+    let target = trash_target(&app, &storage);
+
+    let mut results = Vec::new();
+
+    for path in paths {
+        let Some(entry) = entries.iter().find(|e| e.path == path) else {
+            results.push(TrashActionResult::failed(path, "Not in trash".to_string()));
+            continue;
+        };
+
+        if let Some(copy) = trashed_copies::find(&conn, &entry.path)? {
+            results.push(restore_trashed_copy(&conn, path, copy)?);
+            continue;
+        }
+
+        let Some(operation) = operations::find_by_backup_path(&conn, &entry.path)? else {
+            results.push(TrashActionResult::failed(
+                path,
+                "Original location is unknown".to_string(),
+            ));
+            continue;
+        };
+
+        let batch_id = operation.batch_id.clone();
+        let undo = history::undo_operation(&conn, &target, operation)?;
+        if let Some(batch_id) = batch_id.filter(|_| undo.success) {
+            history::settle_undone_batch(&conn, &batch_id)?;
+        }
+        results.push(TrashActionResult {
+            path,
+            success: undo.success,
+            error: undo.error,
+            conflict: undo.conflict,
+        });
+    }
+
+    Ok(results)
+}
+
+/// Puts an edited copy or extraction back where it was trashed from. The
+/// operation itself stays undone.
+fn restore_trashed_copy(
+    conn: &Connection,
+    path: PathBuf,
+    copy: TrashedCopy,
+) -> Result<TrashActionResult, AppError> {
+    let destination = copy.original_path;
+
+    if destination.exists() {
+        return Ok(TrashActionResult {
+            success: false,
+            error: Some(format!(
+                "Original location is occupied: {}",
+                destination.display()
+            )),
+            conflict: Some(UndoConflict {
+                kind: ConflictKind::SourceOccupied,
+                path: destination,
+                message: "Original location is occupied".to_string(),
+                expected: None,
+                actual: None,
+            }),
+            path,
+        });
+    }
+
+    if let Err(e) = trash::restore_from_trash(&path, &destination) {
+        return Ok(TrashActionResult::failed(path, e.to_string()));
+    }
+    trashed_copies::delete(conn, &path)?;
+
+    Ok(TrashActionResult::ok(path))
+}
+
+/// Permanently deletes the entries, then drops their trashed-copy records
+/// and marks the operations that trashed them as no longer undoable
+fn purge_entries(
+    conn: &mut Connection,
+    entries: &[&TrashEntry],
+) -> Result<Vec<TrashActionResult>, AppError> {
+    let mut results = Vec::new();
+
+    for entry in entries {
+        if let Err(e) = trash::purge(&entry.path) {
+            results.push(TrashActionResult::failed(entry.path.clone(), e.to_string()));
+            continue;
+        }
+
+        let tx = conn.transaction()?;
+        trashed_copies::delete(&tx, &entry.path)?;
+        operations::mark_purged(&tx, &entry.path)?;
+        if let Some(batch_id) =
+            operations::find_by_backup_path(&tx, &entry.path)?.and_then(|op| op.batch_id)
+        {
+            history::settle_undone_batch(&tx, &batch_id)?;
+        }
+        tx.commit()?;
+
+        results.push(TrashActionResult::ok(entry.path.clone()));
+    }
+
+    Ok(results)
+}
+
+/// Permanently deletes the given trash entries
+#[tauri::command]
+pub async fn purge_trash(
+    app: tauri::AppHandle,
+    storage: State<'_, Storage>,
+    paths: Vec<PathBuf>,
+) -> Result<Vec<TrashActionResult>, AppError> {
+    let mut conn = storage.conn()?;
+    let entries = collect_entries(&app, &conn)?;
+
+    let mut results = Vec::new();
+
+    // Only ever delete something that is actually a trash entry
+    for path in paths {
+        match entries.iter().find(|e| e.path == path) {
+            Some(entry) => results.extend(purge_entries(&mut conn, &[entry])?),
+            None => results.push(TrashActionResult::failed(path, "Not in trash".to_string())),
+        }
+    }
+
+    Ok(results)
+}
+
+/// Entries the policy says should go: everything older than the age limit,
+/// then the oldest remaining ones until the total fits the size limit
+fn select_expired<'a>(
+    entries: &'a [TrashEntry],
+    policy: &TrashSettings,
+    now: i64,
+) -> Vec<&'a TrashEntry> {
+    let mut oldest_first: Vec<&TrashEntry> = entries.iter().collect();
+    oldest_first.sort_by_key(|e| e.deleted_at);
+
+    let mut expired = Vec::new();
+    let mut kept = Vec::new();
+
+    for entry in oldest_first {
+        let too_old = policy
+            .max_age_days
+            .is_some_and(|days| now - entry.deleted_at > days as i64 * 86_400);
+        if too_old {
+            expired.push(entry);
+        } else {
+            kept.push(entry);
+        }
+    }
+
+    if let Some(max_total) = policy.max_total_bytes {
+        let mut total: u64 = kept.iter().map(|e| e.size).sum();
+        for entry in kept {
+            if total <= max_total {
+                break;
+            }
+            total -= entry.size;
+            expired.push(entry);
+        }
+    }
+
+    expired
+}
+
+/// Purges whatever the configured retention policy has expired
+pub fn enforce_retention(
+    app: &tauri::AppHandle,
+    storage: &Storage,
+) -> Result<Vec<TrashActionResult>, AppError> {
+    let policy = load_settings(app, storage)?.trash;
+
+    if policy.max_age_days.is_none() && policy.max_total_bytes.is_none() {
+        return Ok(Vec::new());
+    }
+
+    let mut conn = storage.conn()?;
+    let entries = collect_entries(app, &conn)?;
+    let expired = select_expired(&entries, &policy, Utc::now().timestamp());
+
+    purge_entries(&mut conn, &expired)
+}
+
+#[tauri::command]
+pub async fn apply_trash_retention(
+    app: tauri::AppHandle,
+    storage: State<'_, Storage>,
+) -> Result<Vec<TrashActionResult>, AppError> {
+    enforce_retention(&app, &storage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Operation, OperationStatus, OperationType};
+    use crate::storage::migrations::run_migrations;
+
+    const DAY: i64 = 86_400;
+
+    fn entry(name: &str, size: u64, deleted_at: i64) -> TrashEntry {
+        TrashEntry {
+            path: PathBuf::from("/trash").join(name),
+            name: name.to_string(),
+            original_path: None,
+            size,
+            deleted_at,
+            operation_id: None,
+            batch_id: None,
+        }
+    }
+
+    fn names(entries: &[&TrashEntry]) -> Vec<String> {
+        entries.iter().map(|e| e.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_select_expired_by_age() {
+        let now = 100 * DAY;
+        let entries = vec![
+            entry("new", 10, now - DAY),
+            entry("old", 10, now - 40 * DAY),
+            entry("edge", 10, now - 30 * DAY),
+        ];
+        let policy = TrashSettings {
+            max_age_days: Some(30),
+            max_total_bytes: None,
+            ..Default::default()
+        };
+
+        assert_eq!(names(&select_expired(&entries, &policy, now)), ["old"]);
+    }
+
+    #[test]
+    fn test_select_expired_by_size_drops_oldest_first() {
+        let now = 100 * DAY;
+        let entries = vec![
+            entry("c", 300, now - DAY),
+            entry("a", 300, now - 3 * DAY),
+            entry("b", 300, now - 2 * DAY),
+        ];
+        let policy = TrashSettings {
+            max_age_days: None,
+            max_total_bytes: Some(600),
+            ..Default::default()
+        };
+        assert_eq!(names(&select_expired(&entries, &policy, now)), ["a"]);
+
+        let unlimited = TrashSettings::default();
+        assert!(select_expired(&entries, &unlimited, now).is_empty());
+    }
+
+    #[test]
+    fn test_select_expired_size_limit_counts_only_what_age_kept() {
+        let now = 100 * DAY;
+        let entries = vec![
+            entry("ancient", 1000, now - 90 * DAY),
+            entry("a", 300, now - 3 * DAY),
+            entry("b", 300, now - 2 * DAY),
+        ];
+        let policy = TrashSettings {
+            max_age_days: Some(30),
+            max_total_bytes: Some(300),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            names(&select_expired(&entries, &policy, now)),
+            ["ancient", "a"]
+        );
+    }
+
+    #[test]
+    fn test_purge_entries_forgets_trashed_files() {
+        let dir =
+            std::env::temp_dir().join(format!("fileog-trash-cmd-purge-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("fileog.db");
+        run_migrations(&db).unwrap();
+        let mut conn = Connection::open(&db).unwrap();
+
+        let deleted = dir.join("1_a.txt");
+        let copy = dir.join("2_b.txt");
+        std::fs::write(&deleted, "a").unwrap();
+        std::fs::write(&copy, "b").unwrap();
+
+        let op = |id: &str, operation_type, backup_path: Option<PathBuf>| Operation {
+            id: id.to_string(),
+            operation_type,
+            source_path: dir.join(id),
+            destination_path: None,
+            original_name: None,
+            new_name: None,
+            timestamp: 0,
+            status: OperationStatus::Completed,
+            batch_id: Some("b1".to_string()),
+            backup_path,
+            fingerprint: None,
+            error_code: None,
+            purged: false,
+        };
+        operations::insert(
+            &conn,
+            &op("del", OperationType::Delete, Some(deleted.clone())),
+        )
+        .unwrap();
+        operations::insert(&conn, &op("copy", OperationType::Copy, None)).unwrap();
+        trashed_copies::insert(
+            &conn,
+            &TrashedCopy {
+                trash_path: copy.clone(),
+                operation_id: "copy".to_string(),
+                original_path: dir.join("b.txt"),
+                trashed_at: 0,
+            },
+        )
+        .unwrap();
+
+        let gone = dir.join("3_c.txt");
+        let entries = [&deleted, &copy, &gone].map(|path| TrashEntry {
+            path: path.clone(),
+            ..entry("", 1, 0)
+        });
+        let entries: Vec<&TrashEntry> = entries.iter().collect();
+        let results = purge_entries(&mut conn, &entries).unwrap();
+
+        assert_eq!(
+            results.iter().map(|r| r.success).collect::<Vec<_>>(),
+            [true, true, false]
+        );
+        assert!(!deleted.exists() && !copy.exists());
+        assert!(trashed_copies::find(&conn, &copy).unwrap().is_none());
+        assert!(operations::get(&conn, "del").unwrap().unwrap().purged);
+        assert!(!operations::get(&conn, "copy").unwrap().unwrap().purged);
+        assert!(operations::list_backup_paths(&conn).unwrap().is_empty());
+        assert!(operations::list_recent_completed(&conn, 10)
+            .unwrap()
+            .iter()
+            .all(|op| op.id != "del"));
+    }
+}
@@ -8,6 +8,9 @@ pub enum AppError {
     #[error("Database error: {0}")]
     Database(#[from] rusqlite::Error),
 
+    #[error("Database pool error: {0}")]
+    Pool(#[from] r2d2::Error),
+
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
@@ -27,6 +30,27 @@ pub enum AppError {
     OperationFailed(String),
 }
 
+impl AppError {
+    /// Stable identifier for the kind of failure, stored alongside failed operations
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Io(e) => match e.kind() {
+                std::io::ErrorKind::NotFound => "not_found",
+                std::io::ErrorKind::PermissionDenied => "permission_denied",
+                std::io::ErrorKind::AlreadyExists => "already_exists",
+                _ => "io",
+            },
+            AppError::Database(_) | AppError::Pool(_) => "database",
+            AppError::Serialization(_) => "serialization",
+            AppError::Http(_) => "http",
+            AppError::FileNotFound(_) => "not_found",
+            AppError::LlmError(_) => "llm",
+            AppError::Config(_) => "config",
+            AppError::OperationFailed(_) => "operation_failed",
+        }
+    }
+}
+
 impl serde::Serialize for AppError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -2,8 +2,11 @@ pub mod commands;
 pub mod error;
 pub mod models;
 pub mod services;
+pub mod storage;
 
-use commands::{file_ops, history, llm, scan, settings};
+use commands::{file_ops, history, llm, scan, settings, trash};
+use storage::Storage;
+use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -11,19 +14,43 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .manage(scan::ScanRegistry::default())
+        .manage(file_ops::DuplicateSearches::default())
         .setup(|app| {
-            // Initialize database
-            if let Err(e) = history::init_database(app.handle()) {
-                eprintln!("Failed to initialize database: {}", e);
+            // Open the database, migrating it to the latest schema
+            let storage = Storage::open(&storage::get_db_path(app.handle()))?;
+
+            // Reconcile operations interrupted by a crash
+            match history::recover_interrupted_operations(&storage) {
+                Ok(recovered) if !recovered.is_empty() => {
+                    eprintln!("Recovered {} interrupted operation(s)", recovered.len());
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Failed to recover interrupted operations: {}", e),
+            }
+
+            // Expire old trash entries according to the retention policy
+            if let Err(e) = trash::enforce_retention(app.handle(), &storage) {
+                eprintln!("Failed to apply trash retention: {}", e);
             }
+
+            app.manage(storage);
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             // Scan commands
             scan::scan_directory,
+            scan::resume_scan,
+            scan::cancel_scan,
+            scan::list_scans,
+            scan::discard_scan,
             // File operation commands
             file_ops::execute_operations,
             file_ops::find_duplicates,
+            file_ops::cancel_find_duplicates,
+            file_ops::resolve_duplicates,
+            file_ops::plan_extension_fixes,
+            file_ops::plan_organize,
             // Settings commands
             settings::get_settings,
             settings::save_settings,
@@ -32,7 +59,18 @@ pub fn run() {
             // History commands
             history::get_operation_history,
             history::undo_operations,
+            history::undo_batch,
+            history::redo_batch,
+            history::get_undo_stack,
+            history::skip_batch,
             history::clear_history,
+            history::export_history,
+            history::import_history,
+            // Trash commands
+            trash::list_trash,
+            trash::restore_trash,
+            trash::purge_trash,
+            trash::apply_trash_retention,
             // LLM commands
             llm::classify_files,
             llm::classify_single_file,
@@ -0,0 +1,58 @@
+use super::OperationType;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Files with identical content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    /// BLAKE3 hash of the content, in hex
+    pub hash: String,
+    pub files: Vec<PathBuf>,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DuplicateOptions {
+    /// Compare files byte for byte once their hashes match, rather than
+    /// trusting the hash
+    pub verify: bool,
+    /// Files read at once; `None` or 0 picks one per CPU core
+    pub threads: Option<usize>,
+}
+
+/// Which copy in a duplicate group `resolve_duplicates` keeps
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum KeepStrategy {
+    /// The least recently modified copy
+    Oldest,
+    /// The most recently modified copy
+    Newest,
+    ShortestPath,
+    /// A copy inside `path`
+    PreferredDirectory {
+        path: PathBuf,
+    },
+    /// A copy already inside one of the categories' target folders
+    CategoryFolder,
+}
+
+/// What `resolve_duplicates` does with the copies it doesn't keep
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateAction {
+    Delete,
+    Hardlink,
+    Symlink,
+}
+
+impl DuplicateAction {
+    pub fn operation_type(&self) -> OperationType {
+        match self {
+            DuplicateAction::Delete => OperationType::Delete,
+            DuplicateAction::Hardlink => OperationType::Hardlink,
+            DuplicateAction::Symlink => OperationType::Symlink,
+        }
+    }
+}
@@ -9,11 +9,26 @@ pub struct FileItem {
     pub extension: Option<String>,
     pub size: u64,
     pub file_type: FileType,
+    /// BLAKE3 hash of the content in hex, once `find_duplicates` has read it
     pub hash: Option<String>,
+    /// Creation time on the filesystem (unix seconds). A photo's capture
+    /// time stays in `metadata.photo.taken_at` rather than replacing it: EXIF
+    /// has no time zone, and scan filters and the index go by the filesystem.
     pub created_at: i64,
     pub modified_at: i64,
     pub category: Option<String>,
     pub metadata: FileMetadata,
+    /// Where a `Symlink` item points
+    #[serde(default)]
+    pub link_target: Option<PathBuf>,
+    /// The content's signature belongs to a different format than the
+    /// extension names
+    #[serde(default)]
+    pub extension_mismatch: bool,
+    /// Extension matching the content, set for a mismatched or missing
+    /// extension
+    #[serde(default)]
+    pub suggested_extension: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -25,10 +40,25 @@ pub enum FileType {
     Audio,
     Archive,
     Code,
+    /// A symbolic link, recorded as the link itself rather than its target
+    Symlink,
     Other,
 }
 
 impl FileType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileType::Document => "document",
+            FileType::Image => "image",
+            FileType::Video => "video",
+            FileType::Audio => "audio",
+            FileType::Archive => "archive",
+            FileType::Code => "code",
+            FileType::Symlink => "symlink",
+            FileType::Other => "other",
+        }
+    }
+
     pub fn from_extension(ext: &str) -> Self {
         match ext.to_lowercase().as_str() {
             // Documents
@@ -55,8 +85,127 @@ impl FileType {
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct FileMetadata {
+    /// Sniffed from the file's content rather than its extension
     pub mime_type: Option<String>,
+    /// Image width and height in pixels
     pub dimensions: Option<(u32, u32)>,
+    /// Audio or video length in seconds
     pub duration: Option<u64>,
+    /// The start of a text file, or of a document's body text
     pub preview_text: Option<String>,
+    /// EXIF data of a photo
+    #[serde(default)]
+    pub photo: Option<PhotoMetadata>,
+    /// Tags of a music file
+    #[serde(default)]
+    pub audio: Option<AudioTags>,
+    /// Properties of a PDF, Office Open XML or OpenDocument file
+    #[serde(default)]
+    pub document: Option<DocumentMetadata>,
+    /// What a zip, tar or 7z archive holds
+    #[serde(default)]
+    pub archive: Option<ArchiveSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PhotoMetadata {
+    /// When the photo was taken, on the camera's clock (`YYYY-MM-DDTHH:MM:SS`;
+    /// EXIF records no time zone)
+    pub taken_at: Option<String>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub gps: Option<GpsPosition>,
+    /// EXIF orientation, 1 (upright) to 8. `dimensions` are as stored, before
+    /// rotating.
+    pub orientation: Option<u16>,
+}
+
+/// Decimal degrees, negative for south and west; altitude in meters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+/// From ID3v2 frames, Vorbis comments or MP4 metadata atoms
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AudioTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub track: Option<u32>,
+    pub track_total: Option<u32>,
+    pub disc: Option<u32>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Pages, or slides for a presentation
+    pub page_count: Option<u32>,
+    /// As recorded in the document (`YYYY-MM-DDTHH:MM:SS`), without its time
+    /// zone
+    pub created_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarBz2,
+    TarXz,
+    #[serde(rename = "7z")]
+    SevenZip,
+}
+
+/// What an archive's content adds up to, when one kind of file dominates it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveKind {
+    PhotoAlbum,
+    MusicAlbum,
+    Videos,
+    Documents,
+    /// A project's sources, recognized by a build file such as `Cargo.toml`
+    /// or `Makefile` or by mostly holding code
+    SourceCode,
+}
+
+impl ArchiveKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArchiveKind::PhotoAlbum => "photo_album",
+            ArchiveKind::MusicAlbum => "music_album",
+            ArchiveKind::Videos => "videos",
+            ArchiveKind::Documents => "documents",
+            ArchiveKind::SourceCode => "source_code",
+        }
+    }
+}
+
+/// From an archive's listing; nothing is extracted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveSummary {
+    pub format: ArchiveFormat,
+    /// Files in the archive, not counting folders
+    pub entry_count: u64,
+    pub uncompressed_size: u64,
+    /// The entries' file types, most common first
+    pub content_types: Vec<ArchiveContentType>,
+    pub kind: Option<ArchiveKind>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveContentType {
+    pub file_type: FileType,
+    pub count: u64,
+    /// Uncompressed bytes
+    pub size: u64,
 }
@@ -1,9 +1,13 @@
 pub mod category;
+pub mod duplicate;
 pub mod file_item;
 pub mod operation;
+pub mod scan;
 pub mod settings;
 
 pub use category::*;
+pub use duplicate::*;
 pub use file_item::*;
 pub use operation::*;
+pub use scan::*;
 pub use settings::*;
@@ -13,6 +13,25 @@ pub struct Operation {
     pub status: OperationStatus,
     pub batch_id: Option<String>,
     pub backup_path: Option<PathBuf>,
+    /// State of the resulting file right after the operation, used to detect
+    /// external modifications before undoing
+    #[serde(default)]
+    pub fingerprint: Option<FileFingerprint>,
+    /// Machine-readable failure reason, see `AppError::code`
+    #[serde(default)]
+    pub error_code: Option<String>,
+    /// The trashed original in `backup_path` was permanently deleted, so
+    /// the operation can't be undone
+    #[serde(default)]
+    pub purged: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileFingerprint {
+    pub size: u64,
+    pub modified_at: i64,
+    /// Only recorded for files small enough to hash cheaply
+    pub hash: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -22,6 +41,63 @@ pub enum OperationType {
     Copy,
     Rename,
     Delete,
+    /// Unpacks an archive into a new folder, leaving the archive in place
+    Extract,
+    /// Replaces the source with a hard link to the destination, keeping the
+    /// replaced file in the trash
+    Hardlink,
+    /// Replaces the source with a symbolic link to the destination, keeping
+    /// the replaced file in the trash
+    Symlink,
+}
+
+impl Operation {
+    /// Where the file lives once the operation has been applied
+    pub fn result_path(&self) -> Option<&PathBuf> {
+        match self.operation_type {
+            OperationType::Delete => self.backup_path.as_ref(),
+            OperationType::Hardlink | OperationType::Symlink => Some(&self.source_path),
+            _ => self.destination_path.as_ref(),
+        }
+    }
+}
+
+impl OperationType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationType::Move => "move",
+            OperationType::Copy => "copy",
+            OperationType::Rename => "rename",
+            OperationType::Delete => "delete",
+            OperationType::Extract => "extract",
+            OperationType::Hardlink => "hardlink",
+            OperationType::Symlink => "symlink",
+        }
+    }
+
+    /// Whether the source is left where it was, so undoing only has to get
+    /// rid of what the operation created
+    pub fn keeps_source(&self) -> bool {
+        matches!(self, OperationType::Copy | OperationType::Extract)
+    }
+
+    /// Whether the source is swapped for a link, with the original file kept
+    /// in `backup_path`
+    pub fn replaces_with_link(&self) -> bool {
+        matches!(self, OperationType::Hardlink | OperationType::Symlink)
+    }
+
+    pub fn from_db_str(s: &str) -> Self {
+        match s {
+            "copy" => OperationType::Copy,
+            "rename" => OperationType::Rename,
+            "delete" => OperationType::Delete,
+            "extract" => OperationType::Extract,
+            "hardlink" => OperationType::Hardlink,
+            "symlink" => OperationType::Symlink,
+            _ => OperationType::Move,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -34,12 +110,104 @@ pub enum OperationStatus {
     Undone,
 }
 
+impl OperationStatus {
+    /// Value of the `status` column; a failure's message lives in `error_message`
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            OperationStatus::Pending => "pending",
+            OperationStatus::InProgress => "in_progress",
+            OperationStatus::Completed => "completed",
+            OperationStatus::Failed(_) => "failed",
+            OperationStatus::Undone => "undone",
+        }
+    }
+
+    pub fn error_message(&self) -> Option<&str> {
+        match self {
+            OperationStatus::Failed(message) => Some(message),
+            _ => None,
+        }
+    }
+
+    pub fn from_db(status: &str, error_message: Option<String>) -> Self {
+        match status {
+            "pending" => OperationStatus::Pending,
+            "in_progress" => OperationStatus::InProgress,
+            "completed" => OperationStatus::Completed,
+            "undone" => OperationStatus::Undone,
+            "failed" => OperationStatus::Failed(error_message.unwrap_or_default()),
+            // Older databases stored the message inline as `failed:<msg>`
+            other => match other.strip_prefix("failed:") {
+                Some(message) => OperationStatus::Failed(message.to_string()),
+                None => OperationStatus::Failed(format!("Unknown status: {}", other)),
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OperationBatch {
     pub id: String,
     pub operations: Vec<Operation>,
     pub created_at: i64,
     pub description: String,
+    #[serde(default)]
+    pub source: BatchSource,
+    /// Deepest folder containing every source path of the batch
+    #[serde(default)]
+    pub root_folder: Option<PathBuf>,
+    #[serde(default)]
+    pub operation_count: usize,
+    #[serde(default)]
+    pub failed_count: usize,
+    #[serde(default)]
+    pub total_bytes: u64,
+}
+
+/// Filters for `get_operation_history`; every field is optional
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HistoryFilter {
+    /// Earliest batch timestamp (inclusive, unix seconds)
+    pub from: Option<i64>,
+    /// Latest batch timestamp (inclusive, unix seconds)
+    pub to: Option<i64>,
+    /// Only batches containing an operation of this type
+    pub operation_type: Option<OperationType>,
+    /// Only batches touching a path containing this substring
+    pub path: Option<String>,
+    /// Only this batch
+    pub batch_id: Option<String>,
+}
+
+/// What triggered a batch of operations
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchSource {
+    #[default]
+    Manual,
+    Rule,
+    Watcher,
+    Llm,
+}
+
+impl BatchSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BatchSource::Manual => "manual",
+            BatchSource::Rule => "rule",
+            BatchSource::Watcher => "watcher",
+            BatchSource::Llm => "llm",
+        }
+    }
+
+    pub fn from_db_str(s: &str) -> Self {
+        match s {
+            "rule" => BatchSource::Rule,
+            "watcher" => BatchSource::Watcher,
+            "llm" => BatchSource::Llm,
+            _ => BatchSource::Manual,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,123 @@
+use crate::models::{FileItem, FileType};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanOptions {
+    pub path: PathBuf,
+    pub recursive: bool,
+    pub include_hidden: bool,
+    /// Walker threads; `None` or 0 picks one per CPU core
+    #[serde(default)]
+    pub threads: Option<usize>,
+    /// Deepest level to descend to, the scanned folder's own files being 1.
+    /// Ignored when `recursive` is off.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub filters: ScanFilters,
+    /// Descend into symlinked folders and describe linked files by their
+    /// target. Off by default, which records every link as a `Symlink` item.
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// Don't cross into other mounted filesystems
+    #[serde(default)]
+    pub same_file_system: bool,
+    /// Leave out sockets, FIFOs and device nodes
+    #[serde(default = "default_skip_special_files")]
+    pub skip_special_files: bool,
+    /// Read each file's headers to fill in its `metadata`. Off by default,
+    /// since it opens every file.
+    #[serde(default)]
+    pub extract_metadata: bool,
+    /// Classify files by their content's signature rather than their
+    /// extension, flagging extensions that don't match
+    #[serde(default)]
+    pub detect_file_types: bool,
+}
+
+fn default_skip_special_files() -> bool {
+    true
+}
+
+/// Narrows what a scan returns. Glob patterns are matched against the path
+/// relative to the scanned folder; a pattern without `/` matches a name at
+/// any depth, as in `.gitignore`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanFilters {
+    /// When non-empty, only files matching one of these are returned
+    pub include: Vec<String>,
+    /// Files and folders to skip; excluded folders are not descended into
+    pub exclude: Vec<String>,
+    /// Also honor `.gitignore` files. `.fileogignore` files always apply.
+    pub respect_gitignore: bool,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Modification time range (inclusive, unix seconds)
+    pub modified_from: Option<i64>,
+    pub modified_to: Option<i64>,
+    /// Creation time range (inclusive, unix seconds)
+    pub created_from: Option<i64>,
+    pub created_to: Option<i64>,
+    /// When non-empty, only files of these types are returned
+    pub file_types: Vec<FileType>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanErrorKind {
+    PermissionDenied,
+    /// A symlink whose target doesn't exist
+    BrokenSymlink,
+    /// The entry vanished between listing and reading it
+    NotFound,
+    SymlinkLoop,
+    Other,
+}
+
+/// An entry the scan couldn't read; the rest of the scan carries on
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanError {
+    pub path: Option<PathBuf>,
+    pub kind: ScanErrorKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub scan_id: String,
+    pub files: Vec<FileItem>,
+    pub errors: Vec<ScanError>,
+    /// Stopped by `cancel_scan`; `resume_scan` picks up where it left off
+    pub cancelled: bool,
+    #[serde(default)]
+    pub changes: ScanChanges,
+}
+
+/// How the scanned files differ from what the file index last recorded
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanChanges {
+    /// IDs of files the index didn't have yet
+    pub added: Vec<String>,
+    /// IDs of files whose size, modification time or inode changed
+    pub modified: Vec<String>,
+    /// IDs of indexed files that no longer exist. Only a finished scan
+    /// looks for these.
+    pub removed: Vec<String>,
+    /// Files taken from the index without being read again
+    pub unchanged: usize,
+}
+
+/// A scan that stopped before finishing and can be resumed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanCheckpoint {
+    pub scan_id: String,
+    pub options: ScanOptions,
+    /// "running" for a scan that is still going or was cut off by a crash,
+    /// "cancelled" for one stopped with `cancel_scan`
+    pub status: String,
+    pub file_count: usize,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
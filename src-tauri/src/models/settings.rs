@@ -6,6 +6,8 @@ pub struct AppSettings {
     pub language: String,
     pub llm: LlmSettings,
     pub prompts: PromptSettings,
+    #[serde(default)]
+    pub trash: TrashSettings,
 }
 
 impl Default for AppSettings {
@@ -15,10 +17,22 @@ impl Default for AppSettings {
             language: "zh-CN".to_string(),
             llm: LlmSettings::default(),
             prompts: PromptSettings::default(),
+            trash: TrashSettings::default(),
         }
     }
 }
 
+/// Retention policy for deleted files; `None` disables a limit
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TrashSettings {
+    pub max_age_days: Option<u32>,
+    pub max_total_bytes: Option<u64>,
+    /// Use the desktop's trash (freedesktop.org spec, Linux only) instead of
+    /// the app's own trash folder
+    #[serde(default)]
+    pub use_system_trash: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct LlmSettings {
     pub enabled: bool,
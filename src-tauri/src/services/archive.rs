@@ -0,0 +1,517 @@
+//! Lists zip, tar (plain or gzip, bzip2 or xz compressed) and 7z archives to
+//! tell what they hold, and unpacks them for `Extract` operations.
+
+use crate::models::{ArchiveContentType, ArchiveFormat, ArchiveKind, ArchiveSummary, FileType};
+use crate::services::metadata;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// A compressed tarball has to be decompressed in full to be listed, so
+/// larger ones are skipped
+const MAX_COMPRESSED_TAR_LEN: u64 = 512 * 1024 * 1024;
+
+/// Build files that mark a source tree, at the archive's root or one folder
+/// down (tarballs usually wrap everything in `name-version/`)
+const PROJECT_FILES: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "Makefile",
+    "CMakeLists.txt",
+    "configure",
+    "meson.build",
+    "setup.py",
+    "pyproject.toml",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "Gemfile",
+    "composer.json",
+];
+
+/// Files operating systems slip into archives, which say nothing about
+/// their content
+const IGNORED_FILES: &[&str] = &[".DS_Store", "Thumbs.db", "desktop.ini"];
+
+/// An archive's listing and what it adds up to
+pub struct Inspection {
+    pub summary: ArchiveSummary,
+    /// File names from the archive, one per line, up to `max_chars`
+    /// characters
+    pub listing: String,
+}
+
+struct Entry {
+    name: String,
+    size: u64,
+}
+
+/// The archive format behind `mime`. Gzip, bzip2 and xz are taken to be
+/// tarballs; listing or extracting anything else fails.
+pub fn format_of(mime: &str) -> Option<ArchiveFormat> {
+    Some(match mime {
+        "application/zip" => ArchiveFormat::Zip,
+        "application/x-tar" => ArchiveFormat::Tar,
+        "application/gzip" => ArchiveFormat::TarGz,
+        "application/x-bzip2" => ArchiveFormat::TarBz2,
+        "application/x-xz" => ArchiveFormat::TarXz,
+        "application/x-7z-compressed" => ArchiveFormat::SevenZip,
+        _ => return None,
+    })
+}
+
+/// The format of the archive at `path`, from its signature. A compressed
+/// file only counts as a tarball if it decompresses to a tar header.
+pub fn detect(path: &Path) -> Option<ArchiveFormat> {
+    let header = metadata::read_header(path).ok()?;
+    let format = infer::get(&header).and_then(|kind| format_of(kind.mime_type()))?;
+
+    let file = BufReader::new(File::open(path).ok()?);
+    let decoder: Box<dyn Read> = match format {
+        ArchiveFormat::TarGz => Box::new(flate2::read::MultiGzDecoder::new(file)),
+        ArchiveFormat::TarBz2 => Box::new(bzip2::read::MultiBzDecoder::new(file)),
+        ArchiveFormat::TarXz => Box::new(lzma_rust2::XzReader::new(file, true)),
+        _ => return Some(format),
+    };
+
+    let mut block = Vec::new();
+    decoder.take(512).read_to_end(&mut block).ok()?;
+    (block.get(257..262) == Some(b"ustar".as_slice())).then_some(format)
+}
+
+/// `None` if `mime` isn't an archive format or the archive can't be listed
+pub fn inspect(path: &Path, mime: &str, max_chars: usize) -> Option<Inspection> {
+    let format = format_of(mime)?;
+    let compressed_tar = matches!(
+        format,
+        ArchiveFormat::TarGz | ArchiveFormat::TarBz2 | ArchiveFormat::TarXz
+    );
+    if compressed_tar && std::fs::metadata(path).ok()?.len() > MAX_COMPRESSED_TAR_LEN {
+        return None;
+    }
+
+    let entries: Vec<Entry> = list(path, &format)
+        .ok()?
+        .into_iter()
+        .filter(|entry| !is_ignored(&entry.name))
+        .collect();
+
+    let mut listing = String::new();
+    for entry in &entries {
+        if listing.chars().count() + entry.name.chars().count() > max_chars {
+            break;
+        }
+        listing.push_str(&entry.name);
+        listing.push('\n');
+    }
+
+    Some(Inspection {
+        summary: summarize(format, &entries),
+        listing: listing.trim_end().to_string(),
+    })
+}
+
+fn list(path: &Path, format: &ArchiveFormat) -> io::Result<Vec<Entry>> {
+    let file = File::open(path)?;
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut archive = zip::ZipArchive::new(file).map_err(io::Error::other)?;
+            let mut entries = Vec::new();
+            for index in 0..archive.len() {
+                let entry = archive.by_index_raw(index).map_err(io::Error::other)?;
+                if !entry.is_dir() {
+                    entries.push(Entry {
+                        name: entry.name().to_string(),
+                        size: entry.size(),
+                    });
+                }
+            }
+            Ok(entries)
+        }
+        ArchiveFormat::Tar => tar_entries(tar::Archive::new(file).entries_with_seek()?),
+        ArchiveFormat::TarGz => {
+            let decoder = flate2::read::MultiGzDecoder::new(BufReader::new(file));
+            tar_entries(tar::Archive::new(decoder).entries()?)
+        }
+        ArchiveFormat::TarBz2 => {
+            let decoder = bzip2::read::MultiBzDecoder::new(BufReader::new(file));
+            tar_entries(tar::Archive::new(decoder).entries()?)
+        }
+        ArchiveFormat::TarXz => {
+            let decoder = lzma_rust2::XzReader::new(BufReader::new(file), true);
+            tar_entries(tar::Archive::new(decoder).entries()?)
+        }
+        ArchiveFormat::SevenZip => {
+            let archive = sevenz_rust2::Archive::open(path).map_err(io::Error::other)?;
+            Ok(archive
+                .files
+                .iter()
+                .filter(|entry| !entry.is_directory())
+                .map(|entry| Entry {
+                    name: entry.name().to_string(),
+                    size: entry.size(),
+                })
+                .collect())
+        }
+    }
+}
+
+fn tar_entries<R: Read>(entries: tar::Entries<R>) -> io::Result<Vec<Entry>> {
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.header().entry_type().is_file() {
+            files.push(Entry {
+                name: entry.path()?.to_string_lossy().to_string(),
+                size: entry.size(),
+            });
+        }
+    }
+    Ok(files)
+}
+
+fn is_ignored(name: &str) -> bool {
+    name.starts_with("__MACOSX/")
+        || file_name(name).is_some_and(|file_name| IGNORED_FILES.contains(&file_name))
+}
+
+/// The last component of an entry name. Archives made on Windows may use
+/// backslashes.
+fn file_name(name: &str) -> Option<&str> {
+    name.split(['/', '\\']).rfind(|part| !part.is_empty())
+}
+
+fn summarize(format: ArchiveFormat, entries: &[Entry]) -> ArchiveSummary {
+    let mut content_types: Vec<ArchiveContentType> = Vec::new();
+    for entry in entries {
+        let file_type = file_name(&entry.name)
+            .and_then(|name| Path::new(name).extension())
+            .map(|ext| FileType::from_extension(&ext.to_string_lossy()))
+            .unwrap_or(FileType::Other);
+
+        match content_types.iter_mut().find(|t| t.file_type == file_type) {
+            Some(content_type) => {
+                content_type.count += 1;
+                content_type.size += entry.size;
+            }
+            None => content_types.push(ArchiveContentType {
+                file_type,
+                count: 1,
+                size: entry.size,
+            }),
+        }
+    }
+    content_types.sort_by(|a, b| b.count.cmp(&a.count).then(b.size.cmp(&a.size)));
+
+    let entry_count = entries.len() as u64;
+    ArchiveSummary {
+        format,
+        entry_count,
+        uncompressed_size: entries.iter().map(|e| e.size).sum(),
+        kind: kind(entries, &content_types),
+        content_types,
+    }
+}
+
+/// A source tree if it has a project's build file near the top, otherwise
+/// whatever type makes up three quarters of the files
+fn kind(entries: &[Entry], content_types: &[ArchiveContentType]) -> Option<ArchiveKind> {
+    let has_project_file = entries.iter().any(|entry| {
+        let parts: Vec<&str> = entry
+            .name
+            .split(['/', '\\'])
+            .filter(|part| !part.is_empty() && *part != ".")
+            .collect();
+        parts.len() <= 2
+            && parts
+                .last()
+                .is_some_and(|name| PROJECT_FILES.contains(name))
+    });
+    if has_project_file {
+        return Some(ArchiveKind::SourceCode);
+    }
+
+    let dominant = content_types.first()?;
+    if dominant.count * 4 < entries.len() as u64 * 3 {
+        return None;
+    }
+    match dominant.file_type {
+        FileType::Image => Some(ArchiveKind::PhotoAlbum),
+        FileType::Audio => Some(ArchiveKind::MusicAlbum),
+        FileType::Video => Some(ArchiveKind::Videos),
+        FileType::Document => Some(ArchiveKind::Documents),
+        FileType::Code => Some(ArchiveKind::SourceCode),
+        _ => None,
+    }
+}
+
+/// Unpacks the archive at `path` into `destination`, which must not exist
+/// yet. Entries are written to a temporary folder beside it that is renamed
+/// once everything is out, so `destination` only ever holds a complete
+/// extraction. Entries that would land outside it are refused.
+pub fn extract(path: &Path, destination: &Path) -> io::Result<()> {
+    if destination.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", destination.display()),
+        ));
+    }
+
+    let header = metadata::read_header(path)?;
+    let format = infer::get(&header)
+        .and_then(|kind| format_of(kind.mime_type()))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Not a supported archive"))?;
+
+    let partial = partial_path(destination);
+    std::fs::create_dir_all(&partial)?;
+
+    match unpack(path, &format, &partial).and_then(|_| std::fs::rename(&partial, destination)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&partial);
+            Err(e)
+        }
+    }
+}
+
+/// The temporary folder an extraction into `destination` is written to
+pub fn partial_path(destination: &Path) -> PathBuf {
+    let name = destination
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    destination.with_file_name(format!(".{}.partial", name))
+}
+
+fn unpack(path: &Path, format: &ArchiveFormat, destination: &Path) -> io::Result<()> {
+    let file = File::open(path)?;
+
+    match format {
+        ArchiveFormat::Zip => zip::ZipArchive::new(file)
+            .and_then(|mut archive| archive.extract(destination))
+            .map_err(io::Error::other),
+        ArchiveFormat::Tar => tar::Archive::new(file).unpack(destination),
+        ArchiveFormat::TarGz => {
+            let decoder = flate2::read::MultiGzDecoder::new(BufReader::new(file));
+            tar::Archive::new(decoder).unpack(destination)
+        }
+        ArchiveFormat::TarBz2 => {
+            let decoder = bzip2::read::MultiBzDecoder::new(BufReader::new(file));
+            tar::Archive::new(decoder).unpack(destination)
+        }
+        ArchiveFormat::TarXz => {
+            let decoder = lzma_rust2::XzReader::new(BufReader::new(file), true);
+            tar::Archive::new(decoder).unpack(destination)
+        }
+        ArchiveFormat::SevenZip => {
+            sevenz_rust2::decompress(file, destination).map_err(io::Error::other)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("fileog-archive-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn zip_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            if let Some(folder) = name.strip_suffix('/') {
+                zip.add_directory(folder, SimpleFileOptions::default())
+                    .unwrap();
+            } else {
+                zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+                zip.write_all(content).unwrap();
+            }
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn tar_gz_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        let mut tar = tar::Builder::new(encoder);
+        for (name, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, name, *content).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn write(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = temp_path(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn entries(names: &[&str]) -> Vec<Entry> {
+        names
+            .iter()
+            .map(|name| Entry {
+                name: name.to_string(),
+                size: 10,
+            })
+            .collect()
+    }
+
+    fn kind_of(names: &[&str]) -> Option<ArchiveKind> {
+        summarize(ArchiveFormat::Zip, &entries(names)).kind
+    }
+
+    #[test]
+    fn test_photo_album() {
+        let bytes = zip_bytes(&[
+            ("Trip/", b""),
+            ("Trip/IMG_001.jpg", &[1; 300]),
+            ("Trip/IMG_002.JPG", &[2; 200]),
+            ("Trip/IMG_003.heic", &[3; 100]),
+            ("Trip/notes.txt", b"beach"),
+            ("Trip/.DS_Store", &[0; 50]),
+            ("__MACOSX/Trip/._IMG_001.jpg", &[0; 50]),
+        ]);
+        let path = write("album.zip", &bytes);
+
+        let inspection = inspect(&path, "application/zip", 1000).unwrap();
+        let summary = inspection.summary;
+        assert_eq!(summary.format, ArchiveFormat::Zip);
+        assert_eq!(summary.entry_count, 4);
+        assert_eq!(summary.uncompressed_size, 605);
+        assert_eq!(summary.kind, Some(ArchiveKind::PhotoAlbum));
+        assert_eq!(summary.content_types[0].file_type, FileType::Image);
+        assert_eq!(summary.content_types[0].count, 3);
+        assert_eq!(summary.content_types[0].size, 600);
+        assert_eq!(
+            inspection.listing,
+            "Trip/IMG_001.jpg\nTrip/IMG_002.JPG\nTrip/IMG_003.heic\nTrip/notes.txt"
+        );
+
+        // The listing stops at a whole name
+        let short = inspect(&path, "application/zip", 20).unwrap();
+        assert_eq!(short.listing, "Trip/IMG_001.jpg");
+    }
+
+    #[test]
+    fn test_source_tarball() {
+        let bytes = tar_gz_bytes(&[
+            ("tool-1.0/Cargo.toml", b"[package]"),
+            ("tool-1.0/README.md", b"# tool"),
+            ("tool-1.0/logo.png", &[0; 10]),
+            ("tool-1.0/docs/guide.pdf", &[0; 10]),
+        ]);
+        let path = write("tool.tar.gz", &bytes);
+
+        assert_eq!(detect(&path), Some(ArchiveFormat::TarGz));
+        let summary = inspect(&path, "application/gzip", 1000).unwrap().summary;
+        assert_eq!(summary.format, ArchiveFormat::TarGz);
+        assert_eq!(summary.entry_count, 4);
+        assert_eq!(summary.kind, Some(ArchiveKind::SourceCode));
+    }
+
+    #[test]
+    fn test_project_file_near_the_top() {
+        assert_eq!(
+            kind_of(&["Makefile", "a.png", "b.png"]),
+            Some(ArchiveKind::SourceCode)
+        );
+        assert_eq!(
+            kind_of(&["./proj/Makefile", "a.png"]),
+            Some(ArchiveKind::SourceCode)
+        );
+        assert_eq!(
+            kind_of(&["proj\\Cargo.toml", "a.png"]),
+            Some(ArchiveKind::SourceCode)
+        );
+        // Too deep to describe the archive
+        assert_eq!(
+            kind_of(&["proj/vendor/lib/Makefile", "a.png", "b.png", "c.png"]),
+            Some(ArchiveKind::PhotoAlbum)
+        );
+    }
+
+    #[test]
+    fn test_no_dominant_type() {
+        assert_eq!(kind_of(&["a.jpg", "b.jpg", "c.mp3", "d.pdf"]), None);
+        assert_eq!(
+            kind_of(&["a.jpg", "b.jpg", "c.jpg", "d.pdf"]),
+            Some(ArchiveKind::PhotoAlbum)
+        );
+        assert_eq!(
+            kind_of(&["a.mp3", "b.flac", "c.ogg", "cover.jpg"]),
+            Some(ArchiveKind::MusicAlbum)
+        );
+        // Mostly files of no particular type
+        assert_eq!(kind_of(&["a.bin", "b.dat", "c.xyz"]), None);
+        assert_eq!(kind_of(&[]), None);
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        assert!(is_ignored(".DS_Store"));
+        assert!(is_ignored("photos/.DS_Store"));
+        assert!(is_ignored("photos\\Thumbs.db"));
+        assert!(is_ignored("__MACOSX/photos/._a.jpg"));
+
+        assert!(!is_ignored("photos/a.jpg"));
+        assert!(!is_ignored("photos/__MACOSX/a.jpg"));
+        assert!(!is_ignored("my.DS_Store.txt"));
+    }
+
+    #[test]
+    fn test_partial_path() {
+        assert_eq!(
+            partial_path(Path::new("/data/Trip")),
+            Path::new("/data/.Trip.partial")
+        );
+        assert_eq!(
+            partial_path(Path::new("/data/Trip/")),
+            Path::new("/data/.Trip.partial")
+        );
+    }
+
+    #[test]
+    fn test_extract() {
+        let bytes = zip_bytes(&[("a.txt", b"a"), ("sub/b.txt", b"b")]);
+        let archive = write("extract.zip", &bytes);
+        let destination = temp_path("extract-out");
+
+        extract(&archive, &destination).unwrap();
+        assert_eq!(std::fs::read(destination.join("sub/b.txt")).unwrap(), b"b");
+        assert!(!partial_path(&destination).exists());
+
+        // Never into something that's already there
+        let error = extract(&archive, &destination).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(destination.join("a.txt")).unwrap(), b"a");
+    }
+
+    #[test]
+    fn test_failed_extract_leaves_nothing_behind() {
+        // Cut off before the central directory
+        let bytes = zip_bytes(&[("a.txt", &[7; 400]), ("b.txt", &[8; 400])]);
+        let archive = write("truncated.zip", &bytes[..200]);
+        let destination = temp_path("truncated-out");
+
+        assert!(extract(&archive, &destination).is_err());
+        assert!(!destination.exists());
+        assert!(!partial_path(&destination).exists());
+
+        let not_archive = write("plain.txt", b"just text");
+        assert_eq!(
+            extract(&not_archive, &destination).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(!partial_path(&destination).exists());
+    }
+}
@@ -0,0 +1,1122 @@
+//! Reads music tags: ID3v2 frames (MP3), Vorbis comments (FLAC, Ogg Vorbis,
+//! Opus) and MP4 metadata atoms (M4A).
+
+use crate::models::AudioTags;
+use crate::services::media::{be_uint, find_box, read_array, read_vec};
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+use std::path::Path;
+
+/// Tags are read into memory up to this size; only embedded artwork gets
+/// anywhere near it
+const MAX_TAG_LEN: u64 = 16 * 1024 * 1024;
+
+/// How many FLAC blocks or Ogg pages to look through for the tags
+const MAX_STEPS: usize = 256;
+
+/// `None` if the format isn't supported or the file has no tags
+pub fn read(path: &Path, mime: &str) -> Option<AudioTags> {
+    let parse: fn(&mut File) -> Option<AudioTags> = match mime {
+        "audio/mpeg" => id3v2_tags,
+        "audio/x-flac" => flac_tags,
+        "audio/ogg" | "audio/opus" => ogg_tags,
+        "audio/m4a" | "video/mp4" => mp4_tags,
+        _ => return None,
+    };
+
+    let mut file = File::open(path).ok()?;
+    parse(&mut file).filter(|tags| !is_empty(tags))
+}
+
+fn is_empty(tags: &AudioTags) -> bool {
+    tags.title.is_none()
+        && tags.artist.is_none()
+        && tags.album_artist.is_none()
+        && tags.album.is_none()
+        && tags.track.is_none()
+        && tags.disc.is_none()
+        && tags.year.is_none()
+        && tags.genre.is_none()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Title,
+    Artist,
+    AlbumArtist,
+    Album,
+    /// `3` or `3/12`
+    Track,
+    TrackTotal,
+    /// `1` or `1/2`
+    Disc,
+    /// A date; only the year is kept
+    Year,
+    /// A name, or an ID3v1 genre number such as `17` or `(17)`
+    Genre,
+}
+
+/// Stores a tag value; when a format repeats a field, the first value wins
+fn apply(tags: &mut AudioTags, field: Field, value: &str) {
+    let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    if value.is_empty() {
+        return;
+    }
+
+    let (number, total) = match value.split_once('/') {
+        Some((number, total)) => (number.trim().parse().ok(), total.trim().parse().ok()),
+        None => (value.parse().ok(), None),
+    };
+
+    match field {
+        Field::Title => set(&mut tags.title, value.to_string()),
+        Field::Artist => set(&mut tags.artist, value.to_string()),
+        Field::AlbumArtist => set(&mut tags.album_artist, value.to_string()),
+        Field::Album => set(&mut tags.album, value.to_string()),
+        Field::Track => {
+            if let Some(number) = number {
+                set(&mut tags.track, number);
+            }
+            if let Some(total) = total {
+                set(&mut tags.track_total, total);
+            }
+        }
+        Field::TrackTotal => {
+            if let Some(total) = number {
+                set(&mut tags.track_total, total);
+            }
+        }
+        Field::Disc => {
+            if let Some(number) = number {
+                set(&mut tags.disc, number);
+            }
+        }
+        Field::Year => {
+            if let Some(year) = value.get(..4).and_then(|y| y.parse().ok()) {
+                set(&mut tags.year, year);
+            }
+        }
+        Field::Genre => set(&mut tags.genre, genre_name(value)),
+    }
+}
+
+fn set<T>(slot: &mut Option<T>, value: T) {
+    if slot.is_none() {
+        *slot = Some(value);
+    }
+}
+
+/// Resolves ID3v1 genre references: `17`, `(17)`, and `(17)Rock`, where the
+/// refinement after the reference wins
+fn genre_name(value: &str) -> String {
+    let (reference, refinement) = match value.strip_prefix('(') {
+        Some(rest) => match rest.split_once(')') {
+            Some((number, refinement)) => (number, refinement.trim()),
+            None => return value.to_string(),
+        },
+        None => (value, ""),
+    };
+
+    if !refinement.is_empty() {
+        return refinement.to_string();
+    }
+
+    reference
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| ID3V1_GENRES.get(index))
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| value.to_string())
+}
+
+/// The 80 ID3v1 genres, then Winamp's extensions up to 191
+const ID3V1_GENRES: [&str; 192] = [
+    "Blues",
+    "Classic Rock",
+    "Country",
+    "Dance",
+    "Disco",
+    "Funk",
+    "Grunge",
+    "Hip-Hop",
+    "Jazz",
+    "Metal",
+    "New Age",
+    "Oldies",
+    "Other",
+    "Pop",
+    "R&B",
+    "Rap",
+    "Reggae",
+    "Rock",
+    "Techno",
+    "Industrial",
+    "Alternative",
+    "Ska",
+    "Death Metal",
+    "Pranks",
+    "Soundtrack",
+    "Euro-Techno",
+    "Ambient",
+    "Trip-Hop",
+    "Vocal",
+    "Jazz+Funk",
+    "Fusion",
+    "Trance",
+    "Classical",
+    "Instrumental",
+    "Acid",
+    "House",
+    "Game",
+    "Sound Clip",
+    "Gospel",
+    "Noise",
+    "Alternative Rock",
+    "Bass",
+    "Soul",
+    "Punk",
+    "Space",
+    "Meditative",
+    "Instrumental Pop",
+    "Instrumental Rock",
+    "Ethnic",
+    "Gothic",
+    "Darkwave",
+    "Techno-Industrial",
+    "Electronic",
+    "Pop-Folk",
+    "Eurodance",
+    "Dream",
+    "Southern Rock",
+    "Comedy",
+    "Cult",
+    "Gangsta",
+    "Top 40",
+    "Christian Rap",
+    "Pop/Funk",
+    "Jungle",
+    "Native American",
+    "Cabaret",
+    "New Wave",
+    "Psychedelic",
+    "Rave",
+    "Showtunes",
+    "Trailer",
+    "Lo-Fi",
+    "Tribal",
+    "Acid Punk",
+    "Acid Jazz",
+    "Polka",
+    "Retro",
+    "Musical",
+    "Rock & Roll",
+    "Hard Rock",
+    // Winamp extensions
+    "Folk",
+    "Folk-Rock",
+    "National Folk",
+    "Swing",
+    "Fast Fusion",
+    "Bebop",
+    "Latin",
+    "Revival",
+    "Celtic",
+    "Bluegrass",
+    "Avantgarde",
+    "Gothic Rock",
+    "Progressive Rock",
+    "Psychedelic Rock",
+    "Symphonic Rock",
+    "Slow Rock",
+    "Big Band",
+    "Chorus",
+    "Easy Listening",
+    "Acoustic",
+    "Humour",
+    "Speech",
+    "Chanson",
+    "Opera",
+    "Chamber Music",
+    "Sonata",
+    "Symphony",
+    "Booty Bass",
+    "Primus",
+    "Porn Groove",
+    "Satire",
+    "Slow Jam",
+    "Club",
+    "Tango",
+    "Samba",
+    "Folklore",
+    "Ballad",
+    "Power Ballad",
+    "Rhythmic Soul",
+    "Freestyle",
+    "Duet",
+    "Punk Rock",
+    "Drum Solo",
+    "A Cappella",
+    "Euro-House",
+    "Dance Hall",
+    "Goa",
+    "Drum & Bass",
+    "Club-House",
+    "Hardcore Techno",
+    "Terror",
+    "Indie",
+    "BritPop",
+    "Negerpunk",
+    "Polsk Punk",
+    "Beat",
+    "Christian Gangsta Rap",
+    "Heavy Metal",
+    "Black Metal",
+    "Crossover",
+    "Contemporary Christian",
+    "Christian Rock",
+    "Merengue",
+    "Salsa",
+    "Thrash Metal",
+    "Anime",
+    "Jpop",
+    "Synthpop",
+    "Abstract",
+    "Art Rock",
+    "Baroque",
+    "Bhangra",
+    "Big Beat",
+    "Breakbeat",
+    "Chillout",
+    "Downtempo",
+    "Dub",
+    "EBM",
+    "Eclectic",
+    "Electro",
+    "Electroclash",
+    "Emo",
+    "Experimental",
+    "Garage",
+    "Global",
+    "IDM",
+    "Illbient",
+    "Industro-Goth",
+    "Jam Band",
+    "Krautrock",
+    "Leftfield",
+    "Lounge",
+    "Math Rock",
+    "New Romantic",
+    "Nu-Breakz",
+    "Post-Punk",
+    "Post-Rock",
+    "Psytrance",
+    "Shoegaze",
+    "Space Rock",
+    "Trop Rock",
+    "World Music",
+    "Neoclassical",
+    "Audiobook",
+    "Audio Theatre",
+    "Neue Deutsche Welle",
+    "Podcast",
+    "Indie Rock",
+    "G-Funk",
+    "Dubstep",
+    "Garage Rock",
+    "Psybient",
+];
+
+// ID3v2
+
+fn syncsafe(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |acc, &b| (acc << 7) | (b & 0x7F) as u64)
+}
+
+/// Undoes unsynchronisation, which inserts a zero after every 0xFF
+fn remove_unsync(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut previous = 0;
+    for &b in bytes {
+        if !(previous == 0xFF && b == 0) {
+            out.push(b);
+        }
+        previous = b;
+    }
+    out
+}
+
+fn id3_field(id: &[u8]) -> Option<Field> {
+    Some(match id {
+        b"TIT2" | b"TT2" => Field::Title,
+        b"TPE1" | b"TP1" => Field::Artist,
+        b"TPE2" | b"TP2" => Field::AlbumArtist,
+        b"TALB" | b"TAL" => Field::Album,
+        b"TRCK" | b"TRK" => Field::Track,
+        b"TPOS" | b"TPA" => Field::Disc,
+        b"TDRC" | b"TYER" | b"TYE" => Field::Year,
+        b"TCON" | b"TCO" => Field::Genre,
+        _ => return None,
+    })
+}
+
+/// Reads an ID3v2.2, 2.3 or 2.4 tag at the start of the file
+fn id3v2_tags(file: &mut File) -> Option<AudioTags> {
+    let header: [u8; 10] = read_array(file)?;
+    if &header[..3] != b"ID3" {
+        return None;
+    }
+    let version = header[3];
+    let flags = header[5];
+    if !(2..=4).contains(&version) {
+        return None;
+    }
+
+    let size = syncsafe(&header[6..10]).min(MAX_TAG_LEN);
+    let mut tag = read_vec(file, size)?;
+    // 2.4 unsynchronises frame by frame instead
+    if flags & 0x80 != 0 && version < 4 {
+        tag = remove_unsync(&tag);
+    }
+
+    let mut pos = 0;
+    if flags & 0x40 != 0 && version >= 3 {
+        let extended = tag.get(..4)?;
+        pos = match version {
+            3 => be_uint(extended) as usize + 4,
+            _ => syncsafe(extended) as usize,
+        };
+    }
+
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+    let mut tags = AudioTags::default();
+
+    while pos + header_len <= tag.len() {
+        let frame = &tag[pos..pos + header_len];
+        // Padding
+        if frame[0] == 0 {
+            break;
+        }
+
+        let size = match version {
+            2 => be_uint(&frame[3..6]),
+            3 => be_uint(&frame[4..8]),
+            _ => syncsafe(&frame[4..8]),
+        } as usize;
+        let start = pos + header_len;
+        let end = start.saturating_add(size).min(tag.len());
+        pos = end;
+
+        let Some(field) = id3_field(&frame[..id_len]) else {
+            continue;
+        };
+        let mut body = tag[start..end].to_vec();
+
+        match version {
+            // Compressed or encrypted
+            3 if frame[9] & 0xC0 != 0 => continue,
+            4 if frame[9] & 0x0C != 0 => continue,
+            4 => {
+                // Data length indicator
+                if frame[9] & 0x01 != 0 {
+                    body.drain(..4.min(body.len()));
+                }
+                if frame[9] & 0x02 != 0 || flags & 0x80 != 0 {
+                    body = remove_unsync(&body);
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(text) = id3_text(&body) {
+            apply(&mut tags, field, &text);
+        }
+    }
+
+    Some(tags)
+}
+
+/// Decodes a text frame's first value
+fn id3_text(body: &[u8]) -> Option<String> {
+    let (&encoding, data) = body.split_first()?;
+
+    let text = match encoding {
+        0 => data
+            .iter()
+            .take_while(|&&b| b != 0)
+            .map(|&b| b as char)
+            .collect(),
+        1 | 2 => {
+            let (big_endian, data) = match data {
+                [0xFF, 0xFE, rest @ ..] => (false, rest),
+                [0xFE, 0xFF, rest @ ..] => (true, rest),
+                _ => (encoding == 2, data),
+            };
+            let units: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|pair| {
+                    if big_endian {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    }
+                })
+                .take_while(|&unit| unit != 0)
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        3 => {
+            let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+            String::from_utf8_lossy(&data[..end]).to_string()
+        }
+        _ => return None,
+    };
+
+    Some(text)
+}
+
+// Vorbis comments
+
+fn le_u32(bytes: &[u8], pos: usize) -> Option<usize> {
+    let bytes: [u8; 4] = bytes.get(pos..pos + 4)?.try_into().ok()?;
+    Some(u32::from_le_bytes(bytes) as usize)
+}
+
+/// Parses a comment block: a vendor string, then `KEY=value` entries
+fn vorbis_comments(data: &[u8]) -> Option<AudioTags> {
+    let mut pos = 4 + le_u32(data, 0)?;
+    let count = le_u32(data, pos)?;
+    pos += 4;
+
+    let mut tags = AudioTags::default();
+    for _ in 0..count {
+        let len = le_u32(data, pos)?;
+        let entry = data.get(pos + 4..pos + 4 + len)?;
+        pos += 4 + len;
+
+        let entry = String::from_utf8_lossy(entry);
+        let Some((key, value)) = entry.split_once('=') else {
+            continue;
+        };
+        let field = match key.to_uppercase().as_str() {
+            "TITLE" => Field::Title,
+            "ARTIST" => Field::Artist,
+            "ALBUMARTIST" | "ALBUM ARTIST" | "ALBUM_ARTIST" => Field::AlbumArtist,
+            "ALBUM" => Field::Album,
+            "TRACKNUMBER" => Field::Track,
+            "TRACKTOTAL" | "TOTALTRACKS" => Field::TrackTotal,
+            "DISCNUMBER" => Field::Disc,
+            "DATE" | "YEAR" => Field::Year,
+            "GENRE" => Field::Genre,
+            _ => continue,
+        };
+        apply(&mut tags, field, value);
+    }
+
+    Some(tags)
+}
+
+/// Finds the `VORBIS_COMMENT` metadata block
+fn flac_tags(file: &mut File) -> Option<AudioTags> {
+    let magic: [u8; 4] = read_array(file)?;
+    if &magic != b"fLaC" {
+        return None;
+    }
+
+    for _ in 0..MAX_STEPS {
+        let header: [u8; 4] = read_array(file)?;
+        let len = be_uint(&header[1..]);
+
+        if header[0] & 0x7F == 4 {
+            return vorbis_comments(&read_vec(file, len.min(MAX_TAG_LEN))?);
+        }
+        // Last metadata block
+        if header[0] & 0x80 != 0 {
+            return None;
+        }
+        file.seek(SeekFrom::Current(len as i64)).ok()?;
+    }
+
+    None
+}
+
+/// The comments are the second packet of the first logical stream, after
+/// the identification header
+fn ogg_tags(file: &mut File) -> Option<AudioTags> {
+    let mut serial = None;
+    let mut packets = 0;
+    let mut packet = Vec::new();
+
+    for _ in 0..MAX_STEPS {
+        let header: [u8; 27] = read_array(file)?;
+        if &header[..4] != b"OggS" {
+            return None;
+        }
+        let segments = read_vec(file, header[26] as u64)?;
+        let body_len: usize = segments.iter().map(|&s| s as usize).sum();
+        let body = read_vec(file, body_len as u64)?;
+        if segments.len() != header[26] as usize || body.len() != body_len {
+            return None;
+        }
+
+        // Pages of other streams can be interleaved
+        if *serial.get_or_insert(header[14..18].to_vec()) != header[14..18] {
+            continue;
+        }
+
+        let mut offset = 0;
+        for &lacing in &segments {
+            let lacing = lacing as usize;
+            if packets == 1 {
+                packet.extend_from_slice(&body[offset..offset + lacing]);
+            }
+            offset += lacing;
+
+            // A lacing value under 255 ends the packet
+            if lacing < 255 {
+                packets += 1;
+                if packets == 2 {
+                    return ogg_comment_packet(&packet);
+                }
+            }
+        }
+
+        if packet.len() as u64 > MAX_TAG_LEN {
+            return None;
+        }
+    }
+
+    None
+}
+
+fn ogg_comment_packet(packet: &[u8]) -> Option<AudioTags> {
+    if let Some(comments) = packet.strip_prefix(b"\x03vorbis") {
+        vorbis_comments(comments)
+    } else if let Some(comments) = packet.strip_prefix(b"OpusTags") {
+        vorbis_comments(comments)
+    } else {
+        None
+    }
+}
+
+// MP4
+
+/// Reads the items of `moov/udta/meta/ilst`
+fn mp4_tags(file: &mut File) -> Option<AudioTags> {
+    let end = file.metadata().ok()?.len();
+    let (moov_start, moov_end) = find_box(file, 0, end, b"moov")?;
+    let (udta_start, udta_end) = find_box(file, moov_start, moov_end, b"udta")?;
+    let (mut meta_start, meta_end) = find_box(file, udta_start, udta_end, b"meta")?;
+
+    // `meta` is a full box, with version and flags, in MP4 but not in
+    // QuickTime files
+    file.seek(SeekFrom::Start(meta_start)).ok()?;
+    if read_array::<4>(file)? == [0; 4] {
+        meta_start += 4;
+    }
+
+    let (ilst_start, ilst_end) = find_box(file, meta_start, meta_end, b"ilst")?;
+    file.seek(SeekFrom::Start(ilst_start)).ok()?;
+    let ilst = read_vec(file, (ilst_end - ilst_start).min(MAX_TAG_LEN))?;
+
+    let mut tags = AudioTags::default();
+    for (kind, item) in child_boxes(&ilst) {
+        let Some((_, data)) = child_boxes(item).into_iter().find(|(k, _)| *k == b"data") else {
+            continue;
+        };
+        // Type indicator and locale, then the value
+        let Some(value) = data.get(8..) else {
+            continue;
+        };
+
+        match kind {
+            b"trkn" | b"disk" => {
+                let Some(number) = value.get(2..4).map(be_uint) else {
+                    continue;
+                };
+                let total = value.get(4..6).map(be_uint).unwrap_or(0);
+                let text = if total > 0 {
+                    format!("{}/{}", number, total)
+                } else {
+                    number.to_string()
+                };
+                let field = if kind == b"trkn" {
+                    Field::Track
+                } else {
+                    Field::Disc
+                };
+                apply(&mut tags, field, &text);
+            }
+            // ID3v1 genre number plus one
+            b"gnre" => {
+                if let Some(index) = value.get(..2).map(be_uint).filter(|&i| i > 0) {
+                    apply(&mut tags, Field::Genre, &(index - 1).to_string());
+                }
+            }
+            _ => {
+                let field = match kind {
+                    b"\xA9nam" => Field::Title,
+                    b"\xA9ART" => Field::Artist,
+                    b"aART" => Field::AlbumArtist,
+                    b"\xA9alb" => Field::Album,
+                    b"\xA9day" => Field::Year,
+                    b"\xA9gen" => Field::Genre,
+                    _ => continue,
+                };
+                apply(&mut tags, field, &String::from_utf8_lossy(value));
+            }
+        }
+    }
+
+    Some(tags)
+}
+
+/// Splits a buffer of boxes into their types and bodies
+fn child_boxes(buf: &[u8]) -> Vec<(&[u8; 4], &[u8])> {
+    let mut boxes = Vec::new();
+    let mut pos = 0;
+
+    while pos + 8 <= buf.len() {
+        let size = be_uint(&buf[pos..pos + 4]) as usize;
+        if size < 8 || pos + size > buf.len() {
+            break;
+        }
+        let kind = buf[pos + 4..pos + 8].try_into().expect("four bytes");
+        boxes.push((kind, &buf[pos + 8..pos + size]));
+        pos += size;
+    }
+
+    boxes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads `bytes` as a file of type `mime`
+    fn tags(name: &str, mime: &str, bytes: &[u8]) -> Option<AudioTags> {
+        let path =
+            std::env::temp_dir().join(format!("fileog-tags-{}-{}", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let tags = read(&path, mime);
+        std::fs::remove_file(&path).unwrap();
+        tags
+    }
+
+    /// Every truncation of `bytes` parses without panicking
+    fn check_prefixes(name: &str, mime: &str, bytes: &[u8]) {
+        for len in 0..bytes.len() {
+            tags(name, mime, &bytes[..len]);
+        }
+    }
+
+    fn syncsafe_bytes(n: usize) -> [u8; 4] {
+        [
+            (n >> 21) as u8 & 0x7F,
+            (n >> 14) as u8 & 0x7F,
+            (n >> 7) as u8 & 0x7F,
+            n as u8 & 0x7F,
+        ]
+    }
+
+    fn id3(version: u8, flags: u8, body: &[u8]) -> Vec<u8> {
+        [
+            b"ID3",
+            &[version, 0, flags][..],
+            &syncsafe_bytes(body.len()),
+            body,
+        ]
+        .concat()
+    }
+
+    fn frame_v2(id: &[u8; 3], body: &[u8]) -> Vec<u8> {
+        [&id[..], &(body.len() as u32).to_be_bytes()[1..], body].concat()
+    }
+
+    fn frame_v3(id: &[u8; 4], flags: u8, body: &[u8]) -> Vec<u8> {
+        [
+            &id[..],
+            &(body.len() as u32).to_be_bytes(),
+            &[0, flags],
+            body,
+        ]
+        .concat()
+    }
+
+    fn frame_v4(id: &[u8; 4], flags: u8, body: &[u8]) -> Vec<u8> {
+        [&id[..], &syncsafe_bytes(body.len()), &[0, flags], body].concat()
+    }
+
+    fn latin1(text: &str) -> Vec<u8> {
+        [&[0][..], text.as_bytes()].concat()
+    }
+
+    /// Inserts a zero after every 0xFF
+    fn unsync(bytes: &[u8]) -> Vec<u8> {
+        bytes
+            .iter()
+            .flat_map(|&b| if b == 0xFF { vec![b, 0] } else { vec![b] })
+            .collect()
+    }
+
+    #[test]
+    fn test_id3v22() {
+        let body = [
+            frame_v2(b"TT2", &latin1("Song")),
+            frame_v2(b"TP1", &latin1("Band")),
+            frame_v2(b"TCO", &latin1("(13)Synthpop")),
+            frame_v2(b"TYE", &latin1("2001")),
+            vec![0; 16],
+        ]
+        .concat();
+        let tags = tags("id3v22", "audio/mpeg", &id3(2, 0, &body)).unwrap();
+
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+        assert_eq!(tags.artist.as_deref(), Some("Band"));
+        assert_eq!(tags.genre.as_deref(), Some("Synthpop"));
+        assert_eq!(tags.year, Some(2001));
+    }
+
+    #[test]
+    fn test_id3v23() {
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("Ärtist".encode_utf16().flat_map(u16::to_le_bytes))
+            .chain([0, 0])
+            .collect();
+        let body = [
+            frame_v3(b"PRIV", 0, b"owner\0data"),
+            frame_v3(b"TIT2", 0, &latin1("Song\0")),
+            frame_v3(b"TPE1", 0, &[&[1][..], &utf16].concat()),
+            frame_v3(b"TRCK", 0, &latin1("3/12")),
+            frame_v3(b"TYER", 0, &latin1("1999")),
+            frame_v3(b"TCON", 0, &latin1("(17)")),
+            // Compressed
+            frame_v3(b"TALB", 0x80, &latin1("Album")),
+            frame_v3(b"TIT2", 0, &latin1("Second")),
+            vec![0; 32],
+        ]
+        .concat();
+        let bytes = id3(3, 0, &body);
+        let tags = tags("id3v23", "audio/mpeg", &bytes).unwrap();
+
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+        assert_eq!(tags.artist.as_deref(), Some("Ärtist"));
+        assert_eq!((tags.track, tags.track_total), (Some(3), Some(12)));
+        assert_eq!(tags.year, Some(1999));
+        assert_eq!(tags.genre.as_deref(), Some("Rock"));
+        assert_eq!(tags.album, None);
+
+        check_prefixes("id3v23-cut", "audio/mpeg", &bytes);
+    }
+
+    #[test]
+    fn test_id3v23_unsync_and_extended_header() {
+        let body = [
+            // Extended header: its size without the size field, flags, padding
+            vec![0, 0, 0, 6],
+            vec![0; 6],
+            frame_v3(b"TIT2", 0, &[0, b'x', 0xFF, b'y']),
+            frame_v3(b"TALB", 0, &latin1("Album")),
+        ]
+        .concat();
+        let tags = tags("id3v23-unsync", "audio/mpeg", &id3(3, 0xC0, &unsync(&body))).unwrap();
+
+        assert_eq!(tags.title.as_deref(), Some("xÿy"));
+        assert_eq!(tags.album.as_deref(), Some("Album"));
+    }
+
+    #[test]
+    fn test_id3v24() {
+        let album = unsync(&[0, b'A', 0xFF, b'B']);
+        let album = [&(4u32).to_be_bytes()[..], &album].concat();
+        let album_artist: Vec<u8> = [2]
+            .into_iter()
+            .chain("Various".encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+        let body = [
+            // Extended header: its size including the size field, flags
+            syncsafe_bytes(6).to_vec(),
+            vec![1, 0],
+            frame_v4(b"TIT2", 0, &[&[3][..], "Naïve".as_bytes()].concat()),
+            frame_v4(b"TDRC", 0, &latin1("2004-05-06")),
+            // Unsynchronised, with a data length indicator
+            frame_v4(b"TALB", 0x03, &album),
+            frame_v4(b"TPE2", 0, &album_artist),
+            // Compressed
+            frame_v4(b"TPE1", 0x08, &latin1("Hidden")),
+            frame_v4(b"TPOS", 0, &latin1("2/2")),
+        ]
+        .concat();
+        let bytes = id3(4, 0x40, &body);
+        let tags = tags("id3v24", "audio/mpeg", &bytes).unwrap();
+
+        assert_eq!(tags.title.as_deref(), Some("Naïve"));
+        assert_eq!(tags.year, Some(2004));
+        assert_eq!(tags.album.as_deref(), Some("AÿB"));
+        assert_eq!(tags.album_artist.as_deref(), Some("Various"));
+        assert_eq!(tags.artist, None);
+        assert_eq!(tags.disc, Some(2));
+
+        check_prefixes("id3v24-cut", "audio/mpeg", &bytes);
+    }
+
+    #[test]
+    fn test_id3_corrupt() {
+        let title = frame_v3(b"TIT2", 0, &latin1("Song"));
+
+        // Unsupported version, or not a tag at all
+        assert!(tags("id3-v5", "audio/mpeg", &id3(5, 0, &title)).is_none());
+        assert!(tags("id3-none", "audio/mpeg", &[0xFF, 0xFB, 0x90, 0x00]).is_none());
+        // Extended header larger than the tag
+        let body = [vec![0, 0, 0x10, 0], title.clone()].concat();
+        assert!(tags("id3-ext", "audio/mpeg", &id3(3, 0x40, &body)).is_none());
+        // Extended header flag on a tag too short to hold one
+        assert!(tags("id3-ext-short", "audio/mpeg", &id3(3, 0x40, &[1, 2])).is_none());
+        // Unknown text encoding
+        let body = frame_v3(b"TIT2", 0, &[7, b'x']);
+        assert!(tags("id3-encoding", "audio/mpeg", &id3(3, 0, &body)).is_none());
+
+        // A frame size past the end of the tag keeps what's there
+        let mut body = title;
+        body[4..8].copy_from_slice(&u32::MAX.to_be_bytes());
+        let tags = tags("id3-size", "audio/mpeg", &id3(3, 0, &body)).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+    }
+
+    fn vorbis_comment(entries: &[&str]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&6u32.to_le_bytes());
+        out.extend_from_slice(b"fileog");
+        out.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for entry in entries {
+            out.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+            out.extend_from_slice(entry.as_bytes());
+        }
+        out
+    }
+
+    fn flac_block(kind: u8, body: &[u8]) -> Vec<u8> {
+        [&[kind][..], &(body.len() as u32).to_be_bytes()[1..], body].concat()
+    }
+
+    #[test]
+    fn test_flac_vorbis_comments() {
+        let comments = vorbis_comment(&[
+            "TITLE=Song",
+            "artist=Band",
+            "ALBUM ARTIST=Various",
+            "TRACKNUMBER=7",
+            "TRACKTOTAL=10",
+            "DISCNUMBER=2/3",
+            "DATE=2010-01-01",
+            "GENRE=Jazz",
+            "NOEQUALS",
+            "TITLE=Second",
+        ]);
+        let bytes = [
+            b"fLaC".to_vec(),
+            flac_block(0, &[0; 34]),
+            flac_block(1, &[0; 100]),
+            flac_block(0x84, &comments),
+        ]
+        .concat();
+        let tags = tags("flac", "audio/x-flac", &bytes).unwrap();
+
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+        assert_eq!(tags.artist.as_deref(), Some("Band"));
+        assert_eq!(tags.album_artist.as_deref(), Some("Various"));
+        assert_eq!((tags.track, tags.track_total), (Some(7), Some(10)));
+        assert_eq!(tags.disc, Some(2));
+        assert_eq!(tags.year, Some(2010));
+        assert_eq!(tags.genre.as_deref(), Some("Jazz"));
+
+        check_prefixes("flac-cut", "audio/x-flac", &bytes);
+    }
+
+    #[test]
+    fn test_flac_corrupt() {
+        // No comment block before the last one
+        let bytes = [b"fLaC".to_vec(), flac_block(0x80, &[0; 34])].concat();
+        assert!(tags("flac-none", "audio/x-flac", &bytes).is_none());
+
+        // More entries announced than present
+        let mut comments = vorbis_comment(&["TITLE=Song"]);
+        comments[10..14].copy_from_slice(&5u32.to_le_bytes());
+        let bytes = [b"fLaC".to_vec(), flac_block(0x84, &comments)].concat();
+        assert!(tags("flac-count", "audio/x-flac", &bytes).is_none());
+
+        // Vendor length past the end
+        let bytes = [b"fLaC".to_vec(), flac_block(0x84, &[0xFF; 8])].concat();
+        assert!(tags("flac-vendor", "audio/x-flac", &bytes).is_none());
+    }
+
+    fn ogg_page(serial: u32, lacing: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS".to_vec();
+        page.extend_from_slice(&[0; 10]);
+        page.extend_from_slice(&serial.to_le_bytes());
+        page.extend_from_slice(&[0; 8]);
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(lacing);
+        page.extend_from_slice(body);
+        page
+    }
+
+    #[test]
+    fn test_ogg_vorbis_comments_across_pages() {
+        let title = "x".repeat(300);
+        let packet = [
+            b"\x03vorbis".to_vec(),
+            vorbis_comment(&[&format!("TITLE={}", title), "ALBUM=Album"]),
+        ]
+        .concat();
+        let (first, rest) = packet.split_at(255);
+        let bytes = [
+            ogg_page(1, &[30], &[&b"\x01vorbis"[..], &[0; 23]].concat()),
+            ogg_page(1, &[255], first),
+            // Another stream's page in between
+            ogg_page(2, &[5], b"TITLE"),
+            ogg_page(1, &[rest.len() as u8], rest),
+        ]
+        .concat();
+        let tags = tags("ogg", "audio/ogg", &bytes).unwrap();
+
+        assert_eq!(tags.title, Some(title));
+        assert_eq!(tags.album.as_deref(), Some("Album"));
+
+        check_prefixes("ogg-cut", "audio/ogg", &bytes);
+    }
+
+    #[test]
+    fn test_opus_tags() {
+        let packet = [b"OpusTags".to_vec(), vorbis_comment(&["ARTIST=Band"])].concat();
+        let head = [&b"OpusHead"[..], &[0; 11]].concat();
+        // Both packets on one page
+        let bytes = ogg_page(9, &[19, packet.len() as u8], &[head, packet].concat());
+        let tags = tags("opus", "audio/opus", &bytes).unwrap();
+
+        assert_eq!(tags.artist.as_deref(), Some("Band"));
+    }
+
+    #[test]
+    fn test_ogg_corrupt() {
+        let page = ogg_page(1, &[30], &[0; 30]);
+        // Not a page, and a second packet that isn't a comment header
+        assert!(tags(
+            "ogg-magic",
+            "audio/ogg",
+            b"RIFF0000000000000000000000000000"
+        )
+        .is_none());
+        let bytes = [page.clone(), ogg_page(1, &[4], b"\x05xyz")].concat();
+        assert!(tags("ogg-packet", "audio/ogg", &bytes).is_none());
+        // Page body shorter than its lacing values
+        let mut bytes = [page.clone(), ogg_page(1, &[200], &[0; 50])].concat();
+        assert!(tags("ogg-short", "audio/ogg", &bytes).is_none());
+        bytes.truncate(page.len() + 20);
+        assert!(tags("ogg-header", "audio/ogg", &bytes).is_none());
+    }
+
+    fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        [&((body.len() + 8) as u32).to_be_bytes()[..], kind, body].concat()
+    }
+
+    fn ilst_item(kind: &[u8; 4], value: &[u8]) -> Vec<u8> {
+        mp4_box(
+            kind,
+            &mp4_box(b"data", &[&[0, 0, 0, 1, 0, 0, 0, 0][..], value].concat()),
+        )
+    }
+
+    fn mp4(meta: &[u8]) -> Vec<u8> {
+        [
+            mp4_box(b"ftyp", b"M4A \0\0\0\0"),
+            mp4_box(
+                b"moov",
+                &[
+                    mp4_box(b"mvhd", &[0; 100]),
+                    mp4_box(b"udta", &mp4_box(b"meta", meta)),
+                ]
+                .concat(),
+            ),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_mp4_ilst() {
+        let ilst = [
+            ilst_item(b"\xA9nam", b"Song"),
+            ilst_item(b"\xA9ART", b"Band"),
+            ilst_item(b"aART", b"Various"),
+            ilst_item(b"\xA9alb", b"Album"),
+            ilst_item(b"\xA9day", b"2015-03-01T00:00:00Z"),
+            ilst_item(b"trkn", &[0, 0, 0, 5, 0, 12, 0, 0]),
+            ilst_item(b"disk", &[0, 0, 0, 1, 0, 0]),
+            ilst_item(b"gnre", &[0, 18]),
+            ilst_item(b"----", b"ignored"),
+            // Item without a data box
+            mp4_box(b"\xA9gen", &mp4_box(b"name", b"x")),
+        ]
+        .concat();
+        let meta = [
+            vec![0; 4],
+            mp4_box(b"hdlr", &[0; 25]),
+            mp4_box(b"ilst", &ilst),
+        ]
+        .concat();
+        let bytes = mp4(&meta);
+        let tags = tags("mp4", "audio/m4a", &bytes).unwrap();
+
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+        assert_eq!(tags.artist.as_deref(), Some("Band"));
+        assert_eq!(tags.album_artist.as_deref(), Some("Various"));
+        assert_eq!(tags.album.as_deref(), Some("Album"));
+        assert_eq!(tags.year, Some(2015));
+        assert_eq!((tags.track, tags.track_total), (Some(5), Some(12)));
+        assert_eq!(tags.disc, Some(1));
+        assert_eq!(tags.genre.as_deref(), Some("Rock"));
+
+        check_prefixes("mp4-cut", "audio/m4a", &bytes);
+    }
+
+    #[test]
+    fn test_mp4_quicktime_meta() {
+        // No version and flags ahead of the children
+        let meta = mp4_box(b"ilst", &ilst_item(b"\xA9gen", b"Ambient"));
+        let tags = tags("mp4-qt", "video/mp4", &mp4(&meta)).unwrap();
+
+        assert_eq!(tags.genre.as_deref(), Some("Ambient"));
+    }
+
+    #[test]
+    fn test_mp4_corrupt() {
+        // Item size below a box header, and a data box too short for a value
+        let ilst = [vec![0, 0, 0, 4], b"\xA9nam".to_vec()].concat();
+        let meta = [vec![0; 4], mp4_box(b"ilst", &ilst)].concat();
+        assert!(tags("mp4-item", "audio/m4a", &mp4(&meta)).is_none());
+
+        let ilst = mp4_box(b"\xA9nam", &mp4_box(b"data", &[0; 4]));
+        let meta = [vec![0; 4], mp4_box(b"ilst", &ilst)].concat();
+        assert!(tags("mp4-data", "audio/m4a", &mp4(&meta)).is_none());
+
+        assert!(tags("mp4-none", "audio/m4a", &mp4_box(b"moov", &[])).is_none());
+    }
+
+    #[test]
+    fn test_genre_name() {
+        assert_eq!(genre_name("17"), "Rock");
+        assert_eq!(genre_name("(17)"), "Rock");
+        assert_eq!(genre_name("(0)"), "Blues");
+        assert_eq!(genre_name("(79)"), "Hard Rock");
+        assert_eq!(genre_name("(17)Indie Rock"), "Indie Rock");
+        // Out of range, unclosed or not a number
+        assert_eq!(genre_name("(80)"), "Folk");
+        assert_eq!(genre_name("(147)"), "Synthpop");
+        assert_eq!(genre_name("(191)"), "Psybient");
+        assert_eq!(genre_name("(192)"), "(192)");
+        assert_eq!(genre_name("(17"), "(17");
+        assert_eq!(genre_name("Shoegaze"), "Shoegaze");
+    }
+}
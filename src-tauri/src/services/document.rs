@@ -0,0 +1,499 @@
+//! Reads the title, author, page count, creation date and the start of the
+//! body text of PDF, Office Open XML (docx, xlsx, pptx) and OpenDocument
+//! (odt, ods, odp) files.
+
+use crate::models::DocumentMetadata;
+use chrono::{NaiveDate, NaiveDateTime};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// PDFs are parsed in memory, so larger ones are skipped
+const MAX_PDF_LEN: u64 = 100 * 1024 * 1024;
+
+/// Pages searched for text at the start of a PDF
+const MAX_PDF_TEXT_PAGES: u32 = 5;
+
+/// Limit on a PDF page's decompressed content stream
+const MAX_PAGE_CONTENT_LEN: usize = 16 * 1024 * 1024;
+
+/// Limit on the XML read from a property part such as `docProps/core.xml`
+const MAX_PROPERTIES_LEN: u64 = 1024 * 1024;
+
+/// Slides read for a presentation's text
+const MAX_SLIDES: usize = 20;
+
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// What could be read from a document
+pub struct Document {
+    pub metadata: DocumentMetadata,
+    /// Up to `max_chars` characters from the start of the body text, one
+    /// paragraph per line
+    pub text: String,
+}
+
+/// The layouts of ZIP-based documents
+enum Package {
+    Word,
+    Spreadsheet,
+    Presentation,
+    OpenDocument,
+}
+
+/// `None` if `mime` isn't a document format or the file can't be parsed.
+/// ZIP files are looked into, since a document's signature isn't always
+/// recognized.
+pub fn read(path: &Path, mime: &str, max_chars: usize) -> Option<Document> {
+    if mime == "application/pdf" {
+        read_pdf(path, max_chars)
+    } else if mime == "application/zip"
+        || mime.starts_with("application/vnd.openxmlformats-officedocument.")
+        || mime.starts_with("application/vnd.oasis.opendocument.")
+    {
+        read_package(path, max_chars)
+    } else {
+        None
+    }
+}
+
+fn read_pdf(path: &Path, max_chars: usize) -> Option<Document> {
+    if std::fs::metadata(path).ok()?.len() > MAX_PDF_LEN {
+        return None;
+    }
+    let pdf = lopdf::Document::load(path).ok()?;
+
+    let info = pdf
+        .trailer
+        .get_deref(b"Info", &pdf)
+        .and_then(|info| info.as_dict())
+        .ok();
+    let info_text = |key: &[u8]| {
+        let value = info?.get_deref(key, &pdf).ok()?;
+        let text = lopdf::decode_text_string(value).ok()?;
+        non_empty(&text)
+    };
+
+    let pages = pdf.get_pages();
+    let mut text = String::new();
+    for &page in pages.keys().take(MAX_PDF_TEXT_PAGES as usize) {
+        if let Ok(page_text) = pdf.extract_text_with_limit(&[page], MAX_PAGE_CONTENT_LEN) {
+            text.push_str(&page_text);
+            text.push('\n');
+        }
+        if text.chars().count() >= max_chars {
+            break;
+        }
+    }
+
+    Some(Document {
+        metadata: DocumentMetadata {
+            title: info_text(b"Title"),
+            author: info_text(b"Author"),
+            page_count: u32::try_from(pages.len()).ok(),
+            created_at: info_text(b"CreationDate").and_then(|d| pdf_date(&d)),
+        },
+        text: clean_text(&text, max_chars),
+    })
+}
+
+/// A PDF date, `D:YYYYMMDDHHmmSS` followed by the time zone. Everything after
+/// the year is optional.
+fn pdf_date(value: &str) -> Option<String> {
+    let digits: String = value
+        .trim()
+        .trim_start_matches("D:")
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .take(14)
+        .collect();
+    let part = |range: std::ops::Range<usize>, default: u32| {
+        digits.get(range).map_or(Some(default), |p| p.parse().ok())
+    };
+
+    let date = NaiveDate::from_ymd_opt(
+        digits.get(..4)?.parse().ok()?,
+        part(4..6, 1)?,
+        part(6..8, 1)?,
+    )?;
+    let datetime = date.and_hms_opt(part(8..10, 0)?, part(10..12, 0)?, part(12..14, 0)?)?;
+    Some(datetime.format(DATE_FORMAT).to_string())
+}
+
+/// An ISO 8601 date or date and time, as used by both OOXML and ODF
+fn iso_date(value: &str) -> Option<String> {
+    let value = value.trim();
+    let datetime = value
+        .get(..19)
+        .and_then(|v| NaiveDateTime::parse_from_str(v, DATE_FORMAT).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })?;
+    Some(datetime.format(DATE_FORMAT).to_string())
+}
+
+fn read_package(path: &Path, max_chars: usize) -> Option<Document> {
+    let mut archive = ZipArchive::new(File::open(path).ok()?).ok()?;
+    let package = if archive.index_for_name("word/document.xml").is_some() {
+        Package::Word
+    } else if archive.index_for_name("xl/workbook.xml").is_some() {
+        Package::Spreadsheet
+    } else if archive.index_for_name("ppt/presentation.xml").is_some() {
+        Package::Presentation
+    } else if archive.index_for_name("content.xml").is_some()
+        && archive.index_for_name("mimetype").is_some()
+    {
+        Package::OpenDocument
+    } else {
+        return None;
+    };
+
+    let metadata = match package {
+        Package::OpenDocument => {
+            let meta = properties(&mut archive, "meta.xml");
+            DocumentMetadata {
+                title: meta.get("title").cloned(),
+                author: meta.get("initial-creator").or(meta.get("creator")).cloned(),
+                page_count: meta
+                    .get("document-statistic@page-count")
+                    .and_then(|c| c.parse().ok()),
+                created_at: meta.get("creation-date").and_then(|d| iso_date(d)),
+            }
+        }
+        _ => {
+            let core = properties(&mut archive, "docProps/core.xml");
+            let app = properties(&mut archive, "docProps/app.xml");
+            DocumentMetadata {
+                title: core.get("title").cloned(),
+                author: core.get("creator").cloned(),
+                page_count: app
+                    .get("Pages")
+                    .or(app.get("Slides"))
+                    .and_then(|c| c.parse().ok()),
+                created_at: core.get("created").and_then(|d| iso_date(d)),
+            }
+        }
+    };
+
+    let (text_parts, text_elements): (Vec<String>, &[&str]) = match package {
+        Package::Word => (vec!["word/document.xml".to_string()], &["t"]),
+        Package::Spreadsheet => (vec!["xl/sharedStrings.xml".to_string()], &["t"]),
+        Package::Presentation => (
+            (1..=MAX_SLIDES)
+                .map(|n| format!("ppt/slides/slide{}.xml", n))
+                .collect(),
+            &["t"],
+        ),
+        Package::OpenDocument => (vec!["content.xml".to_string()], &["p", "h"]),
+    };
+    let mut text = String::new();
+    for name in &text_parts {
+        let Ok(part) = archive.by_name(name) else {
+            break;
+        };
+        collect_text(BufReader::new(part), text_elements, &mut text, max_chars);
+        if text.chars().count() >= max_chars {
+            break;
+        }
+    }
+
+    Some(Document {
+        metadata,
+        text: clean_text(&text, max_chars),
+    })
+}
+
+/// The text of each element in a small XML part, by local name, along with
+/// each attribute as `element@attribute`. The first occurrence wins.
+fn properties(archive: &mut ZipArchive<File>, name: &str) -> HashMap<String, String> {
+    let mut properties = HashMap::new();
+    let Ok(part) = archive.by_name(name) else {
+        return properties;
+    };
+
+    let mut reader = Reader::from_reader(BufReader::new(part.take(MAX_PROPERTIES_LEN)));
+    let mut buf = Vec::new();
+    let mut element = String::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                element = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                for attribute in e.attributes().flatten() {
+                    let Ok(value) = attribute.unescape_value() else {
+                        continue;
+                    };
+                    let key = format!(
+                        "{}@{}",
+                        element,
+                        String::from_utf8_lossy(attribute.key.local_name().as_ref())
+                    );
+                    if let Some(value) = non_empty(&value) {
+                        properties.entry(key).or_insert(value);
+                    }
+                }
+            }
+            Ok(Event::Text(e)) => {
+                if let Some(value) = e.unescape().ok().and_then(|v| non_empty(&v)) {
+                    properties.entry(element.clone()).or_insert(value);
+                }
+            }
+            Ok(Event::End(_)) => element.clear(),
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    properties
+}
+
+/// Appends the text inside `text_elements` to `text`, ending each paragraph
+/// with a new line, until `max_chars` characters have been collected. The
+/// rest of the XML is never read.
+fn collect_text<R: BufRead>(
+    reader: R,
+    text_elements: &[&str],
+    text: &mut String,
+    max_chars: usize,
+) {
+    let mut reader = Reader::from_reader(reader);
+    let mut buf = Vec::new();
+    let mut depth = 0usize;
+    let mut chars = text.chars().count();
+    let is_text_element = |name: &[u8]| text_elements.iter().any(|t| t.as_bytes() == name);
+
+    while chars < max_chars {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) if is_text_element(e.local_name().as_ref()) => depth += 1,
+            Ok(Event::End(e)) => {
+                let name = e.local_name();
+                if is_text_element(name.as_ref()) {
+                    depth = depth.saturating_sub(1);
+                }
+                // Paragraphs in WordprocessingML, DrawingML and ODF, and
+                // shared strings in SpreadsheetML
+                if matches!(name.as_ref(), b"p" | b"h" | b"si") {
+                    text.push('\n');
+                }
+            }
+            // Tabs, line breaks and ODF's run of spaces
+            Ok(Event::Empty(e)) => match e.local_name().as_ref() {
+                b"tab" => text.push('\t'),
+                b"br" | b"cr" | b"line-break" => text.push('\n'),
+                b"s" => text.push(' '),
+                _ => {}
+            },
+            Ok(Event::Text(e)) if depth > 0 => {
+                if let Ok(value) = e.unescape() {
+                    chars += value.chars().count();
+                    text.push_str(&value);
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// Trims each line, drops blank ones and keeps the first `max_chars`
+/// characters
+fn clean_text(text: &str, max_chars: usize) -> String {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    lines
+        .join("\n")
+        .chars()
+        .take(max_chars)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use zip::write::SimpleFileOptions;
+
+    /// Writes a ZIP package holding `parts`
+    fn package(name: &str, parts: &[(&str, &str)]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("fileog-document-{}-{}", name, std::process::id()));
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        for (part, content) in parts {
+            zip.start_file(*part, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_pdf_date() {
+        assert_eq!(
+            pdf_date("D:20230415093000").as_deref(),
+            Some("2023-04-15T09:30:00")
+        );
+        // Time zone suffixes are ignored; the time is as written
+        assert_eq!(
+            pdf_date("D:20230415093000+02'00'").as_deref(),
+            Some("2023-04-15T09:30:00")
+        );
+        assert_eq!(
+            pdf_date("D:20230415093000Z").as_deref(),
+            Some("2023-04-15T09:30:00")
+        );
+        // Missing parts default to the start of the period
+        assert_eq!(pdf_date("D:2023").as_deref(), Some("2023-01-01T00:00:00"));
+        assert_eq!(pdf_date("D:202304").as_deref(), Some("2023-04-01T00:00:00"));
+        assert_eq!(
+            pdf_date(" 2023041509 ").as_deref(),
+            Some("2023-04-15T09:00:00")
+        );
+
+        assert!(pdf_date("D:").is_none());
+        assert!(pdf_date("D:202").is_none());
+        assert!(pdf_date("D:20231345").is_none());
+        assert!(pdf_date("yesterday").is_none());
+    }
+
+    #[test]
+    fn test_iso_date() {
+        assert_eq!(
+            iso_date("2023-04-15T09:30:00Z").as_deref(),
+            Some("2023-04-15T09:30:00")
+        );
+        assert_eq!(
+            iso_date("2023-04-15T09:30:00.123456789").as_deref(),
+            Some("2023-04-15T09:30:00")
+        );
+        assert_eq!(
+            iso_date(" 2023-04-15 ").as_deref(),
+            Some("2023-04-15T00:00:00")
+        );
+
+        assert!(iso_date("2023-04").is_none());
+        assert!(iso_date("15/04/2023").is_none());
+        assert!(iso_date("").is_none());
+    }
+
+    #[test]
+    fn test_clean_text() {
+        let text = "  First line  \n\n\t\nSecond line\n   \nThird";
+        assert_eq!(clean_text(text, 100), "First line\nSecond line\nThird");
+
+        // Truncation counts characters, not bytes, and drops trailing space
+        assert_eq!(clean_text("日本語のテキスト", 3), "日本語");
+        assert_eq!(clean_text("First line\nSecond", 11), "First line");
+        assert_eq!(clean_text("abc", 0), "");
+    }
+
+    #[test]
+    fn test_ooxml_properties_and_text() {
+        let path = package(
+            "docx",
+            &[
+                (
+                    "word/document.xml",
+                    r#"<w:document xmlns:w="w"><w:body>
+                    <w:p><w:r><w:t>Quarterly</w:t></w:r><w:r><w:tab/><w:t>report</w:t></w:r></w:p>
+                    <w:p><w:r><w:t>Second &amp; last</w:t></w:r></w:p>
+                </w:body></w:document>"#,
+                ),
+                (
+                    "docProps/core.xml",
+                    r#"<cp:coreProperties xmlns:cp="cp" xmlns:dc="dc" xmlns:dcterms="dcterms">
+                    <dc:title>Q1 Report</dc:title>
+                    <dc:creator> Alice </dc:creator>
+                    <dcterms:created xsi:type="dcterms:W3CDTF">2023-04-15T09:30:00Z</dcterms:created>
+                </cp:coreProperties>"#,
+                ),
+                (
+                    "docProps/app.xml",
+                    "<Properties><Pages>3</Pages></Properties>",
+                ),
+            ],
+        );
+
+        let document = read(
+            &path,
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            100,
+        )
+        .unwrap();
+        let metadata = document.metadata;
+        assert_eq!(metadata.title.as_deref(), Some("Q1 Report"));
+        assert_eq!(metadata.author.as_deref(), Some("Alice"));
+        assert_eq!(metadata.page_count, Some(3));
+        assert_eq!(metadata.created_at.as_deref(), Some("2023-04-15T09:30:00"));
+        assert_eq!(document.text, "Quarterly\treport\nSecond & last");
+
+        let short = read(&path, "application/zip", 5).unwrap();
+        assert_eq!(short.text, "Quart");
+    }
+
+    #[test]
+    fn test_odf_meta() {
+        let path = package(
+            "odt",
+            &[
+                ("mimetype", "application/vnd.oasis.opendocument.text"),
+                (
+                    "content.xml",
+                    r#"<office:document-content xmlns:office="o" xmlns:text="t">
+                    <office:body><office:text>
+                        <text:h>Title</text:h>
+                        <text:p>Body<text:s/>text<text:line-break/>next</text:p>
+                    </office:text></office:body>
+                </office:document-content>"#,
+                ),
+                (
+                    "meta.xml",
+                    r#"<office:document-meta xmlns:office="o" xmlns:meta="m" xmlns:dc="dc">
+                    <office:meta>
+                        <dc:title>Minutes</dc:title>
+                        <meta:initial-creator>Bob</meta:initial-creator>
+                        <dc:creator>Carol</dc:creator>
+                        <meta:creation-date>2022-11-02T14:05:09.52</meta:creation-date>
+                        <meta:document-statistic meta:page-count="7" meta:word-count="1200"/>
+                    </office:meta>
+                </office:document-meta>"#,
+                ),
+            ],
+        );
+
+        let document = read(&path, "application/vnd.oasis.opendocument.text", 100).unwrap();
+        let metadata = document.metadata;
+        assert_eq!(metadata.title.as_deref(), Some("Minutes"));
+        // The original author wins over whoever saved it last
+        assert_eq!(metadata.author.as_deref(), Some("Bob"));
+        assert_eq!(metadata.page_count, Some(7));
+        assert_eq!(metadata.created_at.as_deref(), Some("2022-11-02T14:05:09"));
+        assert_eq!(document.text, "Title\nBody text\nnext");
+    }
+
+    #[test]
+    fn test_unknown_packages_are_skipped() {
+        let path = package("plain-zip", &[("notes.txt", "hello")]);
+        assert!(read(&path, "application/zip", 100).is_none());
+
+        // Not a document format at all
+        assert!(read(&path, "image/png", 100).is_none());
+    }
+}
@@ -0,0 +1,768 @@
+//! Finds files with identical content in stages that each read more of fewer
+//! files: files are grouped by size, then by a hash of their first and last
+//! 64 KiB, and only files still matching after that are hashed in full. Each
+//! stage reads files on a pool of threads.
+
+use crate::models::{DuplicateGroup, DuplicateOptions, KeepStrategy};
+use crate::storage::files::FileStamp;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Bytes hashed from each end of a file in the partial stage. Files up to
+/// twice this size are hashed in full straight away.
+const PARTIAL_LEN: u64 = 64 * 1024;
+
+/// Each thread reads through buffers of this size
+const BUFFER_LEN: usize = 1024 * 1024;
+
+/// Minimum time between progress reports
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A file to compare
+pub struct Candidate {
+    pub path: PathBuf,
+    pub stamp: FileStamp,
+    /// The full content hash, if already known for this stamp
+    pub hash: Option<String>,
+}
+
+/// The step `find` is on, for progress
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    PartialHash,
+    FullHash,
+    Verify,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::PartialHash => "partial_hashing",
+            Stage::FullHash => "hashing",
+            Stage::Verify => "verifying",
+        }
+    }
+}
+
+/// Where a stage is up to. Bytes count what was read, and `current_file` is
+/// the file finished last.
+pub struct Progress<'a> {
+    pub stage: Stage,
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub current_file: Option<&'a Path>,
+}
+
+pub struct Duplicates {
+    /// Largest files first; empty if cancelled
+    pub groups: Vec<DuplicateGroup>,
+    /// Files whose full hash was worked out, whether or not they have
+    /// duplicates, so the hash can be kept. Filled in even if cancelled.
+    pub hashed: Vec<Candidate>,
+    pub cancelled: bool,
+}
+
+/// The regular files among `paths`, each once. Other links to a file that
+/// was already listed are left out, since they take no extra space.
+pub fn candidates(paths: &[PathBuf]) -> Vec<Candidate> {
+    let mut seen = HashSet::new();
+    let mut candidates = Vec::new();
+
+    for path in paths {
+        let Ok(metadata) = std::fs::symlink_metadata(path) else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        if let Some(key) = file_key(&metadata) {
+            if !seen.insert(key) {
+                continue;
+            }
+        }
+
+        candidates.push(Candidate {
+            path: path.clone(),
+            stamp: FileStamp::of(&metadata),
+            hash: None,
+        });
+    }
+
+    candidates
+}
+
+/// Identifies the file itself rather than the path to it
+#[cfg(unix)]
+fn file_key(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_key(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Whether two paths are links to the same file. Without inode numbers,
+/// matching size and modification time have to do.
+pub fn same_file(a: &std::fs::Metadata, b: &std::fs::Metadata) -> bool {
+    match (file_key(a), file_key(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.len() == b.len() && a.modified().ok() == b.modified().ok(),
+    }
+}
+
+/// Which of `copies` to keep by `strategy`. Ties, and several copies inside
+/// the folders a strategy asks for, go to the shortest path. `None` if no
+/// copy is inside those folders.
+pub fn keeper(
+    copies: &[Candidate],
+    strategy: &KeepStrategy,
+    category_folders: &[PathBuf],
+) -> Option<usize> {
+    let eligible = copies
+        .iter()
+        .enumerate()
+        .filter(|(_, copy)| match strategy {
+            KeepStrategy::PreferredDirectory { path } => in_folder(&copy.path, path),
+            KeepStrategy::CategoryFolder => category_folders
+                .iter()
+                .any(|folder| in_folder(&copy.path, folder)),
+            _ => true,
+        });
+
+    eligible
+        .min_by(|(_, a), (_, b)| {
+            let by_age = match strategy {
+                KeepStrategy::Oldest => a.stamp.mtime_ns.cmp(&b.stamp.mtime_ns),
+                KeepStrategy::Newest => b.stamp.mtime_ns.cmp(&a.stamp.mtime_ns),
+                _ => std::cmp::Ordering::Equal,
+            };
+            by_age
+                .then_with(|| a.path.as_os_str().len().cmp(&b.path.as_os_str().len()))
+                .then_with(|| a.path.cmp(&b.path))
+        })
+        .map(|(index, _)| index)
+}
+
+/// Whether `path` is somewhere inside `folder`. A relative folder, such as a
+/// category's `Documents`, matches at any depth.
+fn in_folder(path: &Path, folder: &Path) -> bool {
+    if folder.as_os_str().is_empty() {
+        return false;
+    }
+    if folder.is_absolute() {
+        return path.starts_with(folder);
+    }
+
+    path.ancestors().skip(1).any(|dir| dir.ends_with(folder))
+}
+
+/// Groups `candidates` by content. Empty files are never reported, and files
+/// that can't be read or change while being read are left out. Setting
+/// `cancel` stops the search at the next buffer read.
+pub fn find(
+    candidates: Vec<Candidate>,
+    options: &DuplicateOptions,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(&Progress),
+) -> Duplicates {
+    let pool = Pool {
+        threads: match options.threads {
+            Some(threads) if threads > 0 => threads,
+            _ => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        },
+        cancel,
+    };
+    let mut hashed = Vec::new();
+    let cancelled = |hashed| Duplicates {
+        groups: Vec::new(),
+        hashed,
+        cancelled: true,
+    };
+
+    // Only files sharing their size with another can have duplicates
+    let same_size = group_by(candidates, |c| (c.stamp.size > 0).then_some(c.stamp.size));
+
+    // Small files are read once, in full; the others by their ends, unless
+    // all of them are already hashed
+    let (small, large): (Vec<_>, Vec<_>) = same_size
+        .into_iter()
+        .partition(|group| group[0].stamp.size <= PARTIAL_LEN * 2);
+    let (known, large): (Vec<_>, Vec<_>) = large
+        .into_iter()
+        .partition(|group| group.iter().all(|c| c.hash.is_some()));
+
+    let large: Vec<Candidate> = large.into_iter().flatten().collect();
+    let ends = pool.run(
+        Stage::PartialHash,
+        &large,
+        large.len() as u64 * PARTIAL_LEN * 2,
+        |c| &c.path,
+        |c, reader| reader.partial_hash(&c.path, c.stamp.size).ok(),
+        &mut on_progress,
+    );
+    if pool.is_cancelled() {
+        return cancelled(hashed);
+    }
+    let same_ends = group_by(large.into_iter().zip(ends).collect(), |(c, hash)| {
+        Some((c.stamp.size, (*hash)??))
+    });
+
+    let (mut ready, unhashed): (Vec<_>, Vec<_>) = small
+        .into_iter()
+        .flatten()
+        .chain(same_ends.into_iter().flatten().map(|(c, _)| c))
+        .chain(known.into_iter().flatten())
+        .partition(|c| c.hash.is_some());
+    let full = pool.run(
+        Stage::FullHash,
+        &unhashed,
+        unhashed.iter().map(|c| c.stamp.size).sum(),
+        |c| &c.path,
+        |c, reader| reader.full_hash(&c.path, &c.stamp).ok().flatten(),
+        &mut on_progress,
+    );
+    for (mut candidate, hash) in unhashed.into_iter().zip(full) {
+        if let Some(hash) = hash.flatten() {
+            candidate.hash = Some(hash);
+            hashed.push(Candidate {
+                path: candidate.path.clone(),
+                stamp: candidate.stamp,
+                hash: candidate.hash.clone(),
+            });
+            ready.push(candidate);
+        }
+    }
+    if pool.is_cancelled() {
+        return cancelled(hashed);
+    }
+    let mut groups = group_by(ready, |c| c.hash.clone());
+
+    if options.verify {
+        let splits = pool.run(
+            Stage::Verify,
+            &groups,
+            groups
+                .iter()
+                .map(|g| g[0].stamp.size * 2 * (g.len() as u64 - 1))
+                .sum(),
+            |g| &g[0].path,
+            |g, reader| reader.split_by_content(g),
+            &mut on_progress,
+        );
+        if pool.is_cancelled() {
+            return cancelled(hashed);
+        }
+        groups = groups
+            .into_iter()
+            .zip(splits)
+            .flat_map(|(group, split)| {
+                let mut slots: Vec<Option<Candidate>> = group.into_iter().map(Some).collect();
+                split
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|indexes| {
+                        indexes
+                            .into_iter()
+                            .filter_map(|i| slots[i].take())
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .filter(|group| group.len() > 1)
+            .collect();
+    }
+
+    let mut groups: Vec<DuplicateGroup> = groups
+        .into_iter()
+        .map(|group| {
+            let mut files: Vec<PathBuf> = group.iter().map(|c| c.path.clone()).collect();
+            files.sort();
+            DuplicateGroup {
+                hash: group[0].hash.clone().unwrap_or_default(),
+                size: group[0].stamp.size,
+                files,
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.files.cmp(&b.files)));
+
+    Duplicates {
+        groups,
+        hashed,
+        cancelled: false,
+    }
+}
+
+/// The file's full content hash as `find` works it out, or `None` if it no
+/// longer matches `stamp` once read
+pub fn full_hash(path: &Path, stamp: &FileStamp) -> io::Result<Option<String>> {
+    let bytes = AtomicU64::new(0);
+    let cancel = AtomicBool::new(false);
+    let mut reader = Reader {
+        buffer: vec![0; BUFFER_LEN],
+        other: Vec::new(),
+        bytes: &bytes,
+        cancel: &cancel,
+    };
+    reader.full_hash(path, stamp)
+}
+
+/// Groups of two or more items with the same key; items without one are
+/// dropped
+fn group_by<T, K: Eq + std::hash::Hash>(
+    items: Vec<T>,
+    key: impl Fn(&T) -> Option<K>,
+) -> Vec<Vec<T>> {
+    let mut groups: HashMap<K, Vec<T>> = HashMap::new();
+    for item in items {
+        if let Some(k) = key(&item) {
+            groups.entry(k).or_default().push(item);
+        }
+    }
+    groups.into_values().filter(|g| g.len() > 1).collect()
+}
+
+struct Pool<'a> {
+    threads: usize,
+    cancel: &'a AtomicBool,
+}
+
+impl Pool<'_> {
+    fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// Runs `work` on every item, spread over the pool's threads, and returns
+    /// the results in the items' order; `None` for items skipped once
+    /// cancelled. Progress is reported from the calling thread, at least
+    /// every `PROGRESS_INTERVAL` while a large file is being read.
+    fn run<T: Sync, R: Send>(
+        &self,
+        stage: Stage,
+        items: &[T],
+        bytes_total: u64,
+        path: impl Fn(&T) -> &Path,
+        work: impl Fn(&T, &mut Reader) -> R + Sync,
+        on_progress: &mut impl FnMut(&Progress),
+    ) -> Vec<Option<R>> {
+        let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+        if items.is_empty() {
+            return results;
+        }
+
+        let next = AtomicUsize::new(0);
+        let bytes = AtomicU64::new(0);
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::scope(|scope| {
+            for _ in 0..self.threads.min(items.len()) {
+                let tx = tx.clone();
+                let (next, bytes, work) = (&next, &bytes, &work);
+                scope.spawn(move || {
+                    let mut reader = Reader {
+                        buffer: vec![0; BUFFER_LEN],
+                        other: Vec::new(),
+                        bytes,
+                        cancel: self.cancel,
+                    };
+                    while !self.is_cancelled() {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            break;
+                        };
+                        if tx.send((index, work(item, &mut reader))).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(tx);
+
+            let mut files_done = 0;
+            let mut current = None;
+            let mut last_sent: Option<Instant> = None;
+            loop {
+                let finished = match rx.recv_timeout(PROGRESS_INTERVAL) {
+                    Ok((index, result)) => {
+                        results[index] = Some(result);
+                        files_done += 1;
+                        current = Some(index);
+                        files_done == items.len()
+                    }
+                    Err(RecvTimeoutError::Timeout) => false,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+
+                if finished || last_sent.is_none_or(|t| t.elapsed() >= PROGRESS_INTERVAL) {
+                    last_sent = Some(Instant::now());
+                    on_progress(&Progress {
+                        stage,
+                        files_done,
+                        files_total: items.len(),
+                        bytes_done: bytes.load(Ordering::Relaxed).min(bytes_total),
+                        bytes_total,
+                        current_file: current.map(|i| path(&items[i])),
+                    });
+                }
+            }
+        });
+
+        results
+    }
+}
+
+/// A worker thread's buffers. Reads are counted towards the stage's bytes
+/// and fail once the search is cancelled.
+struct Reader<'a> {
+    buffer: Vec<u8>,
+    /// Only needed to compare files
+    other: Vec<u8>,
+    bytes: &'a AtomicU64,
+    cancel: &'a AtomicBool,
+}
+
+impl Reader<'_> {
+    /// Hash of the first and last `PARTIAL_LEN` bytes of a file of `size`
+    /// bytes
+    fn partial_hash(&mut self, path: &Path, size: u64) -> io::Result<[u8; 32]> {
+        let mut file = File::open(path)?;
+        let mut hasher = blake3::Hasher::new();
+        let len = PARTIAL_LEN as usize;
+
+        read_full(&mut file, &mut self.buffer[..len], self.bytes, self.cancel)?;
+        hasher.update(&self.buffer[..len]);
+        file.seek(SeekFrom::Start(size - PARTIAL_LEN))?;
+        read_full(&mut file, &mut self.buffer[..len], self.bytes, self.cancel)?;
+        hasher.update(&self.buffer[..len]);
+
+        Ok(*hasher.finalize().as_bytes())
+    }
+
+    /// The file's BLAKE3 hash in hex, or `None` if it no longer matches
+    /// `stamp` once read
+    fn full_hash(&mut self, path: &Path, stamp: &FileStamp) -> io::Result<Option<String>> {
+        let mut file = File::open(path)?;
+        let mut hasher = blake3::Hasher::new();
+
+        loop {
+            match read_full(&mut file, &mut self.buffer, self.bytes, self.cancel)? {
+                0 => break,
+                n => hasher.update(&self.buffer[..n]),
+            };
+        }
+
+        let unchanged = FileStamp::of(&file.metadata()?) == *stamp;
+        Ok(unchanged.then(|| hasher.finalize().to_hex().to_string()))
+    }
+
+    /// Splits files with equal hashes into groups whose bytes really match,
+    /// by index, comparing each file against the first of every group so far
+    fn split_by_content(&mut self, candidates: &[Candidate]) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+
+        'candidates: for (index, candidate) in candidates.iter().enumerate() {
+            for group in groups.iter_mut() {
+                match self.same_content(&candidates[group[0]].path, &candidate.path) {
+                    Ok(true) => {
+                        group.push(index);
+                        continue 'candidates;
+                    }
+                    Ok(false) => {}
+                    // Can't be vouched for
+                    Err(_) => continue 'candidates,
+                }
+            }
+            groups.push(vec![index]);
+        }
+
+        groups
+    }
+
+    fn same_content(&mut self, a: &Path, b: &Path) -> io::Result<bool> {
+        let mut a = File::open(a)?;
+        let mut b = File::open(b)?;
+        self.other.resize(BUFFER_LEN, 0);
+
+        loop {
+            let n = read_full(&mut a, &mut self.buffer, self.bytes, self.cancel)?;
+            let m = read_full(&mut b, &mut self.other, self.bytes, self.cancel)?;
+            if n != m || self.buffer[..n] != self.other[..m] {
+                return Ok(false);
+            }
+            if n == 0 {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+/// Fills `buffer` unless the file ends first, returning the bytes read
+fn read_full(
+    file: &mut File,
+    buffer: &mut [u8],
+    bytes: &AtomicU64,
+    cancel: &AtomicBool,
+) -> io::Result<usize> {
+    if cancel.load(Ordering::Relaxed) {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+    }
+
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    bytes.fetch_add(filled as u64, Ordering::Relaxed);
+
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("fileog-duplicates-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, name: &str, content: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn hash(path: &Path) -> String {
+        blake3::hash(&std::fs::read(path).unwrap())
+            .to_hex()
+            .to_string()
+    }
+
+    fn options(verify: bool) -> DuplicateOptions {
+        DuplicateOptions {
+            verify,
+            threads: Some(2),
+        }
+    }
+
+    /// Runs `find` on `paths`, returning the result and every stage's last
+    /// progress as (stage, files done, files total)
+    fn run(paths: &[PathBuf], verify: bool) -> (Duplicates, Vec<(Stage, usize, usize)>) {
+        let mut stages: Vec<(Stage, usize, usize)> = Vec::new();
+        let found = find(
+            candidates(paths),
+            &options(verify),
+            &AtomicBool::new(false),
+            |p| match stages.last_mut() {
+                Some(last) if last.0 == p.stage => *last = (p.stage, p.files_done, p.files_total),
+                _ => stages.push((p.stage, p.files_done, p.files_total)),
+            },
+        );
+        (found, stages)
+    }
+
+    #[test]
+    fn test_find_stages() {
+        let dir = temp_dir("stages");
+        let small = vec![b's'; 1000];
+        let large = vec![b'l'; 300 * 1024];
+        let mut middle = large.clone();
+        middle[150 * 1024] = b'm';
+        let mut start = large.clone();
+        start[0] = b'x';
+
+        let paths = vec![
+            write(&dir, "small1", &small),
+            write(&dir, "small2", &small),
+            // Same size, different content
+            write(&dir, "small3", &[b't'; 1000]),
+            write(&dir, "large1", &large),
+            write(&dir, "large2", &large),
+            // Same ends, different middle
+            write(&dir, "large3", &middle),
+            write(&dir, "large4", &start),
+            write(&dir, "empty1", b""),
+            write(&dir, "empty2", b""),
+            write(&dir, "unique", b"unique"),
+            dir.join("missing"),
+        ];
+        let (found, stages) = run(&paths, false);
+
+        assert!(!found.cancelled);
+        let groups = |d: &Duplicates| -> Vec<_> {
+            d.groups.iter().map(|g| (g.size, g.files.clone())).collect()
+        };
+        assert_eq!(
+            groups(&found),
+            [
+                (300 * 1024, vec![paths[3].clone(), paths[4].clone()]),
+                (1000, vec![paths[0].clone(), paths[1].clone()]),
+            ]
+        );
+        assert_eq!(found.groups[0].hash, hash(&paths[3]));
+
+        // The partial stage reads the four large files and drops the one
+        // with a different start; the full stage then reads the rest
+        assert_eq!(
+            stages,
+            [(Stage::PartialHash, 4, 4), (Stage::FullHash, 6, 6)]
+        );
+        let mut hashed: Vec<_> = found.hashed.iter().map(|c| c.path.clone()).collect();
+        hashed.sort();
+        let mut expected = vec![
+            &paths[0], &paths[1], &paths[2], &paths[3], &paths[4], &paths[5],
+        ];
+        expected.sort();
+        assert_eq!(hashed.iter().collect::<Vec<_>>(), expected);
+        assert!(found.hashed.iter().all(|c| c.hash == Some(hash(&c.path))));
+
+        let (verified, stages) = run(&paths, true);
+        assert_eq!(groups(&verified), groups(&found));
+        assert_eq!(stages.last(), Some(&(Stage::Verify, 2, 2)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_reuses_known_hashes() {
+        let dir = temp_dir("known");
+        let paths = vec![
+            write(&dir, "a", &vec![b'a'; 200 * 1024]),
+            write(&dir, "b", &vec![b'b'; 200 * 1024]),
+        ];
+        let mut candidates = candidates(&paths);
+        for candidate in &mut candidates {
+            candidate.hash = Some("known".to_string());
+        }
+
+        let mut reported = false;
+        let found = find(candidates, &options(false), &AtomicBool::new(false), |_| {
+            reported = true
+        });
+
+        // Neither file is read again
+        assert!(!reported);
+        assert!(found.hashed.is_empty());
+        assert_eq!(found.groups.len(), 1);
+        assert_eq!(found.groups[0].hash, "known");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_cancelled_keeps_hashes() {
+        let dir = temp_dir("cancel");
+        let paths: Vec<_> = (0..50)
+            .map(|i| write(&dir, &format!("f{}", i), &[b'x'; 100]))
+            .collect();
+        let cancel = AtomicBool::new(false);
+
+        let found = find(candidates(&paths), &options(false), &cancel, |p| {
+            if p.stage == Stage::FullHash && p.files_done > 0 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        });
+
+        assert!(found.cancelled);
+        assert!(found.groups.is_empty());
+        assert!(!found.hashed.is_empty());
+        assert!(found.hashed.iter().all(|c| c.hash == Some(hash(&c.path))));
+
+        // Cancelled before anything is read
+        cancel.store(true, Ordering::Relaxed);
+        let found = find(candidates(&paths), &options(false), &cancel, |_| {});
+        assert!(found.cancelled && found.hashed.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_candidates_skip_links_to_listed_files() {
+        let dir = temp_dir("candidates");
+        let file = write(&dir, "file", b"content");
+        let link = dir.join("link");
+        std::fs::hard_link(&file, &link).unwrap();
+        let other = write(&dir, "other", b"content");
+
+        let paths: Vec<_> = candidates(&[file.clone(), link, other.clone(), dir.clone()])
+            .into_iter()
+            .map(|c| c.path)
+            .collect();
+        assert_eq!(paths, [file, other]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn copy(path: &str, mtime_ns: i64) -> Candidate {
+        Candidate {
+            path: PathBuf::from(path),
+            stamp: FileStamp {
+                inode: None,
+                size: 1,
+                mtime_ns,
+            },
+            hash: None,
+        }
+    }
+
+    #[test]
+    fn test_keeper() {
+        let copies = [
+            copy("/archive/deep/photo.jpg", 100),
+            copy("/new/photo.jpg", 300),
+            copy("/b/photo.jpg", 100),
+            copy("/a/photo.jpg", 300),
+        ];
+        let keep =
+            |strategy: KeepStrategy, folders: &[PathBuf]| keeper(&copies, &strategy, folders);
+
+        // Ties on age go to the shortest path, then to path order
+        assert_eq!(keep(KeepStrategy::Oldest, &[]), Some(2));
+        assert_eq!(keep(KeepStrategy::Newest, &[]), Some(3));
+        assert_eq!(keep(KeepStrategy::ShortestPath, &[]), Some(3));
+
+        let preferred = |path: &str| KeepStrategy::PreferredDirectory {
+            path: PathBuf::from(path),
+        };
+        assert_eq!(keep(preferred("/archive"), &[]), Some(0));
+        assert_eq!(keep(preferred("/arch"), &[]), None);
+        assert_eq!(keep(preferred(""), &[]), None);
+
+        let folders = [PathBuf::from("deep"), PathBuf::from("/new")];
+        assert_eq!(keep(KeepStrategy::CategoryFolder, &folders), Some(1));
+        assert_eq!(keep(KeepStrategy::CategoryFolder, &[]), None);
+        assert_eq!(keep(KeepStrategy::Oldest, &folders), Some(2));
+        assert_eq!(keeper(&[], &KeepStrategy::Oldest, &[]), None);
+    }
+
+    #[test]
+    fn test_in_folder() {
+        let path = Path::new("/home/me/sorted/Documents/2024/report.pdf");
+
+        assert!(in_folder(path, Path::new("/home/me")));
+        assert!(!in_folder(path, Path::new("/home/m")));
+        // Relative folders match at any depth, but whole components only
+        assert!(in_folder(path, Path::new("Documents")));
+        assert!(in_folder(path, Path::new("sorted/Documents")));
+        assert!(in_folder(path, Path::new("2024")));
+        assert!(!in_folder(path, Path::new("Docs")));
+        assert!(!in_folder(path, Path::new("me/Documents")));
+        // The file itself isn't a folder it's in
+        assert!(!in_folder(path, Path::new("report.pdf")));
+        assert!(!in_folder(path, Path::new("")));
+    }
+}
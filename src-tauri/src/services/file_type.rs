@@ -0,0 +1,234 @@
+//! Identifies a file's type from its signature, so files with a wrong or
+//! missing extension are still classified by what they contain.
+
+use crate::models::{FileItem, FileType};
+use std::path::{Path, PathBuf};
+
+/// Extensions that name the same format
+const EQUIVALENT_EXTENSIONS: &[&[&str]] = &[
+    &["jpg", "jpeg", "jpe", "jfif"],
+    &["tif", "tiff"],
+    &["heif", "heic", "hif"],
+    &["mp4", "m4v", "m4a", "m4b", "m4p", "3gp", "3g2"],
+    &["mkv", "webm", "mka", "mk3d"],
+    &["ogg", "oga", "ogv", "opus", "spx"],
+    &["mpg", "mpeg", "mpe"],
+    &["mov", "qt"],
+    &["midi", "mid"],
+    &["aiff", "aif", "aifc"],
+    &["wav", "wave"],
+    &["gz", "tgz"],
+    &["bz2", "tbz", "tbz2"],
+    &["xz", "txz"],
+    &["exe", "dll", "sys", "scr", "cpl", "ocx"],
+    &["der", "pem", "crt", "cer"],
+    &["ps", "eps"],
+];
+
+/// Signatures shared by many formats (ZIP-based packages, XML dialects,
+/// OLE compound files, ELF objects...), which say little about the extension
+/// a file should have
+const GENERIC_FORMATS: &[&str] = &["zip", "xml", "html", "sh", "elf", "msi", "sqlite"];
+
+/// What a file's signature says it is
+pub struct DetectedType {
+    pub file_type: FileType,
+    /// The format's usual extension, without the dot
+    pub extension: &'static str,
+}
+
+/// Matches `header`, the first bytes of a file, against known signatures
+pub fn detect(header: &[u8]) -> Option<DetectedType> {
+    let kind = infer::get(header)?;
+    let extension = kind.extension();
+
+    let file_type = match FileType::from_extension(extension) {
+        FileType::Other => match kind.matcher_type() {
+            infer::MatcherType::Image => FileType::Image,
+            infer::MatcherType::Video => FileType::Video,
+            infer::MatcherType::Audio => FileType::Audio,
+            infer::MatcherType::Archive => FileType::Archive,
+            infer::MatcherType::Doc | infer::MatcherType::Book => FileType::Document,
+            infer::MatcherType::Text => FileType::Code,
+            _ => FileType::Other,
+        },
+        file_type => file_type,
+    };
+
+    Some(DetectedType {
+        file_type,
+        extension,
+    })
+}
+
+/// Reclassifies `item` by its content and flags an extension that doesn't
+/// match it. Files without a recognizable signature are left as they are.
+pub fn apply(item: &mut FileItem, header: &[u8]) {
+    let Some(detected) = detect(header) else {
+        return;
+    };
+    let generic = GENERIC_FORMATS.contains(&detected.extension);
+
+    // A generic signature only fills in for an extension that says nothing
+    if !generic || item.file_type == FileType::Other {
+        item.file_type = detected.file_type;
+    }
+
+    match item.extension.as_deref() {
+        Some(extension) if !generic && !is_equivalent(extension, detected.extension) => {
+            item.extension_mismatch = true;
+            item.suggested_extension = Some(detected.extension.to_string());
+        }
+        None if !generic => {
+            item.suggested_extension = Some(detected.extension.to_string());
+        }
+        _ => {}
+    }
+}
+
+/// Where `path` goes once it has the `suggested` extension. A name that
+/// already ends in it before the wrong extension, as in `photo.jpg.png`,
+/// just loses the wrong one.
+pub fn fixed_path(path: &Path, suggested: &str) -> PathBuf {
+    let inner = path
+        .file_stem()
+        .and_then(|stem| Path::new(stem).extension())
+        .map(|e| e.to_string_lossy());
+
+    match inner {
+        Some(inner) if path.extension().is_some() && is_equivalent(&inner, suggested) => {
+            path.with_extension("")
+        }
+        _ => path.with_extension(suggested),
+    }
+}
+
+fn is_equivalent(extension: &str, detected: &str) -> bool {
+    let extension = extension.to_lowercase();
+    if extension == detected {
+        return true;
+    }
+
+    EQUIVALENT_EXTENSIONS
+        .iter()
+        .any(|group| group.contains(&extension.as_str()) && group.contains(&detected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FileMetadata;
+
+    const JPEG: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F', 0];
+    const PNG: &[u8] = &[
+        0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0x0D,
+    ];
+    const ZIP: &[u8] = &[b'P', b'K', 3, 4, 0x14, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    fn item(name: &str) -> FileItem {
+        let path = PathBuf::from("/files").join(name);
+        let extension = path.extension().map(|e| e.to_string_lossy().to_string());
+        FileItem {
+            id: "1".to_string(),
+            file_type: extension
+                .as_deref()
+                .map(FileType::from_extension)
+                .unwrap_or(FileType::Other),
+            path,
+            name: name.to_string(),
+            extension,
+            size: 0,
+            hash: None,
+            created_at: 0,
+            modified_at: 0,
+            category: None,
+            metadata: FileMetadata::default(),
+            link_target: None,
+            extension_mismatch: false,
+            suggested_extension: None,
+        }
+    }
+
+    fn applied(name: &str, header: &[u8]) -> FileItem {
+        let mut item = item(name);
+        apply(&mut item, header);
+        item
+    }
+
+    #[test]
+    fn test_detect() {
+        let jpeg = detect(JPEG).unwrap();
+        assert_eq!(jpeg.extension, "jpg");
+        assert_eq!(jpeg.file_type, FileType::Image);
+
+        assert_eq!(detect(PNG).unwrap().extension, "png");
+        assert!(detect(b"just some text").is_none());
+        assert!(detect(&[]).is_none());
+    }
+
+    #[test]
+    fn test_apply_flags_mismatched_extension() {
+        let item = applied("photo.png", JPEG);
+        assert!(item.extension_mismatch);
+        assert_eq!(item.suggested_extension.as_deref(), Some("jpg"));
+        assert_eq!(item.file_type, FileType::Image);
+
+        // Content decides the type when the extension says something else
+        let item = applied("notes.txt", PNG);
+        assert!(item.extension_mismatch);
+        assert_eq!(item.file_type, FileType::Image);
+    }
+
+    #[test]
+    fn test_apply_suggests_missing_extension() {
+        let item = applied("photo", JPEG);
+        assert!(!item.extension_mismatch);
+        assert_eq!(item.suggested_extension.as_deref(), Some("jpg"));
+        assert_eq!(item.file_type, FileType::Image);
+    }
+
+    #[test]
+    fn test_apply_accepts_equivalent_extensions() {
+        for name in ["photo.jpg", "photo.JPEG", "photo.jfif"] {
+            let item = applied(name, JPEG);
+            assert!(!item.extension_mismatch, "{}", name);
+            assert!(item.suggested_extension.is_none(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_apply_generic_formats_only_fill_in() {
+        // A ZIP signature doesn't contradict an Office document or an APK...
+        let item = applied("report.docx", ZIP);
+        assert!(!item.extension_mismatch);
+        assert!(item.suggested_extension.is_none());
+        assert_eq!(item.file_type, FileType::Document);
+
+        // ...but classifies a file whose extension says nothing
+        let item = applied("blob", ZIP);
+        assert_eq!(item.file_type, FileType::Archive);
+        assert!(item.suggested_extension.is_none());
+
+        // Files without a known signature are left alone
+        let item = applied("readme.md", b"# Title\n");
+        assert_eq!(item.file_type, FileType::from_extension("md"));
+        assert!(!item.extension_mismatch);
+    }
+
+    #[test]
+    fn test_fixed_path() {
+        let fixed = |name: &str, suggested: &str| {
+            fixed_path(&Path::new("/files").join(name), suggested)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .to_string()
+        };
+
+        assert_eq!(fixed("photo.png", "jpg"), "photo.jpg");
+        assert_eq!(fixed("photo", "jpg"), "photo.jpg");
+        assert_eq!(fixed("photo.jpg.png", "jpg"), "photo.jpg");
+        assert_eq!(fixed("photo.JPEG.png", "jpg"), "photo.JPEG");
+        assert_eq!(fixed("v1.2.png", "jpg"), "v1.2.jpg");
+    }
+}
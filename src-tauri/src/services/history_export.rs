@@ -0,0 +1,419 @@
+//! Renders operation history as CSV, JSON Lines or an HTML report, and reads
+//! JSON Lines exports back in for import.
+
+use crate::error::AppError;
+use crate::models::{Operation, OperationBatch};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+    Html,
+}
+
+/// Replaces a leading `from` with `to` in imported paths
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathRemap {
+    pub from: String,
+    pub to: String,
+}
+
+const CSV_HEADER: [&str; 17] = [
+    "batch_id",
+    "batch_created_at",
+    "batch_description",
+    "batch_source",
+    "operation_id",
+    "operation_type",
+    "status",
+    "error_code",
+    "error_message",
+    "timestamp",
+    "source_path",
+    "destination_path",
+    "original_name",
+    "new_name",
+    "backup_path",
+    "size",
+    "hash",
+];
+
+pub fn render(format: ExportFormat, batches: &[OperationBatch]) -> Result<String, AppError> {
+    match format {
+        ExportFormat::Csv => Ok(render_csv(batches)),
+        ExportFormat::Jsonl => render_jsonl(batches),
+        ExportFormat::Html => Ok(render_html(batches)),
+    }
+}
+
+fn format_time(timestamp: i64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0)
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_default()
+}
+
+fn path_string(path: Option<&PathBuf>) -> String {
+    path.map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// One row per operation, with its batch's details repeated on each row
+fn render_csv(batches: &[OperationBatch]) -> String {
+    let mut out = CSV_HEADER.join(",");
+    out.push_str("\r\n");
+
+    for batch in batches {
+        for op in &batch.operations {
+            let fingerprint = op.fingerprint.as_ref();
+            let row = [
+                batch.id.clone(),
+                format_time(batch.created_at),
+                batch.description.clone(),
+                batch.source.as_str().to_string(),
+                op.id.clone(),
+                op.operation_type.as_str().to_string(),
+                op.status.as_db_str().to_string(),
+                op.error_code.clone().unwrap_or_default(),
+                op.status.error_message().unwrap_or_default().to_string(),
+                format_time(op.timestamp),
+                op.source_path.to_string_lossy().to_string(),
+                path_string(op.destination_path.as_ref()),
+                op.original_name.clone().unwrap_or_default(),
+                op.new_name.clone().unwrap_or_default(),
+                path_string(op.backup_path.as_ref()),
+                fingerprint.map(|f| f.size.to_string()).unwrap_or_default(),
+                fingerprint.and_then(|f| f.hash.clone()).unwrap_or_default(),
+            ];
+
+            let fields: Vec<String> = row.iter().map(|f| csv_field(f)).collect();
+            out.push_str(&fields.join(","));
+            out.push_str("\r\n");
+        }
+    }
+
+    out
+}
+
+/// Quotes a field per RFC 4180 when it contains a delimiter, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// One batch per line, operations included, so the file can be imported back
+fn render_jsonl(batches: &[OperationBatch]) -> Result<String, AppError> {
+    let mut out = String::new();
+
+    for batch in batches {
+        out.push_str(&serde_json::to_string(batch)?);
+        out.push('\n');
+    }
+
+    Ok(out)
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const HTML_STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem;color:#222}\
+h1{font-size:1.4rem}h2{font-size:1.1rem;margin:1.5rem 0 .25rem}\
+.meta{color:#666;font-size:.85rem;margin-bottom:.5rem}\
+table{border-collapse:collapse;width:100%;font-size:.85rem}\
+th,td{border:1px solid #ddd;padding:.3rem .5rem;text-align:left;vertical-align:top}\
+th{background:#f5f5f5}td.path{font-family:monospace;word-break:break-all}\
+.failed{color:#b00020}.undone{color:#888}";
+
+/// A standalone report: inline styles, no scripts or external resources
+fn render_html(batches: &[OperationBatch]) -> String {
+    let operation_count: usize = batches.iter().map(|b| b.operations.len()).sum();
+
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">");
+    out.push_str("<title>Operation history</title>");
+    out.push_str(&format!("<style>{}</style></head><body>\n", HTML_STYLE));
+    out.push_str("<h1>Operation history</h1>\n");
+    out.push_str(&format!(
+        "<p class=\"meta\">Generated {} &middot; {} batches &middot; {} operations</p>\n",
+        escape_html(&Utc::now().to_rfc3339()),
+        batches.len(),
+        operation_count
+    ));
+
+    for batch in batches {
+        out.push_str(&format!(
+            "<h2>{}</h2>\n<p class=\"meta\">{} &middot; {} &middot; batch {}</p>\n",
+            escape_html(&batch.description),
+            escape_html(&format_time(batch.created_at)),
+            batch.source.as_str(),
+            escape_html(&batch.id)
+        ));
+        out.push_str("<table><tr><th>Time</th><th>Type</th><th>Status</th>");
+        out.push_str("<th>Source</th><th>Destination</th><th>Hash</th></tr>\n");
+
+        for op in &batch.operations {
+            out.push_str(&html_row(op));
+        }
+
+        out.push_str("</table>\n");
+    }
+
+    out.push_str("</body></html>\n");
+    out
+}
+
+fn html_row(op: &Operation) -> String {
+    let status = match (op.status.error_message(), &op.error_code) {
+        (Some(message), Some(code)) => format!("{} ({}): {}", op.status.as_db_str(), code, message),
+        (Some(message), None) => format!("{}: {}", op.status.as_db_str(), message),
+        _ => op.status.as_db_str().to_string(),
+    };
+    let destination = op
+        .destination_path
+        .as_ref()
+        .or(op.backup_path.as_ref())
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let hash = op
+        .fingerprint
+        .as_ref()
+        .and_then(|f| f.hash.as_deref())
+        .unwrap_or_default();
+
+    format!(
+        "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td class=\"path\">{}</td><td class=\"path\">{}</td><td class=\"path\">{}</td></tr>\n",
+        op.status.as_db_str(),
+        escape_html(&format_time(op.timestamp)),
+        op.operation_type.as_str(),
+        escape_html(&status),
+        escape_html(&op.source_path.to_string_lossy()),
+        escape_html(&destination),
+        escape_html(hash)
+    )
+}
+
+/// Parses a JSON Lines export, skipping blank lines
+pub fn parse_jsonl(content: &str) -> Result<Vec<OperationBatch>, AppError> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                AppError::OperationFailed(format!(
+                    "Invalid history record on line {}: {}",
+                    index + 1,
+                    e
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Applies the longest matching remap. Prefixes match whole path components,
+/// and `/` and `\` are treated alike so exports move between platforms.
+pub fn remap_path(path: &Path, remaps: &[PathRemap]) -> PathBuf {
+    let original = path.to_string_lossy();
+
+    let best = remaps
+        .iter()
+        .filter(|remap| !remap.from.is_empty())
+        .filter_map(|remap| {
+            let from = remap.from.trim_end_matches(['/', '\\']);
+            let rest = strip_path_prefix(&original, from)?;
+            Some((from.len(), remap, rest))
+        })
+        .max_by_key(|(len, _, _)| *len);
+
+    let Some((_, remap, rest)) = best else {
+        return path.to_path_buf();
+    };
+
+    let mut remapped = PathBuf::from(&remap.to);
+    for component in rest.split(['/', '\\']).filter(|c| !c.is_empty()) {
+        remapped.push(component);
+    }
+    remapped
+}
+
+fn strip_path_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let normalize = |c: char| if c == '\\' { '/' } else { c };
+
+    let mut path_chars = path.char_indices();
+    for expected in prefix.chars() {
+        let (_, actual) = path_chars.next()?;
+        if normalize(actual) != normalize(expected) {
+            return None;
+        }
+    }
+
+    let rest = &path[prefix.len()..];
+    if rest.is_empty() || rest.starts_with(['/', '\\']) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BatchSource, OperationStatus, OperationType};
+
+    fn remap(from: &str, to: &str) -> PathRemap {
+        PathRemap {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    fn batch(source_path: &str, description: &str) -> OperationBatch {
+        OperationBatch {
+            id: "b1".to_string(),
+            operations: vec![Operation {
+                id: "o1".to_string(),
+                operation_type: OperationType::Move,
+                source_path: PathBuf::from(source_path),
+                destination_path: Some(PathBuf::from("/dest/a.txt")),
+                original_name: Some("a.txt".to_string()),
+                new_name: None,
+                timestamp: 0,
+                status: OperationStatus::Failed("disk \"full\"".to_string()),
+                batch_id: Some("b1".to_string()),
+                backup_path: None,
+                fingerprint: None,
+                error_code: Some("io".to_string()),
+                purged: false,
+            }],
+            created_at: 0,
+            description: description.to_string(),
+            source: BatchSource::Manual,
+            root_folder: None,
+            operation_count: 1,
+            failed_count: 1,
+            total_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_remap_path_matches_whole_components() {
+        let remaps = [remap("/old/dir", "/new")];
+
+        assert_eq!(
+            remap_path(Path::new("/old/dir/a/b.txt"), &remaps),
+            PathBuf::from("/new").join("a").join("b.txt")
+        );
+        assert_eq!(
+            remap_path(Path::new("/old/dir"), &remaps),
+            PathBuf::from("/new")
+        );
+        assert_eq!(
+            remap_path(Path::new("/old/directory/a.txt"), &remaps),
+            PathBuf::from("/old/directory/a.txt")
+        );
+    }
+
+    #[test]
+    fn test_remap_path_treats_separators_alike() {
+        let remaps = [remap("C:/Users/me/", "/home/me")];
+
+        assert_eq!(
+            remap_path(Path::new("C:\\Users\\me\\Docs\\a.txt"), &remaps),
+            PathBuf::from("/home/me").join("Docs").join("a.txt")
+        );
+    }
+
+    #[test]
+    fn test_remap_path_prefers_longest_prefix() {
+        let remaps = [
+            remap("", "/ignored"),
+            remap("/data", "/mnt/data"),
+            remap("/data/photos", "/pictures"),
+        ];
+
+        assert_eq!(
+            remap_path(Path::new("/data/photos/x.jpg"), &remaps),
+            PathBuf::from("/pictures").join("x.jpg")
+        );
+        assert_eq!(
+            remap_path(Path::new("/data/music/y.mp3"), &remaps),
+            PathBuf::from("/mnt/data").join("music").join("y.mp3")
+        );
+        assert_eq!(
+            remap_path(Path::new("/elsewhere/z"), &remaps),
+            PathBuf::from("/elsewhere/z")
+        );
+    }
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn test_render_csv_quotes_paths() {
+        let csv = render(ExportFormat::Csv, &[batch("/in/a,b.txt", "tidy")]).unwrap();
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+
+        assert_eq!(lines[0], CSV_HEADER.join(","));
+        assert!(lines[1].contains(",\"/in/a,b.txt\","));
+        assert!(lines[1].contains(",\"disk \"\"full\"\"\","));
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("<a href=\"x\">Tom & Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_render_html_escapes_paths_and_descriptions() {
+        let html = render(
+            ExportFormat::Html,
+            &[batch("/in/<script>.txt", "<b>bold</b>")],
+        )
+        .unwrap();
+
+        assert!(html.contains("/in/&lt;script&gt;.txt"));
+        assert!(html.contains("<h2>&lt;b&gt;bold&lt;/b&gt;</h2>"));
+        assert!(html.contains("failed (io): disk &quot;full&quot;"));
+        assert!(!html.contains("<script>"));
+    }
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let jsonl = render(ExportFormat::Jsonl, &[batch("/in/a.txt", "tidy")]).unwrap();
+        let parsed = parse_jsonl(&format!("\n{}\n", jsonl)).unwrap();
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(
+            parsed[0].operations[0].source_path,
+            PathBuf::from("/in/a.txt")
+        );
+        assert!(parse_jsonl("{not json").is_err());
+    }
+}
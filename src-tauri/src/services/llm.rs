@@ -58,17 +58,23 @@ impl LlmService {
         file_name: &str,
         file_extension: &str,
         file_size: u64,
+        preview_text: Option<&str>,
         categories: &[String],
         custom_prompt: Option<&str>,
     ) -> Result<ClassificationResult, AppError> {
         let system_prompt = custom_prompt.unwrap_or(DEFAULT_CLASSIFICATION_PROMPT);
 
         let categories_str = categories.join(", ");
+        let content = preview_text
+            .filter(|text| !text.trim().is_empty())
+            .map(|text| format!("文件内容（开头部分）:\n{}\n", text))
+            .unwrap_or_default();
         let user_prompt = format!(
             "请分析以下文件并建议合适的分类：\n\
             文件名: {}\n\
             扩展名: {}\n\
             文件大小: {} bytes\n\
+            {}\
             可用分类: [{}]\n\n\
             请返回JSON格式：\n\
             {{\n\
@@ -77,7 +83,7 @@ impl LlmService {
               \"confidence\": 0.95,\n\
               \"reasoning\": \"分类原因\"\n\
             }}",
-            file_name, file_extension, file_size, categories_str
+            file_name, file_extension, file_size, content, categories_str
         );
 
         let response = self.send_chat_request(system_prompt, &user_prompt).await?;
@@ -90,15 +96,22 @@ impl LlmService {
 
     pub async fn classify_files_batch(
         &self,
-        files: Vec<(String, String, u64)>, // (name, extension, size)
+        files: Vec<(String, String, u64, Option<String>)>, // (name, extension, size, preview text)
         categories: &[String],
         custom_prompt: Option<&str>,
     ) -> Result<Vec<ClassificationResult>, AppError> {
         let mut results = Vec::new();
 
-        for (name, ext, size) in files {
+        for (name, ext, size, preview_text) in files {
             match self
-                .classify_file(&name, &ext, size, categories, custom_prompt)
+                .classify_file(
+                    &name,
+                    &ext,
+                    size,
+                    preview_text.as_deref(),
+                    categories,
+                    custom_prompt,
+                )
                 .await
             {
                 Ok(result) => results.push(result),
@@ -346,7 +359,7 @@ impl LlmService {
     }
 }
 
-const DEFAULT_CLASSIFICATION_PROMPT: &str = r#"你是一个文件分类助手。根据文件名、扩展名和文件大小，将文件分类到合适的类别中。
+const DEFAULT_CLASSIFICATION_PROMPT: &str = r#"你是一个文件分类助手。根据文件名、扩展名、文件大小以及提供的文件内容，将文件分类到合适的类别中。
 
 分类规则：
 - documents: 文档文件（.doc, .docx, .pdf, .txt, .md, .xlsx, .pptx等）
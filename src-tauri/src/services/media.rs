@@ -0,0 +1,727 @@
+//! Reads audio and video duration from container headers, without decoding
+//! any media. Covers MP4/MOV/M4A, Matroska/WebM, MP3 and FLAC.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// How many elements or boxes to step over looking for the one that holds
+/// the duration, so a corrupt file can't keep the parser busy
+const MAX_STEPS: usize = 1024;
+
+/// Length in whole seconds, or `None` if the format isn't supported or the
+/// header can't be parsed
+pub fn duration(path: &Path, mime: &str) -> Option<u64> {
+    let parse: fn(&mut File) -> Option<f64> = match mime {
+        "video/mp4" | "video/x-m4v" | "video/quicktime" | "audio/m4a" => mp4_duration,
+        "video/x-matroska" | "video/webm" => matroska_duration,
+        "audio/mpeg" => mp3_duration,
+        "audio/x-flac" => flac_duration,
+        _ => return None,
+    };
+
+    let mut file = File::open(path).ok()?;
+    let seconds = parse(&mut file)?;
+
+    (seconds.is_finite() && seconds >= 0.0).then(|| seconds.round() as u64)
+}
+
+pub(crate) fn read_array<const N: usize>(file: &mut File) -> Option<[u8; N]> {
+    let mut buf = [0; N];
+    file.read_exact(&mut buf).ok()?;
+    Some(buf)
+}
+
+/// Reads up to `len` bytes, fewer at the end of the file
+pub(crate) fn read_vec(file: &mut File, len: u64) -> Option<Vec<u8>> {
+    let mut buf = Vec::new();
+    file.take(len).read_to_end(&mut buf).ok()?;
+    Some(buf)
+}
+
+pub(crate) fn be_uint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u64)
+}
+
+// MP4 / QuickTime
+
+/// Finds `moov/mvhd` and divides its duration by its timescale
+fn mp4_duration(file: &mut File) -> Option<f64> {
+    let end = file.metadata().ok()?.len();
+    let (moov_start, moov_end) = find_box(file, 0, end, b"moov")?;
+    let (mvhd_start, mvhd_end) = find_box(file, moov_start, moov_end, b"mvhd")?;
+
+    file.seek(SeekFrom::Start(mvhd_start)).ok()?;
+    let body = read_vec(file, (mvhd_end - mvhd_start).min(32))?;
+    let (timescale, duration) = match body.first()? {
+        0 if body.len() >= 20 => (be_uint(&body[12..16]), be_uint(&body[16..20])),
+        1 if body.len() >= 32 => (be_uint(&body[20..24]), be_uint(&body[24..32])),
+        _ => return None,
+    };
+
+    (timescale > 0).then(|| duration as f64 / timescale as f64)
+}
+
+/// Returns the body range of the first box of type `kind` between `start`
+/// and `end`
+pub(crate) fn find_box(
+    file: &mut File,
+    start: u64,
+    end: u64,
+    kind: &[u8; 4],
+) -> Option<(u64, u64)> {
+    let mut pos = start;
+
+    for _ in 0..MAX_STEPS {
+        if pos + 8 > end {
+            return None;
+        }
+        file.seek(SeekFrom::Start(pos)).ok()?;
+        let header: [u8; 8] = read_array(file)?;
+
+        let (body_start, size) = match be_uint(&header[..4]) {
+            // 64-bit size follows the type
+            1 => (pos + 16, be_uint(&read_array::<8>(file)?)),
+            // Box runs to the end of its parent
+            0 => (pos + 8, end - pos),
+            size => (pos + 8, size),
+        };
+        let box_end = pos.checked_add(size)?.min(end);
+        if box_end < body_start {
+            return None;
+        }
+
+        if &header[4..] == kind {
+            return Some((body_start, box_end));
+        }
+        pos = box_end;
+    }
+
+    None
+}
+
+// Matroska / WebM
+
+const EBML_HEADER: u64 = 0x1A45_DFA3;
+const SEGMENT: u64 = 0x1853_8067;
+const SEGMENT_INFO: u64 = 0x1549_A966;
+const TIMESTAMP_SCALE: u64 = 0x2A_D7B1;
+const DURATION: u64 = 0x4489;
+const CLUSTER: u64 = 0x1F43_B675;
+
+/// Default `TimestampScale`: one millisecond, in nanoseconds
+const DEFAULT_TIMESTAMP_SCALE: u64 = 1_000_000;
+
+/// Largest `Info` element read into memory; real ones are well under a
+/// kilobyte, so a bigger one is stepped over
+const MAX_SEGMENT_INFO_LEN: u64 = 64 * 1024;
+
+/// Reads `Segment/Info/Duration`, which is in `TimestampScale` units
+fn matroska_duration(file: &mut File) -> Option<f64> {
+    let (id, size) = read_element_header(file)?;
+    if id != EBML_HEADER {
+        return None;
+    }
+    file.seek(SeekFrom::Current(i64::try_from(size?).ok()?))
+        .ok()?;
+
+    // The segment's size may be unknown; its children are read in place
+    let (id, _) = read_element_header(file)?;
+    if id != SEGMENT {
+        return None;
+    }
+
+    for _ in 0..MAX_STEPS {
+        let (id, size) = read_element_header(file)?;
+        match id {
+            SEGMENT_INFO if size? <= MAX_SEGMENT_INFO_LEN => return read_segment_info(file, size?),
+            // Info always precedes the media data
+            CLUSTER => return None,
+            _ => {
+                file.seek(SeekFrom::Current(i64::try_from(size?).ok()?))
+                    .ok()?;
+            }
+        }
+    }
+
+    None
+}
+
+fn read_segment_info(file: &mut File, size: u64) -> Option<f64> {
+    let body = read_vec(file, size)?;
+    let mut scale = DEFAULT_TIMESTAMP_SCALE;
+    let mut duration = None;
+
+    let mut pos = 0;
+    while pos < body.len() {
+        let (Some(id), id_len) = read_vint(&body[pos..], false)? else {
+            return None;
+        };
+        let (len, len_len) = read_vint(&body[pos + id_len..], true)?;
+        let start = pos + id_len + len_len;
+        let end = start.checked_add(usize::try_from(len?).ok()?)?;
+        let value = body.get(start..end)?;
+
+        match id {
+            TIMESTAMP_SCALE => scale = be_uint(value),
+            DURATION => {
+                duration = match value.len() {
+                    4 => Some(f32::from_be_bytes(value.try_into().ok()?) as f64),
+                    8 => Some(f64::from_be_bytes(value.try_into().ok()?)),
+                    _ => None,
+                }
+            }
+            _ => {}
+        }
+        pos = end;
+    }
+
+    duration.map(|d| d * scale as f64 / 1e9)
+}
+
+/// Reads an element ID and its data size; a size of `None` means unknown
+fn read_element_header(file: &mut File) -> Option<(u64, Option<u64>)> {
+    let mut buf = [0u8; 16];
+    let first: [u8; 1] = read_array(file)?;
+    let id_len = first[0].leading_zeros() as usize + 1;
+    if id_len > 4 {
+        return None;
+    }
+    buf[0] = first[0];
+    file.read_exact(&mut buf[1..id_len]).ok()?;
+
+    let size_first: [u8; 1] = read_array(file)?;
+    let size_len = size_first[0].leading_zeros() as usize + 1;
+    if size_len > 8 {
+        return None;
+    }
+    buf[id_len] = size_first[0];
+    file.read_exact(&mut buf[id_len + 1..id_len + size_len])
+        .ok()?;
+
+    let (id, _) = read_vint(&buf, false)?;
+    let (size, _) = read_vint(&buf[id_len..], true)?;
+    Some((id?, size))
+}
+
+/// Decodes an EBML variable-length integer, returning it with its length.
+/// IDs keep their length marker; sizes drop it, and an all-ones size is
+/// unknown.
+fn read_vint(bytes: &[u8], strip_marker: bool) -> Option<(Option<u64>, usize)> {
+    let first = *bytes.first()?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 || bytes.len() < len {
+        return None;
+    }
+
+    let mut value = be_uint(&bytes[..len]);
+    if strip_marker {
+        let bits = 7 * len as u32;
+        value &= (1u64 << bits) - 1;
+        if value == (1u64 << bits) - 1 {
+            return Some((None, len));
+        }
+    }
+
+    Some((Some(value), len))
+}
+
+// FLAC
+
+/// Total samples over sample rate, both from the `STREAMINFO` block
+fn flac_duration(file: &mut File) -> Option<f64> {
+    let header: [u8; 8] = read_array(file)?;
+    // `STREAMINFO` is always the first metadata block
+    if &header[..4] != b"fLaC" || header[4] & 0x7F != 0 {
+        return None;
+    }
+
+    let info: [u8; 18] = read_array(file)?;
+    let sample_rate = be_uint(&info[10..13]) >> 4;
+    let total_samples = (be_uint(&info[13..14]) & 0x0F) << 32 | be_uint(&info[14..18]);
+
+    (sample_rate > 0 && total_samples > 0).then(|| total_samples as f64 / sample_rate as f64)
+}
+
+// MP3
+
+/// How far past the ID3 tag to look for the first frame
+const MP3_SYNC_SEARCH: u64 = 64 * 1024;
+
+/// Uses the frame count from a Xing/Info or VBRI header when there is one,
+/// and otherwise assumes a constant bitrate
+fn mp3_duration(file: &mut File) -> Option<f64> {
+    let file_len = file.metadata().ok()?.len();
+
+    let head: [u8; 10] = read_array(file)?;
+    let mut audio_start = 0;
+    if &head[..3] == b"ID3" {
+        let tag_size = head[6..10]
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 7) | (b & 0x7F) as u64);
+        let footer = if head[5] & 0x10 != 0 { 10 } else { 0 };
+        audio_start = 10 + tag_size + footer;
+    }
+
+    file.seek(SeekFrom::Start(audio_start)).ok()?;
+    let window = read_vec(file, MP3_SYNC_SEARCH)?;
+    let (offset, frame) = (0..window.len().saturating_sub(4))
+        .find_map(|i| Mp3Frame::parse(&window[i..i + 4]).map(|f| (i, f)))?;
+    let frame_bytes = &window[offset..];
+
+    if let Some(frames) = vbr_frame_count(frame_bytes, &frame) {
+        return Some(frames as f64 * frame.samples as f64 / frame.sample_rate as f64);
+    }
+
+    let audio_len = file_len.checked_sub(audio_start + offset as u64)?;
+    Some(audio_len as f64 * 8.0 / (frame.bitrate_kbps as f64 * 1000.0))
+}
+
+struct Mp3Frame {
+    mpeg1: bool,
+    mono: bool,
+    bitrate_kbps: u32,
+    sample_rate: u32,
+    samples: u32,
+}
+
+const BITRATES_V1: [[u32; 15]; 3] = [
+    [
+        0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+    ],
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+    ],
+    [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ],
+];
+const BITRATES_V2: [[u32; 15]; 2] = [
+    [
+        0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+    ],
+    [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+
+impl Mp3Frame {
+    fn parse(header: &[u8]) -> Option<Self> {
+        if header[0] != 0xFF || header[1] & 0xE0 != 0xE0 {
+            return None;
+        }
+
+        let version = (header[1] >> 3) & 0x03;
+        let layer = (header[1] >> 1) & 0x03;
+        let bitrate_index = (header[2] >> 4) as usize;
+        let rate_index = ((header[2] >> 2) & 0x03) as usize;
+        // 1 is reserved; 0 and 15 are free-format and invalid bitrates
+        if version == 1 || layer == 0 || bitrate_index == 0 || bitrate_index == 15 {
+            return None;
+        }
+
+        let base_rate = [44100, 48000, 32000].get(rate_index).copied()?;
+        let mpeg1 = version == 3;
+        // Layer bits count down: 3 is layer I, 1 is layer III
+        let layer_number = 4 - layer as usize;
+
+        let bitrate_kbps = if mpeg1 {
+            BITRATES_V1[layer_number - 1][bitrate_index]
+        } else {
+            BITRATES_V2[usize::from(layer_number > 1)][bitrate_index]
+        };
+        let sample_rate = match version {
+            3 => base_rate,
+            2 => base_rate / 2,
+            _ => base_rate / 4,
+        };
+        let samples = match layer_number {
+            1 => 384,
+            2 => 1152,
+            _ if mpeg1 => 1152,
+            _ => 576,
+        };
+
+        Some(Self {
+            mpeg1,
+            mono: header[3] >> 6 == 3,
+            bitrate_kbps,
+            sample_rate,
+            samples,
+        })
+    }
+}
+
+/// Frame count from the Xing/Info header, which sits after the side info, or
+/// from the VBRI header, at a fixed offset
+fn vbr_frame_count(frame: &[u8], header: &Mp3Frame) -> Option<u64> {
+    let side_info = match (header.mpeg1, header.mono) {
+        (true, false) => 32,
+        (true, true) | (false, false) => 17,
+        (false, true) => 9,
+    };
+
+    let xing = frame.get(4 + side_info..4 + side_info + 12)?;
+    if &xing[..4] == b"Xing" || &xing[..4] == b"Info" {
+        let flags = be_uint(&xing[4..8]);
+        return (flags & 0x01 != 0).then(|| be_uint(&xing[8..12]));
+    }
+
+    let vbri = frame.get(36..36 + 18)?;
+    (&vbri[..4] == b"VBRI").then(|| be_uint(&vbri[14..18]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opens `bytes` as a file; the path is removed right away
+    fn open(name: &str, bytes: &[u8]) -> File {
+        let path =
+            std::env::temp_dir().join(format!("fileog-media-{}-{}", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let file = File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        file
+    }
+
+    fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn ebml(id: &[u8], size: &[u8], body: &[u8]) -> Vec<u8> {
+        [id, size, body].concat()
+    }
+
+    #[test]
+    fn test_find_box_steps_over_siblings() {
+        let bytes = [mp4_box(b"ftyp", &[0; 12]), mp4_box(b"moov", &[1; 5])].concat();
+        let mut file = open("siblings", &bytes);
+        let len = bytes.len() as u64;
+
+        assert_eq!(find_box(&mut file, 0, len, b"moov"), Some((28, 33)));
+        assert_eq!(find_box(&mut file, 0, len, b"mdat"), None);
+    }
+
+    #[test]
+    fn test_find_box_size_zero_runs_to_parent_end() {
+        let bytes = [
+            mp4_box(b"free", &[]),
+            vec![0, 0, 0, 0],
+            b"moov".to_vec(),
+            vec![0; 10],
+        ]
+        .concat();
+        let mut file = open("size0", &bytes);
+
+        assert_eq!(
+            find_box(&mut file, 0, bytes.len() as u64, b"moov"),
+            Some((16, 26))
+        );
+    }
+
+    #[test]
+    fn test_find_box_size_one_reads_64_bit_size() {
+        let mut bytes = vec![0, 0, 0, 1];
+        bytes.extend_from_slice(b"mdat");
+        bytes.extend_from_slice(&20u64.to_be_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend(mp4_box(b"moov", &[0; 3]));
+        let mut file = open("size1", &bytes);
+        let len = bytes.len() as u64;
+
+        assert_eq!(find_box(&mut file, 0, len, b"mdat"), Some((16, 20)));
+        assert_eq!(find_box(&mut file, 0, len, b"moov"), Some((28, 31)));
+    }
+
+    #[test]
+    fn test_find_box_rejects_size_below_header() {
+        let mut bytes = vec![0, 0, 0, 4];
+        bytes.extend_from_slice(b"free");
+        bytes.extend(mp4_box(b"moov", &[]));
+        let mut file = open("size4", &bytes);
+
+        assert_eq!(find_box(&mut file, 0, bytes.len() as u64, b"moov"), None);
+        // A header that doesn't fit in the range
+        assert_eq!(find_box(&mut file, 0, 7, b"free"), None);
+    }
+
+    #[test]
+    fn test_mp4_duration_mvhd_v0() {
+        let mut mvhd = vec![0; 20];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&90_500u32.to_be_bytes());
+        let bytes = [
+            mp4_box(b"ftyp", &[0; 8]),
+            mp4_box(b"moov", &mp4_box(b"mvhd", &[mvhd, vec![0; 80]].concat())),
+        ]
+        .concat();
+
+        assert_eq!(mp4_duration(&mut open("mvhd0", &bytes)), Some(90.5));
+    }
+
+    #[test]
+    fn test_mp4_duration_mvhd_v1() {
+        let mut mvhd = vec![0; 32];
+        mvhd[0] = 1;
+        mvhd[20..24].copy_from_slice(&600u32.to_be_bytes());
+        mvhd[24..32].copy_from_slice(&(600u64 * 7_200_000).to_be_bytes());
+        let bytes = mp4_box(
+            b"moov",
+            &[mp4_box(b"trak", &[0; 4]), mp4_box(b"mvhd", &mvhd)].concat(),
+        );
+
+        assert_eq!(mp4_duration(&mut open("mvhd1", &bytes)), Some(7_200_000.0));
+    }
+
+    #[test]
+    fn test_mp4_duration_rejects_bad_mvhd() {
+        // Zero timescale
+        let bytes = mp4_box(b"moov", &mp4_box(b"mvhd", &[0; 20]));
+        assert_eq!(mp4_duration(&mut open("mvhd-scale", &bytes)), None);
+
+        // Version 1 body cut short
+        let mut mvhd = vec![0; 24];
+        mvhd[0] = 1;
+        let bytes = mp4_box(b"moov", &mp4_box(b"mvhd", &mvhd));
+        assert_eq!(mp4_duration(&mut open("mvhd-short", &bytes)), None);
+
+        assert_eq!(
+            mp4_duration(&mut open("mvhd-none", &mp4_box(b"moov", &[]))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_read_vint() {
+        assert_eq!(read_vint(&[0x81], true), Some((Some(1), 1)));
+        assert_eq!(read_vint(&[0x40, 0x02], true), Some((Some(2), 2)));
+        assert_eq!(
+            read_vint(&[0x1A, 0x45, 0xDF, 0xA3], false),
+            Some((Some(EBML_HEADER), 4))
+        );
+        assert_eq!(read_vint(&[0x44, 0x89], false), Some((Some(DURATION), 2)));
+
+        // All ones after the marker is an unknown size, but only for sizes
+        assert_eq!(read_vint(&[0xFF], true), Some((None, 1)));
+        let unknown = [0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+        assert_eq!(read_vint(&unknown, true), Some((None, 8)));
+        assert_eq!(read_vint(&[0xFF], false), Some((Some(0xFF), 1)));
+
+        // No marker in the first byte, or fewer bytes than the marker asks for
+        assert_eq!(read_vint(&[0x00, 0x81], true), None);
+        assert_eq!(read_vint(&[0x20, 0x00], true), None);
+        assert_eq!(read_vint(&[], true), None);
+    }
+
+    fn matroska(segment_size: &[u8], children: &[u8]) -> Vec<u8> {
+        [
+            ebml(&[0x1A, 0x45, 0xDF, 0xA3], &[0x84], b"webm"),
+            ebml(&[0x18, 0x53, 0x80, 0x67], segment_size, children),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_matroska_duration_unknown_size_segment() {
+        let info = [
+            ebml(&[0x2A, 0xD7, 0xB1], &[0x83], &[0x0F, 0x42, 0x40]),
+            ebml(&[0x44, 0x89], &[0x88], &12_345.0f64.to_be_bytes()),
+        ]
+        .concat();
+        let children = [
+            // Void element ahead of Info
+            ebml(&[0xEC], &[0x82], &[0, 0]),
+            ebml(&[0x15, 0x49, 0xA9, 0x66], &[0x80 | info.len() as u8], &info),
+        ]
+        .concat();
+        let unknown = [0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+        let bytes = matroska(&unknown, &children);
+
+        assert_eq!(
+            matroska_duration(&mut open("mkv-unknown", &bytes)),
+            Some(12.345)
+        );
+    }
+
+    #[test]
+    fn test_matroska_duration_float32_and_scale() {
+        // Two-byte size, a 10 ms timestamp scale and a 32-bit duration
+        let info = [
+            ebml(&[0x2A, 0xD7, 0xB1], &[0x83], &[0x98, 0x96, 0x80]),
+            ebml(&[0x44, 0x89], &[0x84], &250.0f32.to_be_bytes()),
+        ]
+        .concat();
+        let children = ebml(&[0x15, 0x49, 0xA9, 0x66], &[0x40, info.len() as u8], &info);
+        let bytes = matroska(&[0x80 | children.len() as u8], &children);
+
+        assert_eq!(matroska_duration(&mut open("mkv-f32", &bytes)), Some(2.5));
+    }
+
+    #[test]
+    fn test_matroska_duration_stops_at_cluster() {
+        let children = [
+            ebml(&[0x1F, 0x43, 0xB6, 0x75], &[0x81], &[0]),
+            ebml(&[0x15, 0x49, 0xA9, 0x66], &[0x80], &[]),
+        ]
+        .concat();
+        let bytes = matroska(&[0xFF], &children);
+        assert_eq!(matroska_duration(&mut open("mkv-cluster", &bytes)), None);
+
+        // Not an EBML file, or cut off inside the header
+        assert_eq!(matroska_duration(&mut open("mkv-bad", b"RIFF....")), None);
+        assert_eq!(matroska_duration(&mut open("mkv-cut", &bytes[..6])), None);
+    }
+
+    #[test]
+    fn test_matroska_duration_steps_over_oversized_info() {
+        let duration = ebml(&[0x44, 0x89], &[0x88], &7_000.0f64.to_be_bytes());
+        let len = MAX_SEGMENT_INFO_LEN + 1;
+        let oversized = [
+            // An eight-byte size: the marker, then seven bytes of the value
+            &[0x15, 0x49, 0xA9, 0x66, 0x01][..],
+            &len.to_be_bytes()[1..],
+            &vec![0; len as usize],
+        ]
+        .concat();
+        let children = [
+            oversized,
+            ebml(
+                &[0x15, 0x49, 0xA9, 0x66],
+                &[0x80 | duration.len() as u8],
+                &duration,
+            ),
+        ]
+        .concat();
+        let bytes = matroska(&[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF], &children);
+
+        assert_eq!(
+            matroska_duration(&mut open("mkv-oversized", &bytes)),
+            Some(7.0)
+        );
+    }
+
+    fn flac(sample_rate: u64, total_samples: u64) -> Vec<u8> {
+        // Two channels at 16 bits, stored as one less
+        let packed = sample_rate << 44 | 1 << 41 | 15 << 36 | total_samples;
+        let mut info = vec![0; 34];
+        info[10..18].copy_from_slice(&packed.to_be_bytes());
+        [b"fLaC".as_slice(), &[0x80, 0, 0, 34], &info].concat()
+    }
+
+    #[test]
+    fn test_flac_duration_streaminfo_bits() {
+        let bytes = flac(44_100, 44_100 * 90);
+        assert_eq!(flac_duration(&mut open("flac", &bytes)), Some(90.0));
+
+        // Total samples past 32 bits use the low nibble of the shared byte
+        let bytes = flac(96_000, 96_000 * 50_000);
+        assert!(96_000u64 * 50_000 > u32::MAX as u64);
+        assert_eq!(
+            flac_duration(&mut open("flac-36bit", &bytes)),
+            Some(50_000.0)
+        );
+    }
+
+    #[test]
+    fn test_flac_duration_rejects_bad_input() {
+        let mut bytes = flac(44_100, 0);
+        assert_eq!(flac_duration(&mut open("flac-unknown", &bytes)), None);
+
+        // First block isn't STREAMINFO
+        bytes = flac(44_100, 100);
+        bytes[4] = 0x84;
+        assert_eq!(flac_duration(&mut open("flac-block", &bytes)), None);
+
+        assert_eq!(
+            flac_duration(&mut open("flac-cut", &flac(44_100, 100)[..20])),
+            None
+        );
+    }
+
+    /// MPEG-1 layer III, 128 kbit/s, 44.1 kHz, stereo
+    const MPEG1_FRAME: [u8; 4] = [0xFF, 0xFB, 0x90, 0x00];
+
+    fn mp3(id3_size: u8, frame: &[u8; 4], at: usize, tag: &[u8], len: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        if id3_size > 0 {
+            bytes.extend_from_slice(b"ID3\x04\x00\x00\x00\x00\x00");
+            bytes.push(id3_size);
+            bytes.resize(10 + id3_size as usize, 0);
+        }
+        let mut audio = vec![0; len];
+        audio[..4].copy_from_slice(frame);
+        audio[at..at + tag.len()].copy_from_slice(tag);
+        bytes.extend(audio);
+        bytes
+    }
+
+    #[test]
+    fn test_mp3_duration_constant_bitrate() {
+        // 16000 bytes a second after a 20-byte ID3 tag
+        let bytes = mp3(20, &MPEG1_FRAME, 0, &[], 48_000);
+        assert_eq!(mp3_duration(&mut open("mp3-cbr", &bytes)), Some(3.0));
+    }
+
+    #[test]
+    fn test_mp3_duration_xing() {
+        let xing = [
+            b"Xing".as_slice(),
+            &1u32.to_be_bytes(),
+            &3_828u32.to_be_bytes(),
+        ]
+        .concat();
+        let bytes = mp3(0, &MPEG1_FRAME, 36, &xing, 1000);
+        let seconds = mp3_duration(&mut open("mp3-xing", &bytes)).unwrap();
+        assert!((seconds - 3_828.0 * 1152.0 / 44_100.0).abs() < 1e-9);
+
+        // Without the frames flag the bitrate decides
+        let info = [
+            b"Info".as_slice(),
+            &0u32.to_be_bytes(),
+            &3_828u32.to_be_bytes(),
+        ]
+        .concat();
+        let bytes = mp3(0, &MPEG1_FRAME, 36, &info, 32_000);
+        assert_eq!(mp3_duration(&mut open("mp3-info", &bytes)), Some(2.0));
+    }
+
+    #[test]
+    fn test_mp3_duration_info_mpeg2_mono() {
+        // MPEG-2 layer III, 40 kbit/s, 22.05 kHz, mono: 9 bytes of side info
+        let frame = [0xFF, 0xF3, 0x50, 0xC0];
+        let info = [
+            b"Info".as_slice(),
+            &1u32.to_be_bytes(),
+            &3_675u32.to_be_bytes(),
+        ]
+        .concat();
+        let bytes = mp3(0, &frame, 13, &info, 500);
+        assert_eq!(mp3_duration(&mut open("mp3-mono", &bytes)), Some(96.0));
+    }
+
+    #[test]
+    fn test_mp3_duration_vbri() {
+        let mut vbri = b"VBRI".to_vec();
+        vbri.resize(14, 0);
+        vbri.extend_from_slice(&1_225u32.to_be_bytes());
+        let bytes = mp3(0, &MPEG1_FRAME, 36, &vbri, 1000);
+        assert_eq!(mp3_duration(&mut open("mp3-vbri", &bytes)), Some(32.0));
+    }
+
+    #[test]
+    fn test_mp3_duration_without_frame() {
+        assert_eq!(mp3_duration(&mut open("mp3-empty", &[0; 200])), None);
+        // Reserved version and free-format bitrate
+        assert_eq!(
+            mp3_duration(&mut open("mp3-reserved", &[0xFF, 0xEB, 0x90, 0, 0])),
+            None
+        );
+        assert_eq!(
+            mp3_duration(&mut open("mp3-free", &[0xFF, 0xFB, 0x00, 0, 0])),
+            None
+        );
+        assert_eq!(mp3_duration(&mut open("mp3-short", b"ID3")), None);
+    }
+}
@@ -0,0 +1,107 @@
+//! Fills in `FileMetadata` from a file's content: the MIME type from its
+//! magic bytes, image dimensions and EXIF data, media duration, music tags,
+//! document properties, archive contents and a text preview.
+
+use crate::models::FileMetadata;
+use crate::services::{archive, audio_tags, document, media, photo};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes read up front for sniffing; enough for every magic number `infer`
+/// knows and for the preview
+const HEADER_LEN: usize = 8192;
+
+/// Longest preview, in characters
+const PREVIEW_CHARS: usize = 500;
+
+/// Reads the first bytes of a file, which both type detection and metadata
+/// extraction work from
+pub fn read_header(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    File::open(path)?
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok(header)
+}
+
+/// Reads what it can from the file at `path`, given its `header`. Extraction
+/// is best effort: anything that can't be parsed is left empty.
+pub fn extract(path: &Path, header: &[u8]) -> FileMetadata {
+    let mut metadata = FileMetadata::default();
+    if header.is_empty() {
+        return metadata;
+    }
+
+    if let Some(kind) = infer::get(header) {
+        let mime = kind.mime_type();
+        if kind.matcher_type() == infer::MatcherType::Image {
+            metadata.dimensions = imagesize::size(path)
+                .ok()
+                .map(|size| (size.width as u32, size.height as u32));
+            metadata.photo = photo::read(path);
+        }
+        if mime.starts_with("text/") || mime.ends_with("xml") {
+            metadata.preview_text = decode_text(header).map(preview);
+        }
+        metadata.duration = media::duration(path, mime);
+        metadata.audio = audio_tags::read(path, mime);
+        if let Some(document) = document::read(path, mime, PREVIEW_CHARS) {
+            metadata.preview_text = Some(document.text).filter(|t| !t.is_empty());
+            metadata.document = Some(document.metadata);
+        } else if let Some(inspection) = archive::inspect(path, mime, PREVIEW_CHARS) {
+            metadata.preview_text = Some(inspection.listing).filter(|l| !l.is_empty());
+            metadata.archive = Some(inspection.summary);
+        }
+        metadata.mime_type = Some(mime.to_string());
+    } else if let Some(text) = decode_text(header) {
+        metadata.mime_type = Some(text_mime(path).to_string());
+        metadata.preview_text = Some(preview(text));
+    }
+
+    metadata
+}
+
+/// The header as text, if it looks like UTF-8 text. A character cut off by
+/// the end of the header doesn't count against it.
+fn decode_text(header: &[u8]) -> Option<&str> {
+    let bytes = header.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(header);
+    if bytes.contains(&0) {
+        return None;
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Some(text),
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).ok(),
+        Err(_) => None,
+    }
+}
+
+fn preview(text: &str) -> String {
+    text.chars()
+        .take(PREVIEW_CHARS)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+/// Text formats can't be told apart by content, so the extension decides
+fn text_mime(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "md" => "text/markdown",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "yaml" | "yml" => "application/yaml",
+        _ => "text/plain",
+    }
+}
@@ -1,3 +1,15 @@
+pub mod archive;
+pub mod audio_tags;
+pub mod document;
+pub mod duplicates;
+pub mod file_type;
+pub mod history_export;
 pub mod llm;
+pub mod media;
+pub mod metadata;
+pub mod photo;
+pub mod scanner;
+pub mod template;
+pub mod trash;
 
 pub use llm::{ClassificationResult, LlmService};
@@ -0,0 +1,275 @@
+//! Reads capture date, camera, GPS position and orientation from a photo's
+//! EXIF data. JPEG, HEIC, TIFF and TIFF-based RAW formats (CR2, NEF, ARW,
+//! DNG...) are supported.
+
+use crate::models::{GpsPosition, PhotoMetadata};
+use chrono::NaiveDateTime;
+use exif::{Exif, In, Tag, Value};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// `None` if the file has no readable EXIF data
+pub fn read(path: &Path) -> Option<PhotoMetadata> {
+    let file = File::open(path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+
+    let photo = PhotoMetadata {
+        taken_at: taken_at(&exif),
+        camera_make: text(&exif, Tag::Make),
+        camera_model: text(&exif, Tag::Model),
+        gps: gps(&exif),
+        orientation: field(&exif, Tag::Orientation)
+            .and_then(|v| v.get_uint(0))
+            .and_then(|o| u16::try_from(o).ok())
+            .filter(|o| (1..=8).contains(o)),
+    };
+
+    let empty = photo.taken_at.is_none()
+        && photo.camera_make.is_none()
+        && photo.camera_model.is_none()
+        && photo.gps.is_none()
+        && photo.orientation.is_none();
+    (!empty).then_some(photo)
+}
+
+fn field(exif: &Exif, tag: Tag) -> Option<&Value> {
+    exif.get_field(tag, In::PRIMARY).map(|f| &f.value)
+}
+
+/// An ASCII field, trimmed of the padding cameras leave in it
+fn text(exif: &Exif, tag: Tag) -> Option<String> {
+    let Value::Ascii(parts) = field(exif, tag)? else {
+        return None;
+    };
+
+    let text = String::from_utf8_lossy(parts.first()?);
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// The original capture time, falling back to digitization and then to the
+/// file's own modification time as the camera recorded it
+fn taken_at(exif: &Exif) -> Option<String> {
+    [Tag::DateTimeOriginal, Tag::DateTimeDigitized, Tag::DateTime]
+        .into_iter()
+        .find_map(|tag| {
+            let value = text(exif, tag)?;
+            NaiveDateTime::parse_from_str(&value, "%Y:%m:%d %H:%M:%S").ok()
+        })
+        .map(|time| time.format("%Y-%m-%dT%H:%M:%S").to_string())
+}
+
+fn gps(exif: &Exif) -> Option<GpsPosition> {
+    let latitude = degrees(exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S')?;
+    let longitude = degrees(exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W')?;
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return None;
+    }
+
+    let altitude = match field(exif, Tag::GPSAltitude) {
+        Some(Value::Rational(v)) if !v.is_empty() && v[0].denom != 0 => {
+            // A reference of 1 means below sea level
+            let below = field(exif, Tag::GPSAltitudeRef).and_then(|r| r.get_uint(0)) == Some(1);
+            let meters = v[0].to_f64();
+            Some(if below { -meters } else { meters })
+        }
+        _ => None,
+    };
+
+    Some(GpsPosition {
+        latitude,
+        longitude,
+        altitude,
+    })
+}
+
+/// Degrees, minutes and seconds to signed decimal degrees
+fn degrees(exif: &Exif, tag: Tag, reference: Tag, negative: u8) -> Option<f64> {
+    let Value::Rational(parts) = field(exif, tag)? else {
+        return None;
+    };
+    if parts.len() < 3 || parts.iter().take(3).any(|p| p.denom == 0) {
+        return None;
+    }
+
+    let value = parts[0].to_f64() + parts[1].to_f64() / 60.0 + parts[2].to_f64() / 3600.0;
+    let sign = match field(exif, reference) {
+        Some(Value::Ascii(r)) if r.first().and_then(|r| r.first()) == Some(&negative) => -1.0,
+        _ => 1.0,
+    };
+
+    Some(sign * value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::{Field, Rational};
+    use std::path::PathBuf;
+
+    fn field(tag: Tag, value: Value) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        }
+    }
+
+    fn ascii(text: &str) -> Value {
+        Value::Ascii(vec![text.as_bytes().to_vec()])
+    }
+
+    fn dms(degrees: u32, minutes: u32, hundredths_of_seconds: u32) -> Value {
+        Value::Rational(vec![
+            Rational::from((degrees, 1)),
+            Rational::from((minutes, 1)),
+            Rational::from((hundredths_of_seconds, 100)),
+        ])
+    }
+
+    /// Writes `fields` as a bare TIFF file
+    fn tiff(name: &str, fields: &[Field]) -> PathBuf {
+        let mut writer = exif::experimental::Writer::new();
+        for field in fields {
+            writer.push_field(field);
+        }
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        writer.write(&mut bytes, false).unwrap();
+
+        let path =
+            std::env::temp_dir().join(format!("fileog-photo-{}-{}.tif", name, std::process::id()));
+        std::fs::write(&path, bytes.into_inner()).unwrap();
+        path
+    }
+
+    fn photo(name: &str, fields: &[Field]) -> PhotoMetadata {
+        read(&tiff(name, fields)).unwrap()
+    }
+
+    #[test]
+    fn test_taken_at_prefers_original() {
+        let photo = photo(
+            "original",
+            &[
+                field(Tag::DateTime, ascii("2024:05:01 10:00:00")),
+                field(Tag::DateTimeOriginal, ascii("2023:12:31 23:59:58")),
+            ],
+        );
+        assert_eq!(photo.taken_at.as_deref(), Some("2023-12-31T23:59:58"));
+    }
+
+    #[test]
+    fn test_taken_at_falls_back_to_date_time() {
+        let fallback = photo(
+            "fallback",
+            &[field(Tag::DateTime, ascii("2024:05:01 10:00:00"))],
+        );
+        assert_eq!(fallback.taken_at.as_deref(), Some("2024-05-01T10:00:00"));
+
+        // Cameras with an unset clock write blanks or zeros
+        let unset = photo(
+            "unset",
+            &[
+                field(Tag::DateTimeOriginal, ascii("    :  :     :  :  ")),
+                field(Tag::DateTime, ascii("2024:05:01 10:00:00")),
+            ],
+        );
+        assert_eq!(unset.taken_at.as_deref(), Some("2024-05-01T10:00:00"));
+    }
+
+    #[test]
+    fn test_camera_text_is_trimmed() {
+        let photo = photo(
+            "camera",
+            &[
+                field(Tag::Make, ascii("Canon  \0\0")),
+                field(Tag::Model, ascii("   ")),
+            ],
+        );
+        assert_eq!(photo.camera_make.as_deref(), Some("Canon"));
+        assert!(photo.camera_model.is_none());
+    }
+
+    #[test]
+    fn test_gps_north_east() {
+        let photo = photo(
+            "gps-ne",
+            &[
+                field(Tag::GPSLatitudeRef, ascii("N")),
+                field(Tag::GPSLatitude, dms(48, 51, 2400)),
+                field(Tag::GPSLongitudeRef, ascii("E")),
+                field(Tag::GPSLongitude, dms(2, 21, 0)),
+                field(Tag::GPSAltitude, Value::Rational(vec![(355, 10).into()])),
+            ],
+        );
+        let gps = photo.gps.unwrap();
+        assert!((gps.latitude - 48.856_667).abs() < 1e-6);
+        assert!((gps.longitude - 2.35).abs() < 1e-9);
+        assert_eq!(gps.altitude, Some(35.5));
+    }
+
+    #[test]
+    fn test_gps_south_west_below_sea_level() {
+        let photo = photo(
+            "gps-sw",
+            &[
+                field(Tag::GPSLatitudeRef, ascii("S")),
+                field(Tag::GPSLatitude, dms(33, 52, 0)),
+                field(Tag::GPSLongitudeRef, ascii("W")),
+                field(Tag::GPSLongitude, dms(70, 30, 0)),
+                field(Tag::GPSAltitudeRef, Value::Byte(vec![1])),
+                field(Tag::GPSAltitude, Value::Rational(vec![(28, 1).into()])),
+            ],
+        );
+        let gps = photo.gps.unwrap();
+        assert!((gps.latitude + 33.866_667).abs() < 1e-6);
+        assert!((gps.longitude + 70.5).abs() < 1e-9);
+        assert_eq!(gps.altitude, Some(-28.0));
+    }
+
+    #[test]
+    fn test_gps_rejects_bad_coordinates() {
+        let out_of_range = [
+            field(Tag::GPSLatitude, dms(91, 0, 0)),
+            field(Tag::GPSLongitude, dms(10, 0, 0)),
+            field(Tag::Make, ascii("Nikon")),
+        ];
+        assert!(photo("gps-range", &out_of_range).gps.is_none());
+
+        let zero_denominator = [
+            field(
+                Tag::GPSLatitude,
+                Value::Rational(vec![(10, 1).into(), (0, 0).into(), (0, 1).into()]),
+            ),
+            field(Tag::GPSLongitude, dms(10, 0, 0)),
+            field(Tag::Make, ascii("Nikon")),
+        ];
+        assert!(photo("gps-denom", &zero_denominator).gps.is_none());
+    }
+
+    #[test]
+    fn test_orientation() {
+        let rotated = photo(
+            "orientation",
+            &[field(Tag::Orientation, Value::Short(vec![6]))],
+        );
+        assert_eq!(rotated.orientation, Some(6));
+
+        let invalid = tiff(
+            "orientation-bad",
+            &[field(Tag::Orientation, Value::Short(vec![9]))],
+        );
+        assert!(read(&invalid).is_none());
+    }
+
+    #[test]
+    fn test_without_exif() {
+        let path =
+            std::env::temp_dir().join(format!("fileog-photo-none-{}.jpg", std::process::id()));
+        std::fs::write(&path, [0xFF, 0xD8, 0xFF, 0xD9]).unwrap();
+        assert!(read(&path).is_none());
+    }
+}
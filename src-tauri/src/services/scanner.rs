@@ -0,0 +1,761 @@
+//! Parallel directory walk behind `scan_directory`.
+
+use crate::error::AppError;
+use crate::models::{
+    FileItem, FileMetadata, FileType, ScanChanges, ScanError, ScanErrorKind, ScanFilters,
+    ScanOptions, ScanReport,
+};
+use crate::services::{file_type, metadata};
+use crate::storage::files::{FileStamp, IndexedFile};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{DirEntry, WalkBuilder, WalkState};
+use std::collections::{HashMap, HashSet};
+use std::fs::Metadata;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Per-folder ignore file honored by every scan, in `.gitignore` syntax
+const IGNORE_FILE_NAME: &str = ".fileogignore";
+
+/// What the walker threads hand back to the collecting thread
+enum Found {
+    File {
+        file: Box<IndexedFile>,
+        changed: bool,
+    },
+    Error(ScanError),
+}
+
+/// Progress reported to the caller, in the order things were found.
+/// `changed` is false for a file taken from the index as it was.
+pub enum ScanUpdate<'a> {
+    File {
+        count: usize,
+        file: &'a IndexedFile,
+        changed: bool,
+    },
+    Error(&'a ScanError),
+}
+
+/// A running scan: its ID, the flag `cancel_scan` sets, the files an
+/// earlier, interrupted run of it already found, and what the file index
+/// holds for the scanned folder
+pub struct ScanJob {
+    pub id: String,
+    pub cancelled: AtomicBool,
+    pub already_scanned: HashSet<PathBuf>,
+    pub index: HashMap<PathBuf, IndexedFile>,
+}
+
+impl ScanJob {
+    pub fn new(
+        id: String,
+        already_scanned: HashSet<PathBuf>,
+        index: HashMap<PathBuf, IndexedFile>,
+    ) -> Self {
+        Self {
+            id,
+            cancelled: AtomicBool::new(false),
+            already_scanned,
+            index,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Fails the way `scan` would before walking: on an unreadable
+/// `options.path` or an invalid include or exclude pattern
+pub fn check_options(options: &ScanOptions) -> Result<(), AppError> {
+    std::fs::metadata(&options.path)?;
+    EntryFilter::new(options)?;
+    Ok(())
+}
+
+/// Walks `options.path` on a pool of work-stealing threads. Results are
+/// funnelled back to the calling thread, which passes each one to
+/// `on_update` as it arrives, so progress is reported in order. Entries that
+/// can't be read end up in the report's errors instead of failing the scan;
+/// only an unreadable `options.path` does that.
+///
+/// Files in `job.already_scanned` are skipped without being read, as are
+/// files whose size, modification time and inode still match `job.index`;
+/// those are reported as the index has them. The walk stops early once the
+/// job is cancelled.
+pub fn scan(
+    options: &ScanOptions,
+    job: &ScanJob,
+    mut on_update: impl FnMut(ScanUpdate),
+) -> Result<ScanReport, AppError> {
+    std::fs::metadata(&options.path)?;
+
+    let filter = Arc::new(EntryFilter::new(options)?);
+    let respect_gitignore = options.filters.respect_gitignore;
+
+    let mut builder = WalkBuilder::new(&options.path);
+    builder
+        .standard_filters(false)
+        .git_ignore(respect_gitignore)
+        .git_exclude(respect_gitignore)
+        .require_git(false)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME)
+        .follow_links(options.follow_symlinks)
+        .same_file_system(options.same_file_system)
+        .threads(options.threads.unwrap_or(0))
+        .max_depth(if options.recursive {
+            options.max_depth
+        } else {
+            Some(1)
+        })
+        .filter_entry(move |entry| filter.keep(entry));
+    let walker = builder.build_parallel();
+
+    let (tx, rx) = mpsc::channel();
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            walker.run(|| {
+                let tx = tx.clone();
+                Box::new(move |entry| {
+                    if job.is_cancelled() {
+                        return WalkState::Quit;
+                    }
+
+                    let found = match entry {
+                        Ok(entry) => visit(&entry, options, job),
+                        Err(e) => Some(Found::Error(walk_error(&e))),
+                    };
+                    if let Some(found) = found {
+                        let _ = tx.send(found);
+                    }
+                    WalkState::Continue
+                })
+            });
+        });
+
+        for found in rx {
+            // Files the walkers found before stopping are left for a resume
+            if job.is_cancelled() {
+                break;
+            }
+            match found {
+                Found::File { file, changed } => {
+                    on_update(ScanUpdate::File {
+                        count: files.len() + 1,
+                        file: &file,
+                        changed,
+                    });
+                    files.push(file.item);
+                }
+                Found::Error(error) => {
+                    on_update(ScanUpdate::Error(&error));
+                    errors.push(error);
+                }
+            }
+        }
+    });
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    errors.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(ScanReport {
+        scan_id: job.id.clone(),
+        files,
+        errors,
+        cancelled: job.is_cancelled(),
+        changes: ScanChanges::default(),
+    })
+}
+
+/// Turns a walk entry into a `FileItem`, an error, or `None` for entries the
+/// scan skips
+fn visit(entry: &DirEntry, options: &ScanOptions, job: &ScanJob) -> Option<Found> {
+    let file_type = entry.file_type()?;
+
+    // Skip directories
+    if file_type.is_dir() {
+        return None;
+    }
+
+    // Found by the run being resumed
+    if job.already_scanned.contains(entry.path()) {
+        return None;
+    }
+
+    if options.skip_special_files && is_special(&file_type) {
+        return None;
+    }
+
+    // Skip hidden files if not included
+    if !options.include_hidden && entry.file_name().to_string_lossy().starts_with('.') {
+        return None;
+    }
+
+    // Links are only reported as links when they aren't being followed, so
+    // organizing moves the link and never its target. Broken links are
+    // reported as errors.
+    let is_symlink = file_type.is_symlink();
+    if is_symlink {
+        if let Err(e) = std::fs::metadata(entry.path()) {
+            return Some(Found::Error(io_error(Some(entry.path()), &e)));
+        }
+    }
+
+    // Reuse the metadata the walker already read
+    let metadata = match entry.metadata() {
+        Ok(metadata) => metadata,
+        Err(e) => return Some(Found::Error(entry_error(entry, &e))),
+    };
+    let stamp = FileStamp::of(&metadata);
+    let previous = job.index.get(entry.path());
+
+    // Unchanged since it was indexed, and read with at least these options
+    if let Some(previous) = previous.filter(|p| p.stamp == stamp && covers(p, options)) {
+        let file = reuse(previous, options);
+        if !selected(options, &file.item) {
+            return None;
+        }
+        return Some(Found::File {
+            file: Box::new(file),
+            changed: false,
+        });
+    }
+
+    let mut item = file_item(entry.path(), &metadata);
+    if is_symlink {
+        item.file_type = FileType::Symlink;
+        item.link_target = std::fs::read_link(entry.path()).ok();
+    }
+
+    // Keep the file's identity and classification, and its hash unless the
+    // content may have changed
+    if let Some(previous) = previous {
+        item.id = previous.item.id.clone();
+        item.category = previous.item.category.clone();
+        if previous.stamp == stamp {
+            item.hash = previous.item.hash.clone();
+        }
+    }
+
+    if !within_ranges(&options.filters, &item) {
+        return None;
+    }
+
+    // Runs on the walker threads, so files are read in parallel
+    if !is_symlink && (options.detect_file_types || options.extract_metadata) {
+        if let Ok(header) = metadata::read_header(entry.path()) {
+            if options.detect_file_types {
+                file_type::apply(&mut item, &header);
+            }
+            if options.extract_metadata {
+                item.metadata = metadata::extract(entry.path(), &header);
+            }
+        }
+    }
+
+    // Detected types are only known once the file has been read
+    if options.detect_file_types && !type_selected(&options.filters.file_types, &item.file_type) {
+        return None;
+    }
+
+    Some(Found::File {
+        file: Box::new(IndexedFile {
+            item,
+            stamp,
+            metadata_extracted: is_symlink || options.extract_metadata,
+            types_detected: is_symlink || options.detect_file_types,
+        }),
+        changed: true,
+    })
+}
+
+/// Whether an indexed file was read with everything `options` asks for
+fn covers(file: &IndexedFile, options: &ScanOptions) -> bool {
+    (file.metadata_extracted || !options.extract_metadata)
+        && (file.types_detected || !options.detect_file_types)
+}
+
+/// An unchanged file as the index has it, less anything `options` didn't ask
+/// for, so the scan's results don't depend on what earlier scans read
+fn reuse(previous: &IndexedFile, options: &ScanOptions) -> IndexedFile {
+    let mut file = previous.clone();
+    let item = &mut file.item;
+
+    if !options.detect_file_types && item.file_type != FileType::Symlink {
+        item.file_type = file_type_of(&item.path);
+        item.extension_mismatch = false;
+        item.suggested_extension = None;
+    }
+    if !options.extract_metadata {
+        item.metadata = FileMetadata::default();
+    }
+
+    file
+}
+
+/// The filters `EntryFilter` can't apply before the file has been read
+fn selected(options: &ScanOptions, item: &FileItem) -> bool {
+    within_ranges(&options.filters, item)
+        && (!options.detect_file_types
+            || type_selected(&options.filters.file_types, &item.file_type))
+}
+
+/// Sockets, FIFOs and device nodes
+#[cfg(unix)]
+fn is_special(file_type: &std::fs::FileType) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+
+    file_type.is_socket()
+        || file_type.is_fifo()
+        || file_type.is_block_device()
+        || file_type.is_char_device()
+}
+
+#[cfg(not(unix))]
+fn is_special(_file_type: &std::fs::FileType) -> bool {
+    false
+}
+
+/// The path-based filters, checked while walking so excluded folders are
+/// pruned instead of traversed
+struct EntryFilter {
+    root: PathBuf,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    file_types: Vec<FileType>,
+    /// Leave the type check on regular files to `visit`, after detection
+    detect_file_types: bool,
+}
+
+impl EntryFilter {
+    fn new(options: &ScanOptions) -> Result<Self, AppError> {
+        Ok(Self {
+            root: options.path.clone(),
+            include: build_globs(&options.filters.include)?,
+            exclude: build_globs(&options.filters.exclude)?,
+            file_types: options.filters.file_types.clone(),
+            detect_file_types: options.detect_file_types,
+        })
+    }
+
+    fn keep(&self, entry: &DirEntry) -> bool {
+        if entry.depth() == 0 {
+            return true;
+        }
+
+        let relative = entry
+            .path()
+            .strip_prefix(&self.root)
+            .unwrap_or(entry.path());
+
+        if self.exclude.as_ref().is_some_and(|g| g.is_match(relative)) {
+            return false;
+        }
+
+        // Include patterns and types pick files; folders are always entered
+        if entry.file_type().is_some_and(|t| t.is_dir()) {
+            return true;
+        }
+
+        if self.include.as_ref().is_some_and(|g| !g.is_match(relative)) {
+            return false;
+        }
+
+        let file_type = if entry.file_type().is_some_and(|t| t.is_symlink()) {
+            FileType::Symlink
+        } else if self.detect_file_types {
+            return true;
+        } else {
+            file_type_of(entry.path())
+        };
+
+        type_selected(&self.file_types, &file_type)
+    }
+}
+
+/// Compiles patterns with `.gitignore` anchoring: a leading or inner `/` ties
+/// the pattern to the scanned folder, otherwise it matches at any depth
+fn build_globs(patterns: &[String]) -> Result<Option<GlobSet>, AppError> {
+    let mut builder = GlobSetBuilder::new();
+    let mut count = 0;
+
+    for pattern in patterns {
+        let trimmed = pattern.trim().trim_end_matches('/');
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let anchored = match trimmed.strip_prefix('/') {
+            Some(rest) => rest.to_string(),
+            None if trimmed.contains('/') => trimmed.to_string(),
+            None => format!("**/{}", trimmed),
+        };
+
+        let glob = GlobBuilder::new(&anchored)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| AppError::Config(format!("Invalid glob pattern '{}': {}", pattern, e)))?;
+        builder.add(glob);
+        count += 1;
+    }
+
+    if count == 0 {
+        return Ok(None);
+    }
+
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| AppError::Config(e.to_string()))
+}
+
+/// Size and date filters, which need the file's metadata
+fn within_ranges(filters: &ScanFilters, item: &FileItem) -> bool {
+    in_range(item.size, filters.min_size, filters.max_size)
+        && in_range(item.modified_at, filters.modified_from, filters.modified_to)
+        && in_range(item.created_at, filters.created_from, filters.created_to)
+}
+
+fn type_selected(file_types: &[FileType], file_type: &FileType) -> bool {
+    file_types.is_empty() || file_types.contains(file_type)
+}
+
+fn in_range<T: PartialOrd>(value: T, from: Option<T>, to: Option<T>) -> bool {
+    from.is_none_or(|from| value >= from) && to.is_none_or(|to| value <= to)
+}
+
+fn file_type_of(path: &Path) -> FileType {
+    path.extension()
+        .map(|e| FileType::from_extension(&e.to_string_lossy()))
+        .unwrap_or(FileType::Other)
+}
+
+fn io_error(path: Option<&Path>, error: &std::io::Error) -> ScanError {
+    let kind = match error.kind() {
+        ErrorKind::PermissionDenied => ScanErrorKind::PermissionDenied,
+        ErrorKind::NotFound if path.is_some_and(|p| p.is_symlink()) => ScanErrorKind::BrokenSymlink,
+        ErrorKind::NotFound => ScanErrorKind::NotFound,
+        _ => ScanErrorKind::Other,
+    };
+
+    ScanError {
+        path: path.map(Path::to_path_buf),
+        kind,
+        message: error.to_string(),
+    }
+}
+
+fn entry_error(entry: &DirEntry, error: &ignore::Error) -> ScanError {
+    let mut scan_error = walk_error(error);
+    if scan_error.path.is_none() {
+        scan_error.path = Some(entry.path().to_path_buf());
+    }
+    scan_error
+}
+
+/// Unwraps the walker's error wrappers to find the path and the cause
+fn walk_error(error: &ignore::Error) -> ScanError {
+    let mut path = None;
+    let mut current = error;
+
+    loop {
+        match current {
+            ignore::Error::WithPath { path: p, err } => {
+                path.get_or_insert(p.as_path());
+                current = err;
+            }
+            ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+                current = err;
+            }
+            _ => break,
+        }
+    }
+
+    match current {
+        ignore::Error::Io(e) => io_error(path, e),
+        ignore::Error::Loop { child, .. } => ScanError {
+            path: Some(child.clone()),
+            kind: ScanErrorKind::SymlinkLoop,
+            message: error.to_string(),
+        },
+        _ => ScanError {
+            path: path.map(Path::to_path_buf),
+            kind: ScanErrorKind::Other,
+            message: error.to_string(),
+        },
+    }
+}
+
+fn unix_seconds(time: std::io::Result<SystemTime>) -> i64 {
+    time.ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn file_item(path: &Path, metadata: &Metadata) -> FileItem {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().to_string());
+
+    FileItem {
+        id: Uuid::new_v4().to_string(),
+        path: path.to_path_buf(),
+        name,
+        extension,
+        size: metadata.len(),
+        file_type: file_type_of(path),
+        hash: None,
+        created_at: unix_seconds(metadata.created()),
+        modified_at: unix_seconds(metadata.modified()),
+        category: None,
+        metadata: FileMetadata::default(),
+        link_target: None,
+        extension_mismatch: false,
+        suggested_extension: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("fileog-scanner-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn touch(root: &Path, relative: &str) {
+        let path = root.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, relative).unwrap();
+    }
+
+    fn options(path: &Path) -> ScanOptions {
+        ScanOptions {
+            path: path.to_path_buf(),
+            recursive: true,
+            include_hidden: false,
+            threads: None,
+            max_depth: None,
+            filters: ScanFilters::default(),
+            follow_symlinks: false,
+            same_file_system: false,
+            skip_special_files: true,
+            extract_metadata: false,
+            detect_file_types: false,
+        }
+    }
+
+    fn run(options: &ScanOptions) -> ScanReport {
+        let job = ScanJob::new("test".to_string(), HashSet::new(), HashMap::new());
+        scan(options, &job, |_| {}).unwrap()
+    }
+
+    /// Scanned paths relative to the scanned folder, sorted
+    fn found(root: &Path, report: &ScanReport) -> Vec<String> {
+        report
+            .files
+            .iter()
+            .map(|f| {
+                f.path
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    fn globs(patterns: &[&str]) -> GlobSet {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        build_globs(&patterns).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_build_globs_anchoring() {
+        // No slash: any depth
+        let set = globs(&["*.log"]);
+        assert!(set.is_match("a.log"));
+        assert!(set.is_match("deep/down/a.log"));
+
+        // Leading or inner slash: relative to the scanned folder
+        let set = globs(&["/build"]);
+        assert!(set.is_match("build"));
+        assert!(!set.is_match("src/build"));
+        let set = globs(&["docs/*.md"]);
+        assert!(set.is_match("docs/a.md"));
+        assert!(!set.is_match("docs/sub/a.md"));
+        assert!(!set.is_match("x/docs/a.md"));
+
+        // A trailing slash is dropped, so the folder itself matches
+        let set = globs(&["node_modules/"]);
+        assert!(set.is_match("web/node_modules"));
+    }
+
+    #[test]
+    fn test_build_globs_empty_and_invalid() {
+        assert!(build_globs(&[]).unwrap().is_none());
+        assert!(build_globs(&["  ".to_string(), "/".to_string()])
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            build_globs(&["a[".to_string()]),
+            Err(AppError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_include_and_exclude_filters() {
+        let root = temp_dir("filters");
+        for file in [
+            "a.txt",
+            "b.log",
+            "src/c.txt",
+            "build/d.txt",
+            "src/build/e.txt",
+        ] {
+            touch(&root, file);
+        }
+
+        let mut opts = options(&root);
+        opts.filters.include = vec!["*.txt".to_string()];
+        opts.filters.exclude = vec!["/build".to_string()];
+
+        assert_eq!(
+            found(&root, &run(&opts)),
+            ["a.txt", "src/build/e.txt", "src/c.txt"]
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_excluded_folders_are_not_entered() {
+        let root = temp_dir("prune");
+        touch(&root, "keep/a.txt");
+        touch(&root, "skip/b.txt");
+
+        // An unreadable entry inside the excluded folder would show up as an
+        // error if the walk went in
+        std::os::unix::fs::symlink(root.join("missing"), root.join("skip/broken")).unwrap();
+
+        let mut opts = options(&root);
+        opts.filters.exclude = vec!["skip".to_string()];
+        let report = run(&opts);
+
+        assert_eq!(found(&root, &report), ["keep/a.txt"]);
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+    }
+
+    #[test]
+    fn test_fileogignore_is_honored() {
+        let root = temp_dir("ignore");
+        touch(&root, "a.txt");
+        touch(&root, "b.tmp");
+        touch(&root, "cache/c.txt");
+        touch(&root, "sub/d.tmp");
+        touch(&root, "sub/e.txt");
+        std::fs::write(root.join(IGNORE_FILE_NAME), "*.tmp\ncache/\n").unwrap();
+        std::fs::write(root.join("sub").join(IGNORE_FILE_NAME), "e.txt\n").unwrap();
+
+        assert_eq!(found(&root, &run(&options(&root))), ["a.txt"]);
+    }
+
+    #[test]
+    fn test_gitignore_only_when_asked() {
+        let root = temp_dir("gitignore");
+        touch(&root, "a.txt");
+        touch(&root, "b.log");
+        std::fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+
+        let mut opts = options(&root);
+        assert_eq!(found(&root, &run(&opts)), ["a.txt", "b.log"]);
+
+        opts.filters.respect_gitignore = true;
+        assert_eq!(found(&root, &run(&opts)), ["a.txt"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_symlink_is_recorded_as_link() {
+        let root = temp_dir("symlink");
+        touch(&root, "photo.jpg");
+        std::os::unix::fs::symlink(root.join("photo.jpg"), root.join("link.jpg")).unwrap();
+
+        let report = run(&options(&root));
+        let link = report.files.iter().find(|f| f.name == "link.jpg").unwrap();
+        assert_eq!(link.file_type, FileType::Symlink);
+        assert_eq!(
+            link.link_target.as_deref(),
+            Some(root.join("photo.jpg").as_path())
+        );
+
+        let target = report.files.iter().find(|f| f.name == "photo.jpg").unwrap();
+        assert_eq!(target.file_type, FileType::Image);
+        assert!(target.link_target.is_none());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_special_files_are_skipped() {
+        let root = temp_dir("special");
+        touch(&root, "a.txt");
+        let _socket = std::os::unix::net::UnixListener::bind(root.join("app.sock")).unwrap();
+
+        let mut opts = options(&root);
+        assert_eq!(found(&root, &run(&opts)), ["a.txt"]);
+
+        opts.skip_special_files = false;
+        assert_eq!(found(&root, &run(&opts)), ["a.txt", "app.sock"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_unreadable_entries_are_collected_as_errors() {
+        let root = temp_dir("errors");
+        touch(&root, "a.txt");
+        std::os::unix::fs::symlink(root.join("missing"), root.join("broken")).unwrap();
+        std::os::unix::fs::symlink(root.join("loop"), root.join("loop")).unwrap();
+
+        let report = run(&options(&root));
+
+        assert_eq!(found(&root, &report), ["a.txt"]);
+        assert_eq!(report.errors.len(), 2, "{:?}", report.errors);
+        assert_eq!(report.errors[0].path, Some(root.join("broken")));
+        assert_eq!(report.errors[0].kind, ScanErrorKind::BrokenSymlink);
+        assert_eq!(report.errors[1].path, Some(root.join("loop")));
+        assert!(!report.cancelled);
+    }
+
+    #[test]
+    fn test_check_options_rejects_what_scan_would() {
+        let root = temp_dir("check");
+        assert!(check_options(&options(&root)).is_ok());
+
+        let mut bad_glob = options(&root);
+        bad_glob.filters.include = vec!["a[".to_string()];
+        assert!(matches!(check_options(&bad_glob), Err(AppError::Config(_))));
+
+        let missing = options(&root.join("nope"));
+        assert!(check_options(&missing).is_err());
+        let job = ScanJob::new("test".to_string(), HashSet::new(), HashMap::new());
+        assert!(scan(&missing, &job, |_| {}).is_err());
+    }
+}
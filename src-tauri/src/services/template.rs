@@ -0,0 +1,441 @@
+//! Destination templates such as `Photos/{year}/{month}`: a relative path
+//! whose `{field}` placeholders are filled in from each file.
+
+use crate::error::AppError;
+use crate::models::FileItem;
+use chrono::{DateTime, Datelike, Local, NaiveDateTime};
+use std::path::PathBuf;
+
+/// Stands in for a field the file has no value for
+const UNKNOWN: &str = "Unknown";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    /// The tagged year for music, the capture date for photos, the creation
+    /// date for documents, otherwise the modification date
+    Year,
+    Month,
+    Day,
+    /// File name without the extension
+    Name,
+    Ext,
+    Filename,
+    Type,
+    Category,
+    Make,
+    Camera,
+    /// A song's or a document's title
+    Title,
+    /// A document's author
+    Author,
+    /// Falls back to the album artist
+    Artist,
+    /// Falls back to the artist, so compilations stay in one folder
+    AlbumArtist,
+    Album,
+    Track,
+    Disc,
+    Genre,
+    /// What an archive holds, such as `photo_album` or `source_code`
+    Contents,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "year" => Field::Year,
+            "month" => Field::Month,
+            "day" => Field::Day,
+            "name" => Field::Name,
+            "ext" => Field::Ext,
+            "filename" => Field::Filename,
+            "type" => Field::Type,
+            "category" => Field::Category,
+            "make" => Field::Make,
+            "camera" => Field::Camera,
+            "title" => Field::Title,
+            "author" => Field::Author,
+            "artist" => Field::Artist,
+            "albumartist" => Field::AlbumArtist,
+            "album" => Field::Album,
+            "track" => Field::Track,
+            "disc" => Field::Disc,
+            "genre" => Field::Genre,
+            "contents" => Field::Contents,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    /// `width` zero-pads numbers, as in `{month:02}`
+    Field {
+        field: Field,
+        width: usize,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, AppError> {
+        let invalid =
+            |reason: &str| AppError::Config(format!("Invalid template '{}': {}", template, reason));
+
+        if template.starts_with(['/', '\\']) {
+            return Err(invalid("it must be a relative path"));
+        }
+
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if rest[..start].contains('}') {
+                return Err(invalid("unmatched '}'"));
+            }
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| invalid("unclosed '{'"))?;
+            let placeholder = &rest[start + 1..end];
+            let (name, spec) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+
+            let field = Field::from_name(name.trim())
+                .ok_or_else(|| invalid(&format!("unknown field '{}'", name)))?;
+            let width = match spec {
+                "" => 0,
+                spec if spec.starts_with('0') => spec[1..]
+                    .parse()
+                    .map_err(|_| invalid(&format!("bad format '{}'", spec)))?,
+                spec => return Err(invalid(&format!("bad format '{}'", spec))),
+            };
+
+            parts.push(Part::Field { field, width });
+            rest = &rest[end + 1..];
+        }
+        if rest.contains('}') {
+            return Err(invalid("unmatched '}'"));
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+
+        let literals_escape = parts.iter().any(|part| match part {
+            Part::Literal(text) => text
+                .split(['/', '\\'])
+                .any(|c| c == ".." || c.contains(':')),
+            Part::Field { .. } => false,
+        });
+        if literals_escape {
+            return Err(invalid("it must stay inside the destination folder"));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Whether the template's last part is a file name, i.e. it contains
+    /// `{ext}` or `{filename}`. Otherwise it only names folders.
+    pub fn names_file(&self) -> bool {
+        let last_separator = self.parts.iter().rposition(|part| match part {
+            Part::Literal(text) => text.contains(['/', '\\']),
+            Part::Field { .. } => false,
+        });
+
+        self.parts[last_separator.map_or(0, |i| i + 1)..]
+            .iter()
+            .any(|part| {
+                matches!(
+                    part,
+                    Part::Field {
+                        field: Field::Ext | Field::Filename,
+                        ..
+                    }
+                )
+            })
+    }
+
+    /// The relative path for `file`. Field values can't add path separators
+    /// of their own.
+    pub fn render(&self, file: &FileItem) -> PathBuf {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => rendered.push_str(text),
+                Part::Field { field, width } => match field_value(*field, file) {
+                    Some(value) => {
+                        let value = match value.parse::<u64>() {
+                            Ok(number) if *width > 0 => {
+                                format!("{:0width$}", number, width = *width)
+                            }
+                            _ => value,
+                        };
+                        rendered.push_str(&sanitize(&value));
+                    }
+                    // A file without an extension keeps its bare name, so the
+                    // dot before `{ext}` goes too
+                    None if *field == Field::Ext => {
+                        if rendered.ends_with('.') {
+                            rendered.pop();
+                        }
+                    }
+                    None => rendered.push_str(UNKNOWN),
+                },
+            }
+        }
+
+        rendered
+            .split(['/', '\\'])
+            .map(str::trim)
+            .filter(|c| !c.is_empty() && *c != ".")
+            .collect()
+    }
+}
+
+/// The field's value, or `None` if the file has none
+fn field_value(field: Field, file: &FileItem) -> Option<String> {
+    let photo = file.metadata.photo.as_ref();
+    let audio = file.metadata.audio.as_ref();
+    let document = file.metadata.document.as_ref();
+
+    match field {
+        Field::Year => audio
+            .and_then(|a| a.year)
+            .or_else(|| date(file).map(|d| d.year()))
+            .map(|year| year.to_string()),
+        Field::Month => date(file).map(|d| format!("{:02}", d.month())),
+        Field::Day => date(file).map(|d| format!("{:02}", d.day())),
+        Field::Name => Some(match &file.extension {
+            Some(ext) => file
+                .name
+                .strip_suffix(ext.as_str())
+                .and_then(|n| n.strip_suffix('.'))
+                .unwrap_or(&file.name)
+                .to_string(),
+            None => file.name.clone(),
+        }),
+        Field::Ext => file.extension.clone(),
+        Field::Filename => Some(file.name.clone()),
+        Field::Type => Some(file.file_type.as_str().to_string()),
+        Field::Category => file.category.clone(),
+        Field::Make => photo.and_then(|p| p.camera_make.clone()),
+        Field::Camera => photo.and_then(|p| p.camera_model.clone()),
+        Field::Title => audio
+            .and_then(|a| a.title.clone())
+            .or_else(|| document.and_then(|d| d.title.clone())),
+        Field::Author => document.and_then(|d| d.author.clone()),
+        Field::Artist => audio.and_then(|a| a.artist.clone().or(a.album_artist.clone())),
+        Field::AlbumArtist => audio.and_then(|a| a.album_artist.clone().or(a.artist.clone())),
+        Field::Album => audio.and_then(|a| a.album.clone()),
+        Field::Track => audio.and_then(|a| a.track).map(|t| t.to_string()),
+        Field::Disc => audio.and_then(|a| a.disc).map(|d| d.to_string()),
+        Field::Genre => audio.and_then(|a| a.genre.clone()),
+        Field::Contents => file
+            .metadata
+            .archive
+            .as_ref()
+            .and_then(|a| a.kind.as_ref())
+            .map(|kind| kind.as_str().to_string()),
+    }
+    .filter(|v| !v.trim().is_empty())
+}
+
+/// When a photo was taken or a document created, or else when the file was
+/// last modified
+fn date(file: &FileItem) -> Option<NaiveDateTime> {
+    let metadata = &file.metadata;
+    let recorded = metadata
+        .photo
+        .as_ref()
+        .and_then(|p| p.taken_at.as_deref())
+        .or_else(|| metadata.document.as_ref()?.created_at.as_deref())
+        .and_then(|t| NaiveDateTime::parse_from_str(t, "%Y-%m-%dT%H:%M:%S").ok());
+
+    recorded.or_else(|| {
+        DateTime::from_timestamp(file.modified_at, 0).map(|t| t.with_timezone(&Local).naive_local())
+    })
+}
+
+/// Makes a value safe as (part of) a single path component on any platform
+fn sanitize(value: &str) -> String {
+    let cleaned: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    let cleaned = cleaned.trim().trim_end_matches('.');
+    if cleaned.is_empty() {
+        UNKNOWN.to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AudioTags, DocumentMetadata, FileMetadata, FileType, PhotoMetadata};
+
+    fn file(name: &str, extension: Option<&str>) -> FileItem {
+        FileItem {
+            id: name.to_string(),
+            path: PathBuf::from("/files").join(name),
+            name: name.to_string(),
+            extension: extension.map(str::to_string),
+            size: 0,
+            file_type: extension.map_or(FileType::Other, FileType::from_extension),
+            hash: None,
+            created_at: 0,
+            // 2021-06-15 12:00 UTC
+            modified_at: 1_623_758_400,
+            category: None,
+            metadata: FileMetadata::default(),
+            link_target: None,
+            extension_mismatch: false,
+            suggested_extension: None,
+        }
+    }
+
+    fn render(template: &str, file: &FileItem) -> PathBuf {
+        Template::parse(template).unwrap().render(file)
+    }
+
+    #[test]
+    fn test_parse_rejects_escaping_templates() {
+        for template in [
+            "/abs/{year}",
+            "\\abs",
+            "../{year}",
+            "a/../{name}",
+            "a\\..\\b",
+            "C:/{year}",
+            "a:b/{ext}",
+        ] {
+            assert!(Template::parse(template).is_err(), "{}", template);
+        }
+        // Dots inside a component are fine
+        assert!(Template::parse("a..b/{name}.{ext}").is_ok());
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_placeholders() {
+        for template in [
+            "{nope}",
+            "{year",
+            "year}",
+            "{year}}",
+            "{month:2}",
+            "{month:0x}",
+        ] {
+            assert!(Template::parse(template).is_err(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("AC/DC"), "AC_DC");
+        assert_eq!(sanitize("a\\b:c*d?e\"f<g>h|i"), "a_b_c_d_e_f_g_h_i");
+        assert_eq!(sanitize("tab\there"), "tab_here");
+        assert_eq!(sanitize("  Trailing dots... "), "Trailing dots");
+        assert_eq!(sanitize(".."), UNKNOWN);
+        assert_eq!(sanitize("   "), UNKNOWN);
+    }
+
+    #[test]
+    fn test_names_file() {
+        let names_file = |t: &str| Template::parse(t).unwrap().names_file();
+
+        assert!(names_file("{name}.{ext}"));
+        assert!(names_file("Music/{artist}/{filename}"));
+        assert!(!names_file("Photos/{year}/{month}"));
+        // `{ext}` only names a folder here
+        assert!(!names_file("{ext}/{year}"));
+    }
+
+    #[test]
+    fn test_render_pads_numbers() {
+        let mut song = file("a.mp3", Some("mp3"));
+        song.metadata.audio = Some(AudioTags {
+            title: Some("Song".to_string()),
+            album: Some("Best".to_string()),
+            track: Some(3),
+            disc: Some(12),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            render("{album:02}/{disc:03}-{track:02} {title}.{ext}", &song),
+            PathBuf::from("Best/012-03 Song.mp3")
+        );
+        assert_eq!(render("{track}", &song), PathBuf::from("3"));
+    }
+
+    #[test]
+    fn test_render_missing_extension() {
+        let readme = file("README", None);
+
+        assert_eq!(render("{name}.{ext}", &readme), PathBuf::from("README"));
+        assert_eq!(
+            render("{category}/{filename}", &readme),
+            PathBuf::from("Unknown/README")
+        );
+        assert_eq!(render("{ext}/{name}", &readme), PathBuf::from("README"));
+    }
+
+    #[test]
+    fn test_render_fallbacks() {
+        let mut song = file("a.flac", Some("flac"));
+        song.metadata.audio = Some(AudioTags {
+            album_artist: Some("Various".to_string()),
+            genre: Some("  ".to_string()),
+            year: Some(1999),
+            ..Default::default()
+        });
+        assert_eq!(
+            render("{artist}/{albumartist}/{genre}/{year}", &song),
+            PathBuf::from("Various/Various/Unknown/1999")
+        );
+
+        let mut doc = file("r.pdf", Some("pdf"));
+        doc.metadata.document = Some(DocumentMetadata {
+            title: Some("Q1/Q2: ..".to_string()),
+            created_at: Some("2019-02-03T04:05:06".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            render("{year}/{month}/{day}/{title}", &doc),
+            PathBuf::from("2019/02/03/Q1_Q2_")
+        );
+
+        let mut photo = file("p.jpg", Some("jpg"));
+        photo.metadata.photo = Some(PhotoMetadata {
+            taken_at: Some("2020-12-31T23:59:59".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            render("{year}-{month}/{make}", &photo),
+            PathBuf::from("2020-12/Unknown")
+        );
+
+        // Without a recorded date the modification time decides
+        assert_eq!(
+            render("{year}/{type}/{contents}", &file("x.zip", Some("zip"))),
+            PathBuf::from("2021/archive/Unknown")
+        );
+        assert_eq!(
+            render("{name}/./{ext}", &file("x.tar.gz", Some("gz"))),
+            PathBuf::from("x.tar/gz")
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//! Filesystem side of the trash: the app's own trash folder and, on Linux,
+//! the freedesktop.org home trash (`$XDG_DATA_HOME/Trash`).
+
+use chrono::{Local, Utc};
+use std::path::{Path, PathBuf};
+
+/// Picks where a deleted file will be kept. The system trash is only used
+/// when it lives on the same filesystem as the file, so the move is a rename.
+pub fn backup_path_for(app_trash_dir: &Path, source: &Path, use_system_trash: bool) -> PathBuf {
+    let name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    if use_system_trash {
+        if let Some(files_dir) = system_trash_files_dir(source) {
+            return unique_path(&files_dir, &name);
+        }
+    }
+
+    // Files of the same name trashed within the same millisecond get
+    // successive timestamps
+    let mut timestamp = Utc::now().timestamp_millis();
+    loop {
+        let candidate = app_trash_dir.join(format!("{}_{}", timestamp, name));
+        if std::fs::symlink_metadata(&candidate).is_err() {
+            return candidate;
+        }
+        timestamp += 1;
+    }
+}
+
+/// Where files sent to the trash end up, per the current settings
+#[derive(Debug, Clone)]
+pub struct TrashTarget {
+    pub app_dir: PathBuf,
+    pub use_system_trash: bool,
+}
+
+impl TrashTarget {
+    pub fn backup_path_for(&self, source: &Path) -> PathBuf {
+        backup_path_for(&self.app_dir, source, self.use_system_trash)
+    }
+}
+
+/// Moves `source` to `backup`, writing the `.trashinfo` record first when
+/// `backup` is inside a freedesktop trash
+pub fn move_to_trash(source: &Path, backup: &Path) -> std::io::Result<()> {
+    if let Some(parent) = backup.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let info_path = trash_info_path(backup);
+
+    if let Some(info_path) = &info_path {
+        write_trash_info(info_path, source)?;
+    }
+
+    if let Err(e) = std::fs::rename(source, backup) {
+        if let Some(info_path) = &info_path {
+            let _ = std::fs::remove_file(info_path);
+        }
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// Moves a trashed file back and drops its `.trashinfo` record
+pub fn restore_from_trash(backup: &Path, original: &Path) -> std::io::Result<()> {
+    if let Some(parent) = original.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::rename(backup, original)?;
+    forget(backup);
+
+    Ok(())
+}
+
+/// Bytes a trashed file or folder takes up; a folder counts everything in
+/// it. Symlinks count as themselves, not what they point at.
+pub fn entry_size(path: &Path, metadata: &std::fs::Metadata) -> u64 {
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    let mut size = 0;
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                size += metadata.len();
+            }
+        }
+    }
+
+    size
+}
+
+/// Permanently deletes a trashed file or folder
+pub fn purge(backup: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(backup)?;
+
+    if metadata.is_dir() {
+        std::fs::remove_dir_all(backup)?;
+    } else {
+        std::fs::remove_file(backup)?;
+    }
+    forget(backup);
+
+    Ok(())
+}
+
+fn forget(backup: &Path) {
+    if let Some(info_path) = trash_info_path(backup) {
+        let _ = std::fs::remove_file(info_path);
+    }
+}
+
+/// `Trash/info/<name>.trashinfo` for a file at `Trash/files/<name>`
+fn trash_info_path(backup: &Path) -> Option<PathBuf> {
+    let files_dir = backup.parent()?;
+    if files_dir.file_name()? != "files" {
+        return None;
+    }
+
+    let info_dir = files_dir.parent()?.join("info");
+    if !info_dir.is_dir() {
+        return None;
+    }
+
+    let name = backup.file_name()?.to_string_lossy().to_string();
+    Some(info_dir.join(format!("{}.trashinfo", name)))
+}
+
+fn write_trash_info(info_path: &Path, original: &Path) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let original = std::path::absolute(original)?;
+    let content = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode_path(&original.to_string_lossy()),
+        Local::now().format("%Y-%m-%dT%H:%M:%S")
+    );
+
+    // create_new reserves the name, as the spec requires
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(info_path)?;
+    file.write_all(content.as_bytes())
+}
+
+fn percent_encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// First `dir/name`, `dir/name.2`, ... that is free in both `files` and `info`
+fn unique_path(files_dir: &Path, name: &str) -> PathBuf {
+    let mut candidate = files_dir.join(name);
+    let mut counter = 2;
+
+    while candidate.exists() || trash_info_path(&candidate).is_some_and(|info| info.exists()) {
+        candidate = files_dir.join(format!("{}.{}", name, counter));
+        counter += 1;
+    }
+
+    candidate
+}
+
+/// The home trash, if it is on the same filesystem as `source`
+#[cfg(target_os = "linux")]
+pub fn system_trash_files_dir(source: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+        })?;
+
+    let trash_dir = data_home.join("Trash");
+    std::fs::create_dir_all(trash_dir.join("files")).ok()?;
+    std::fs::create_dir_all(trash_dir.join("info")).ok()?;
+
+    let source_dev = std::fs::symlink_metadata(source).ok()?.dev();
+    let trash_dev = std::fs::metadata(&trash_dir).ok()?.dev();
+
+    (source_dev == trash_dev).then(|| trash_dir.join("files"))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn system_trash_files_dir(_source: &Path) -> Option<PathBuf> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("fileog-trash-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_app_trash_names_do_not_collide() {
+        let dir = temp_dir("names");
+        let source = dir.join("report.txt");
+
+        let first = backup_path_for(&dir, &source, false);
+        assert!(first
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .ends_with("_report.txt"));
+
+        std::fs::write(&first, "a").unwrap();
+        let second = backup_path_for(&dir, &source, false);
+        assert_ne!(first, second);
+        assert!(!second.exists());
+    }
+
+    #[test]
+    fn test_trash_and_restore_round_trip() {
+        let dir = temp_dir("restore");
+        let source = dir.join("docs/report.txt");
+        std::fs::create_dir_all(source.parent().unwrap()).unwrap();
+        std::fs::write(&source, "report").unwrap();
+
+        let backup = dir.join("trash/1_report.txt");
+        move_to_trash(&source, &backup).unwrap();
+        assert!(!source.exists());
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "report");
+
+        // The original folder may have gone in the meantime
+        std::fs::remove_dir(source.parent().unwrap()).unwrap();
+        restore_from_trash(&backup, &source).unwrap();
+        assert!(!backup.exists());
+        assert_eq!(std::fs::read_to_string(&source).unwrap(), "report");
+    }
+
+    #[test]
+    fn test_freedesktop_trash_info_is_written_and_forgotten() {
+        let dir = temp_dir("trashinfo");
+        let trash = dir.join("Trash");
+        std::fs::create_dir_all(trash.join("files")).unwrap();
+        std::fs::create_dir_all(trash.join("info")).unwrap();
+        let source = dir.join("my file.txt");
+        std::fs::write(&source, "x").unwrap();
+        std::fs::write(trash.join("files/my file.txt"), "taken").unwrap();
+
+        let backup = unique_path(&trash.join("files"), "my file.txt");
+        assert_eq!(backup, trash.join("files/my file.txt.2"));
+
+        move_to_trash(&source, &backup).unwrap();
+        let info_path = trash.join("info/my file.txt.2.trashinfo");
+        let info = std::fs::read_to_string(&info_path).unwrap();
+        assert!(info.starts_with("[Trash Info]\n"));
+        assert!(info.contains("my%20file.txt\n"));
+        assert!(info.contains("DeletionDate="));
+
+        restore_from_trash(&backup, &source).unwrap();
+        assert!(!info_path.exists());
+        assert!(source.exists());
+    }
+
+    #[test]
+    fn test_purge_deletes_files_and_folders() {
+        let dir = temp_dir("purge");
+        let file = dir.join("1_a.txt");
+        std::fs::write(&file, "a").unwrap();
+        let folder = dir.join("2_photos");
+        std::fs::create_dir_all(folder.join("2024")).unwrap();
+        std::fs::write(folder.join("2024/b.jpg"), "bb").unwrap();
+
+        purge(&file).unwrap();
+        purge(&folder).unwrap();
+        assert!(!file.exists());
+        assert!(!folder.exists());
+        assert!(purge(&file).is_err());
+    }
+
+    #[test]
+    fn test_entry_size_counts_folder_contents() {
+        let dir = temp_dir("size");
+        let folder = dir.join("album");
+        std::fs::create_dir_all(folder.join("raw")).unwrap();
+        std::fs::write(folder.join("a.jpg"), [0u8; 100]).unwrap();
+        std::fs::write(folder.join("raw/b.cr2"), [0u8; 250]).unwrap();
+        let file = dir.join("c.txt");
+        std::fs::write(&file, [0u8; 7]).unwrap();
+
+        let size = |path: &Path| entry_size(path, &std::fs::symlink_metadata(path).unwrap());
+        assert_eq!(size(&folder), 350);
+        assert_eq!(size(&file), 7);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::error::AppError;
+use crate::models::{BatchSource, HistoryFilter, OperationBatch};
+use chrono::Utc;
+use rusqlite::{Connection, OptionalExtension, ToSql};
+use std::path::PathBuf;
+
+pub fn insert(conn: &Connection, batch: &OperationBatch) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO batches (id, description, source, root_folder, operation_count, failed_count, total_bytes, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        (
+            &batch.id,
+            &batch.description,
+            batch.source.as_str(),
+            batch.root_folder.as_ref().map(|p| p.to_string_lossy().to_string()),
+            batch.operation_count as i64,
+            batch.failed_count as i64,
+            batch.total_bytes as i64,
+            batch.created_at,
+        ),
+    )?;
+
+    Ok(())
+}
+
+pub fn update_counts(
+    conn: &Connection,
+    batch_id: &str,
+    operation_count: usize,
+    failed_count: usize,
+) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE batches SET operation_count = ?1, failed_count = ?2 WHERE id = ?3",
+        (operation_count as i64, failed_count as i64, batch_id),
+    )?;
+
+    Ok(())
+}
+
+/// Escapes `%`, `_` and `\` for a `LIKE ... ESCAPE '\'` pattern
+fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+const SELECT_FILTERED: &str = "SELECT id, description, source, root_folder, operation_count,
+            failed_count, total_bytes, created_at
+     FROM batches b
+     WHERE (?1 IS NULL OR created_at >= ?1)
+       AND (?2 IS NULL OR created_at <= ?2)
+       AND (?3 IS NULL OR EXISTS (
+            SELECT 1 FROM operations o WHERE o.batch_id = b.id AND o.operation_type = ?3))
+       AND (?4 IS NULL OR EXISTS (
+            SELECT 1 FROM operations o WHERE o.batch_id = b.id
+              AND (o.source_path LIKE ?4 ESCAPE '\\' OR o.destination_path LIKE ?4 ESCAPE '\\')))
+       AND (?5 IS NULL OR id = ?5)
+     ORDER BY created_at DESC, rowid DESC";
+
+/// A page of the batches matching `filter`, newest first, without their
+/// operations
+pub fn query(
+    conn: &Connection,
+    filter: &HistoryFilter,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<OperationBatch>, AppError> {
+    query_filtered(
+        conn,
+        &format!("{} LIMIT ?6 OFFSET ?7", SELECT_FILTERED),
+        filter,
+        Some((limit as i64, offset as i64)),
+    )
+}
+
+/// Every batch matching `filter`, newest first, without their operations
+pub fn query_all(
+    conn: &Connection,
+    filter: &HistoryFilter,
+) -> Result<Vec<OperationBatch>, AppError> {
+    query_filtered(conn, SELECT_FILTERED, filter, None)
+}
+
+fn query_filtered(
+    conn: &Connection,
+    sql: &str,
+    filter: &HistoryFilter,
+    page: Option<(i64, i64)>,
+) -> Result<Vec<OperationBatch>, AppError> {
+    let operation_type = filter.operation_type.as_ref().map(|t| t.as_str());
+    let path = filter
+        .path
+        .as_ref()
+        .map(|p| format!("%{}%", escape_like(p)));
+
+    let mut params: Vec<&dyn ToSql> = vec![
+        &filter.from,
+        &filter.to,
+        &operation_type,
+        &path,
+        &filter.batch_id,
+    ];
+    if let Some((limit, offset)) = &page {
+        params.push(limit);
+        params.push(offset);
+    }
+
+    let mut stmt = conn.prepare_cached(sql)?;
+    let batches = stmt
+        .query_map(params.as_slice(), |row| {
+            Ok(OperationBatch {
+                id: row.get(0)?,
+                operations: Vec::new(),
+                created_at: row.get(7)?,
+                description: row.get(1)?,
+                source: BatchSource::from_db_str(&row.get::<_, String>(2)?),
+                root_folder: row.get::<_, Option<String>>(3)?.map(PathBuf::from),
+                operation_count: row.get::<_, i64>(4)? as usize,
+                failed_count: row.get::<_, i64>(5)? as usize,
+                total_bytes: row.get::<_, i64>(6)? as u64,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(batches)
+}
+
+pub fn exists(conn: &Connection, batch_id: &str) -> Result<bool, AppError> {
+    Ok(conn
+        .query_row("SELECT 1 FROM batches WHERE id = ?", [batch_id], |_| Ok(()))
+        .optional()?
+        .is_some())
+}
+
+/// Batch states on the undo stack
+pub const STACK_DONE: &str = "done";
+pub const STACK_UNDONE: &str = "undone";
+
+/// Puts a freshly executed batch on top of the undo stack. Like any editor,
+/// a new action discards batches that were waiting to be redone.
+pub fn push_undo_stack(conn: &Connection, batch_id: &str) -> Result<(), AppError> {
+    conn.execute("DELETE FROM undo_stack WHERE state = ?", [STACK_UNDONE])?;
+    set_stack_state(conn, batch_id, STACK_DONE)
+}
+
+pub fn set_stack_state(conn: &Connection, batch_id: &str, state: &str) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO undo_stack (batch_id, state, updated_at) VALUES (?1, ?2, ?3)",
+        (batch_id, state, Utc::now().timestamp_millis()),
+    )?;
+    Ok(())
+}
+
+pub fn remove_from_stack(conn: &Connection, batch_id: &str) -> Result<(), AppError> {
+    conn.execute("DELETE FROM undo_stack WHERE batch_id = ?", [batch_id])?;
+    Ok(())
+}
+
+pub fn stack_state(conn: &Connection, batch_id: &str) -> Result<Option<String>, AppError> {
+    Ok(conn
+        .query_row(
+            "SELECT state FROM undo_stack WHERE batch_id = ?",
+            [batch_id],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Batch ids in `state`, most recently changed first
+pub fn list_stack(conn: &Connection, state: &str) -> Result<Vec<String>, AppError> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT batch_id FROM undo_stack WHERE state = ? ORDER BY updated_at DESC, rowid DESC",
+    )?;
+
+    let ids = stmt
+        .query_map([state], |row| row.get(0))?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(ids)
+}
+
+pub fn clear(conn: &Connection) -> Result<(), AppError> {
+    conn.execute("DELETE FROM undo_stack", [])?;
+    conn.execute("DELETE FROM batches", [])?;
+    Ok(())
+}
@@ -0,0 +1,526 @@
+use crate::error::AppError;
+use crate::models::FileItem;
+use chrono::Utc;
+use rusqlite::types::Type;
+use rusqlite::{Connection, OptionalExtension, Row};
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::time::UNIX_EPOCH;
+
+pub const CHANGE_ADDED: &str = "added";
+pub const CHANGE_MODIFIED: &str = "modified";
+
+const SELECT_FILES: &str = "SELECT path, id, inode, size, mtime_ns, hash, category, item,
+     metadata_extracted, types_detected FROM files";
+
+/// What a file is compared on to tell whether it changed since it was indexed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileStamp {
+    /// `None` on platforms without inode numbers
+    pub inode: Option<u64>,
+    pub size: u64,
+    /// Finer than `FileItem::modified_at`, so quick successive edits show
+    pub mtime_ns: i64,
+}
+
+impl FileStamp {
+    pub fn of(metadata: &Metadata) -> Self {
+        let mtime_ns = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos() as i64)
+            .unwrap_or(0);
+
+        Self {
+            inode: inode(metadata),
+            size: metadata.len(),
+            mtime_ns,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn inode(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// A file as the index last saw it
+#[derive(Debug, Clone)]
+pub struct IndexedFile {
+    pub item: FileItem,
+    pub stamp: FileStamp,
+    /// The item was read with `extract_metadata` on
+    pub metadata_extracted: bool,
+    /// The item was read with `detect_file_types` on
+    pub types_detected: bool,
+}
+
+fn row_to_indexed(row: &Row) -> rusqlite::Result<(PathBuf, IndexedFile)> {
+    let path: String = row.get(0)?;
+    let item: String = row.get(7)?;
+    let mut item: FileItem = serde_json::from_str(&item)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(7, Type::Text, Box::new(e)))?;
+
+    // The columns are kept current when hashes and categories change
+    item.id = row.get(1)?;
+    item.hash = row.get(5)?;
+    item.category = row.get(6)?;
+
+    let file = IndexedFile {
+        item,
+        stamp: FileStamp {
+            inode: row.get::<_, Option<i64>>(2)?.map(|i| i as u64),
+            size: row.get::<_, i64>(3)? as u64,
+            mtime_ns: row.get(4)?,
+        },
+        metadata_extracted: row.get(8)?,
+        types_detected: row.get(9)?,
+    };
+
+    Ok((PathBuf::from(path), file))
+}
+
+/// Indexed files anywhere below `root`, by path
+pub fn load_under(
+    conn: &Connection,
+    root: &Path,
+) -> Result<HashMap<PathBuf, IndexedFile>, AppError> {
+    let (from, to) = path_range(root);
+    let mut stmt =
+        conn.prepare_cached(&format!("{} WHERE path >= ?1 AND path < ?2", SELECT_FILES))?;
+
+    let files = stmt
+        .query_map((from, to), row_to_indexed)?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(files)
+}
+
+/// The indexed file at `path`
+pub fn find(conn: &Connection, path: &Path) -> Result<Option<IndexedFile>, AppError> {
+    let mut stmt = conn.prepare_cached(&format!("{} WHERE path = ?", SELECT_FILES))?;
+
+    Ok(stmt
+        .query_row([path.to_string_lossy().to_string()], row_to_indexed)
+        .optional()?
+        .map(|(_, file)| file))
+}
+
+/// The paths below `root` are exactly those from `root/` up to, but not
+/// including, `root` followed by the character after the separator
+fn path_range(root: &Path) -> (String, String) {
+    let mut from = root.to_string_lossy().to_string();
+    if !from.ends_with(MAIN_SEPARATOR) {
+        from.push(MAIN_SEPARATOR);
+    }
+
+    let mut to = from.clone();
+    to.pop();
+    to.push((MAIN_SEPARATOR as u8 + 1) as char);
+
+    (from, to)
+}
+
+/// Records new and changed files found by `scan_id` in one transaction. A
+/// file the index already had is marked modified, unless this scan is the
+/// one that added it or only its metadata was filled in.
+pub fn save(conn: &mut Connection, scan_id: &str, files: &[IndexedFile]) -> Result<(), AppError> {
+    let now = Utc::now().timestamp();
+    let tx = conn.transaction()?;
+
+    {
+        let mut stmt = tx.prepare_cached(
+            "INSERT INTO files (path, id, inode, size, mtime_ns, hash, category, item,
+                                metadata_extracted, types_detected, scan_id, change, indexed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+             ON CONFLICT(path) DO UPDATE SET
+                id = excluded.id,
+                inode = excluded.inode,
+                size = excluded.size,
+                mtime_ns = excluded.mtime_ns,
+                hash = excluded.hash,
+                category = excluded.category,
+                item = excluded.item,
+                metadata_extracted = excluded.metadata_extracted,
+                types_detected = excluded.types_detected,
+                change = CASE WHEN files.scan_id = excluded.scan_id OR (
+                        files.inode IS excluded.inode AND files.size = excluded.size
+                        AND files.mtime_ns = excluded.mtime_ns
+                    ) THEN files.change ELSE ?14 END,
+                scan_id = CASE WHEN files.inode IS excluded.inode AND files.size = excluded.size
+                    AND files.mtime_ns = excluded.mtime_ns
+                    THEN files.scan_id ELSE excluded.scan_id END,
+                indexed_at = excluded.indexed_at",
+        )?;
+        for file in files {
+            stmt.execute(rusqlite::params![
+                file.item.path.to_string_lossy().to_string(),
+                file.item.id,
+                file.stamp.inode.map(|i| i as i64),
+                file.stamp.size as i64,
+                file.stamp.mtime_ns,
+                file.item.hash,
+                file.item.category,
+                serde_json::to_string(&file.item)?,
+                file.metadata_extracted,
+                file.types_detected,
+                scan_id,
+                CHANGE_ADDED,
+                now,
+                CHANGE_MODIFIED,
+            ])?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// IDs of the files `scan_id` added and modified, in path order
+pub fn changes(conn: &Connection, scan_id: &str) -> Result<(Vec<String>, Vec<String>), AppError> {
+    let mut stmt =
+        conn.prepare_cached("SELECT id, change FROM files WHERE scan_id = ? ORDER BY path")?;
+
+    let mut added = Vec::new();
+    let mut modified = Vec::new();
+    let rows = stmt.query_map([scan_id], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    for (id, change) in rows.filter_map(|r| r.ok()) {
+        if change == CHANGE_ADDED {
+            added.push(id);
+        } else {
+            modified.push(id);
+        }
+    }
+
+    Ok((added, modified))
+}
+
+/// The content hash recorded for `path`, if the file still matches `stamp`
+pub fn stored_hash(
+    conn: &Connection,
+    path: &Path,
+    stamp: &FileStamp,
+) -> Result<Option<String>, AppError> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT hash FROM files
+         WHERE path = ?1 AND inode IS ?2 AND size = ?3 AND mtime_ns = ?4",
+    )?;
+
+    let hash = stmt
+        .query_row(
+            (
+                path.to_string_lossy().to_string(),
+                stamp.inode.map(|i| i as i64),
+                stamp.size as i64,
+                stamp.mtime_ns,
+            ),
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(hash.flatten())
+}
+
+/// Records content hashes in one transaction, each only if its file is
+/// still indexed with the stamp it was hashed at
+pub fn save_hashes(
+    conn: &mut Connection,
+    hashes: &[(&Path, FileStamp, &str)],
+) -> Result<(), AppError> {
+    let tx = conn.transaction()?;
+
+    {
+        let mut stmt = tx.prepare_cached(
+            "UPDATE files SET hash = ?1
+             WHERE path = ?2 AND inode IS ?3 AND size = ?4 AND mtime_ns = ?5",
+        )?;
+        for (path, stamp, hash) in hashes {
+            stmt.execute((
+                hash,
+                path.to_string_lossy().to_string(),
+                stamp.inode.map(|i| i as i64),
+                stamp.size as i64,
+                stamp.mtime_ns,
+            ))?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn set_category(conn: &Connection, path: &Path, category: &str) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE files SET category = ?1 WHERE path = ?2",
+        (category, path.to_string_lossy().to_string()),
+    )?;
+    Ok(())
+}
+
+/// Follows a file that was moved or renamed to `to`, so it keeps its ID,
+/// hash and category. A record already at `to` is replaced.
+pub fn rename(
+    conn: &Connection,
+    from: &Path,
+    to: &Path,
+    stamp: &FileStamp,
+) -> Result<(), AppError> {
+    let Some(mut file) = find(conn, from)? else {
+        return Ok(());
+    };
+
+    let item = &mut file.item;
+    item.path = to.to_path_buf();
+    item.name = to
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    item.extension = to.extension().map(|e| e.to_string_lossy().to_string());
+
+    conn.execute(
+        "UPDATE OR REPLACE files SET path = ?1, item = ?2, inode = ?3, size = ?4, mtime_ns = ?5
+         WHERE path = ?6",
+        (
+            to.to_string_lossy().to_string(),
+            serde_json::to_string(&file.item)?,
+            stamp.inode.map(|i| i as i64),
+            stamp.size as i64,
+            stamp.mtime_ns,
+            from.to_string_lossy().to_string(),
+        ),
+    )?;
+    Ok(())
+}
+
+/// Drops files that no longer exist from the index
+pub fn delete(conn: &mut Connection, paths: &[&Path]) -> Result<(), AppError> {
+    let tx = conn.transaction()?;
+
+    {
+        let mut stmt = tx.prepare_cached("DELETE FROM files WHERE path = ?")?;
+        for path in paths {
+            stmt.execute([path.to_string_lossy().to_string()])?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FileMetadata, FileType};
+    use crate::storage::migrations::run_migrations;
+
+    fn temp_db(name: &str) -> Connection {
+        let dir =
+            std::env::temp_dir().join(format!("fileog-files-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("fileog.db");
+        run_migrations(&db).unwrap();
+        Connection::open(&db).unwrap()
+    }
+
+    fn stamp(inode: u64, size: u64, mtime_ns: i64) -> FileStamp {
+        FileStamp {
+            inode: Some(inode),
+            size,
+            mtime_ns,
+        }
+    }
+
+    fn indexed(id: &str, path: &str, stamp: FileStamp) -> IndexedFile {
+        let path = PathBuf::from(path);
+        IndexedFile {
+            item: FileItem {
+                id: id.to_string(),
+                name: path.file_name().unwrap().to_string_lossy().to_string(),
+                extension: path.extension().map(|e| e.to_string_lossy().to_string()),
+                path,
+                size: stamp.size,
+                file_type: FileType::Document,
+                hash: None,
+                created_at: 0,
+                modified_at: 0,
+                category: None,
+                metadata: FileMetadata::default(),
+                link_target: None,
+                extension_mismatch: false,
+                suggested_extension: None,
+            },
+            stamp,
+            metadata_extracted: false,
+            types_detected: false,
+        }
+    }
+
+    fn changes_of(conn: &Connection, scan_id: &str) -> (Vec<String>, Vec<String>) {
+        changes(conn, scan_id).unwrap()
+    }
+
+    #[test]
+    fn test_save_tracks_changes() {
+        let mut conn = temp_db("changes");
+        let a = indexed("a", "/docs/a.txt", stamp(1, 10, 100));
+        let b = indexed("b", "/docs/b.txt", stamp(2, 20, 200));
+
+        save(&mut conn, "s1", &[a.clone(), b.clone()]).unwrap();
+        assert_eq!(
+            changes_of(&conn, "s1"),
+            (vec!["a".to_string(), "b".to_string()], vec![])
+        );
+
+        // Saved again by the scan that added it, it is still new
+        let mut a_later = a.clone();
+        a_later.stamp.mtime_ns = 150;
+        save(&mut conn, "s1", &[a_later]).unwrap();
+        assert_eq!(changes_of(&conn, "s1").0.len(), 2);
+
+        // Unchanged, say with metadata filled in: no change for the new scan
+        let mut b_read = b.clone();
+        b_read.metadata_extracted = true;
+        save(&mut conn, "s2", &[b_read]).unwrap();
+        assert_eq!(changes_of(&conn, "s2"), (vec![], vec![]));
+        assert!(
+            find(&conn, Path::new("/docs/b.txt"))
+                .unwrap()
+                .unwrap()
+                .metadata_extracted
+        );
+
+        // Size, modification time and inode each count as a change
+        let mut bigger = b.clone();
+        bigger.stamp.size = 21;
+        let touched = indexed("a", "/docs/a.txt", stamp(1, 10, 300));
+        save(&mut conn, "s3", &[bigger, touched]).unwrap();
+        assert_eq!(
+            changes_of(&conn, "s3"),
+            (vec![], vec!["a".to_string(), "b".to_string()])
+        );
+
+        let replaced = indexed("b", "/docs/b.txt", stamp(9, 21, 200));
+        save(&mut conn, "s4", &[replaced]).unwrap();
+        assert_eq!(changes_of(&conn, "s4"), (vec![], vec!["b".to_string()]));
+    }
+
+    #[test]
+    fn test_load_under() {
+        let mut conn = temp_db("load");
+        let files = [
+            indexed("a", "/docs/a.txt", stamp(1, 1, 1)),
+            indexed("b", "/docs/sub/b.txt", stamp(2, 1, 1)),
+            indexed("c", "/docs-old/c.txt", stamp(3, 1, 1)),
+            indexed("d", "/other/d.txt", stamp(4, 1, 1)),
+        ];
+        save(&mut conn, "s1", &files).unwrap();
+
+        let mut under: Vec<PathBuf> = load_under(&conn, Path::new("/docs"))
+            .unwrap()
+            .into_keys()
+            .collect();
+        under.sort();
+        assert_eq!(
+            under,
+            [
+                PathBuf::from("/docs/a.txt"),
+                PathBuf::from("/docs/sub/b.txt")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hashes_only_for_matching_stamps() {
+        let mut conn = temp_db("hashes");
+        let path = Path::new("/docs/a.txt");
+        let current = stamp(1, 10, 100);
+        let stale = stamp(1, 10, 99);
+        save(&mut conn, "s1", &[indexed("a", "/docs/a.txt", current)]).unwrap();
+
+        assert_eq!(stored_hash(&conn, path, &current).unwrap(), None);
+
+        save_hashes(&mut conn, &[(path, stale, "old")]).unwrap();
+        assert_eq!(stored_hash(&conn, path, &current).unwrap(), None);
+
+        save_hashes(&mut conn, &[(path, current, "abc")]).unwrap();
+        assert_eq!(
+            stored_hash(&conn, path, &current).unwrap().as_deref(),
+            Some("abc")
+        );
+        assert_eq!(stored_hash(&conn, path, &stale).unwrap(), None);
+        assert_eq!(
+            find(&conn, path).unwrap().unwrap().item.hash.as_deref(),
+            Some("abc")
+        );
+    }
+
+    #[test]
+    fn test_set_category() {
+        let mut conn = temp_db("category");
+        let path = Path::new("/docs/a.txt");
+        save(
+            &mut conn,
+            "s1",
+            &[indexed("a", "/docs/a.txt", stamp(1, 1, 1))],
+        )
+        .unwrap();
+
+        set_category(&conn, path, "Work").unwrap();
+        assert_eq!(
+            find(&conn, path).unwrap().unwrap().item.category.as_deref(),
+            Some("Work")
+        );
+
+        // Unindexed files are left alone
+        set_category(&conn, Path::new("/docs/none.txt"), "Work").unwrap();
+        assert!(find(&conn, Path::new("/docs/none.txt")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rename_keeps_identity() {
+        let mut conn = temp_db("rename");
+        let from = Path::new("/docs/a.txt");
+        let to = Path::new("/archive/2024/report.md");
+        let moved = stamp(5, 10, 100);
+        save(
+            &mut conn,
+            "s1",
+            &[
+                indexed("a", "/docs/a.txt", stamp(1, 10, 100)),
+                indexed("old", "/archive/2024/report.md", stamp(2, 3, 3)),
+            ],
+        )
+        .unwrap();
+        save_hashes(&mut conn, &[(from, stamp(1, 10, 100), "abc")]).unwrap();
+        set_category(&conn, from, "Work").unwrap();
+
+        rename(&conn, from, to, &moved).unwrap();
+
+        assert!(find(&conn, from).unwrap().is_none());
+        let file = find(&conn, to).unwrap().unwrap();
+        assert_eq!(file.item.id, "a");
+        assert_eq!(file.item.path, to);
+        assert_eq!(file.item.name, "report.md");
+        assert_eq!(file.item.extension.as_deref(), Some("md"));
+        assert_eq!(file.item.hash.as_deref(), Some("abc"));
+        assert_eq!(file.item.category.as_deref(), Some("Work"));
+        assert_eq!(file.stamp, moved);
+        assert_eq!(load_under(&conn, Path::new("/archive")).unwrap().len(), 1);
+
+        // Nothing to follow for a file that was never indexed
+        rename(&conn, Path::new("/none"), Path::new("/elsewhere"), &moved).unwrap();
+        assert!(find(&conn, Path::new("/elsewhere")).unwrap().is_none());
+    }
+}
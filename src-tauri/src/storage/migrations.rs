@@ -0,0 +1,492 @@
+use crate::error::AppError;
+use rusqlite::{Connection, Transaction};
+use std::path::{Path, PathBuf};
+
+/// A schema change, applied once when `PRAGMA user_version` is below `version`
+struct Migration {
+    version: u32,
+    description: &'static str,
+    apply: fn(&Transaction) -> Result<(), AppError>,
+}
+
+/// Ordered list of every schema change. Append new migrations at the end;
+/// never edit or reorder one that has shipped.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "operations table",
+        apply: create_operations,
+    },
+    Migration {
+        version: 2,
+        description: "undo stack",
+        apply: create_undo_stack,
+    },
+    Migration {
+        version: 3,
+        description: "operation result fingerprints",
+        apply: add_fingerprint_columns,
+    },
+    Migration {
+        version: 4,
+        description: "operation error codes",
+        apply: add_error_columns,
+    },
+    Migration {
+        version: 5,
+        description: "batches table",
+        apply: create_batches,
+    },
+    Migration {
+        version: 6,
+        description: "settings table",
+        apply: create_settings,
+    },
+    Migration {
+        version: 7,
+        description: "scan checkpoints",
+        apply: create_scans,
+    },
+    Migration {
+        version: 8,
+        description: "file index",
+        apply: create_files,
+    },
+    Migration {
+        version: 9,
+        description: "trashed copies",
+        apply: create_trashed_copies,
+    },
+    Migration {
+        version: 10,
+        description: "purged backups",
+        apply: add_purged_column,
+    },
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn schema_version(conn: &Connection) -> Result<u32, AppError> {
+    Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+/// Brings the database at `db_path` up to the latest schema. Each migration
+/// runs in its own transaction together with the `user_version` bump, and an
+/// existing database is backed up before the first one is applied.
+pub fn run_migrations(db_path: &Path) -> Result<(), AppError> {
+    let existed = db_path.exists();
+    let mut conn = Connection::open(db_path)?;
+
+    let current = schema_version(&conn)?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(AppError::Config(format!(
+            "Database schema v{} is newer than this version of FileOG supports (v{})",
+            current, latest
+        )));
+    }
+
+    if current == latest {
+        return Ok(());
+    }
+
+    if existed {
+        backup_database(&conn, db_path, current)?;
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+        (migration.apply)(&tx).map_err(|e| {
+            AppError::Config(format!(
+                "Migration v{} ({}) failed: {}",
+                migration.version, migration.description, e
+            ))
+        })?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+/// Writes a consistent snapshot next to the database, e.g. `fileog.db.v3.bak`
+fn backup_database(conn: &Connection, db_path: &Path, version: u32) -> Result<PathBuf, AppError> {
+    let file_name = db_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "fileog.db".to_string());
+    let backup_path = db_path.with_file_name(format!("{}.v{}.bak", file_name, version));
+
+    // VACUUM INTO refuses to overwrite an existing file
+    if backup_path.exists() {
+        std::fs::remove_file(&backup_path)?;
+    }
+
+    conn.execute(
+        "VACUUM INTO ?1",
+        [backup_path.to_string_lossy().to_string()],
+    )?;
+
+    Ok(backup_path)
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), AppError> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|r| r.ok())
+        .any(|name| name == column);
+
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+// Migrations below tolerate objects that already exist, since databases
+// created before versioning have `user_version` 0 but may hold any of them.
+
+fn create_operations(tx: &Transaction) -> Result<(), AppError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS operations (
+            id TEXT PRIMARY KEY,
+            batch_id TEXT,
+            operation_type TEXT NOT NULL,
+            source_path TEXT NOT NULL,
+            destination_path TEXT,
+            original_name TEXT,
+            new_name TEXT,
+            timestamp INTEGER NOT NULL,
+            status TEXT NOT NULL,
+            backup_path TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_undo_stack(tx: &Transaction) -> Result<(), AppError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS undo_stack (
+            batch_id TEXT PRIMARY KEY,
+            state TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn add_fingerprint_columns(tx: &Transaction) -> Result<(), AppError> {
+    add_column_if_missing(tx, "operations", "result_size", "INTEGER")?;
+    add_column_if_missing(tx, "operations", "result_mtime", "INTEGER")?;
+    add_column_if_missing(tx, "operations", "result_hash", "TEXT")?;
+    Ok(())
+}
+
+fn add_error_columns(tx: &Transaction) -> Result<(), AppError> {
+    add_column_if_missing(tx, "operations", "error_code", "TEXT")?;
+    add_column_if_missing(tx, "operations", "error_message", "TEXT")?;
+
+    // Split legacy `failed:<msg>` statuses into the status and message columns
+    tx.execute(
+        "UPDATE operations SET status = 'failed', error_message = substr(status, 8)
+         WHERE status LIKE 'failed:%'",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_batches(tx: &Transaction) -> Result<(), AppError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS batches (
+            id TEXT PRIMARY KEY,
+            description TEXT NOT NULL,
+            source TEXT NOT NULL,
+            root_folder TEXT,
+            operation_count INTEGER NOT NULL,
+            failed_count INTEGER NOT NULL,
+            total_bytes INTEGER NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Batches recorded before the batches table existed only live in operations
+    tx.execute(
+        "INSERT OR IGNORE INTO batches (id, description, source, root_folder, operation_count, failed_count, total_bytes, created_at)
+         SELECT batch_id, '', 'manual', NULL, COUNT(*),
+                SUM(CASE WHEN status = 'failed' THEN 1 ELSE 0 END), 0, MIN(timestamp)
+         FROM operations
+         WHERE batch_id IS NOT NULL
+         GROUP BY batch_id",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_settings(tx: &Transaction) -> Result<(), AppError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_scans(tx: &Transaction) -> Result<(), AppError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS scans (
+            id TEXT PRIMARY KEY,
+            options TEXT NOT NULL,
+            status TEXT NOT NULL,
+            file_count INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS scan_files (
+            scan_id TEXT NOT NULL,
+            path TEXT NOT NULL,
+            item TEXT NOT NULL,
+            PRIMARY KEY (scan_id, path)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_files(tx: &Transaction) -> Result<(), AppError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS files (
+            path TEXT PRIMARY KEY,
+            id TEXT NOT NULL UNIQUE,
+            inode INTEGER,
+            size INTEGER NOT NULL,
+            mtime_ns INTEGER NOT NULL,
+            hash TEXT,
+            category TEXT,
+            item TEXT NOT NULL,
+            metadata_extracted INTEGER NOT NULL,
+            types_detected INTEGER NOT NULL,
+            scan_id TEXT NOT NULL,
+            change TEXT NOT NULL,
+            indexed_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS idx_files_scan ON files (scan_id, change)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_trashed_copies(tx: &Transaction) -> Result<(), AppError> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS trashed_copies (
+            trash_path TEXT PRIMARY KEY,
+            operation_id TEXT NOT NULL,
+            original_path TEXT NOT NULL,
+            trashed_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Undo used to keep the trashed copy in the operation's backup_path
+    tx.execute(
+        "INSERT OR IGNORE INTO trashed_copies (trash_path, operation_id, original_path, trashed_at)
+         SELECT backup_path, id, destination_path, timestamp FROM operations
+         WHERE operation_type IN ('copy', 'extract')
+           AND backup_path IS NOT NULL AND destination_path IS NOT NULL",
+        [],
+    )?;
+    tx.execute(
+        "UPDATE operations SET backup_path = NULL WHERE operation_type IN ('copy', 'extract')",
+        [],
+    )?;
+    Ok(())
+}
+
+fn add_purged_column(tx: &Transaction) -> Result<(), AppError> {
+    tx.execute(
+        "ALTER TABLE operations ADD COLUMN purged INTEGER NOT NULL DEFAULT 0",
+        [],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("fileog-migrations-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("fileog.db")
+    }
+
+    fn tables(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        let mut stmt = conn
+            .prepare(&format!("PRAGMA table_info({})", table))
+            .unwrap();
+        stmt.query_map([], |row| row.get(1))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_migrates_empty_database() {
+        let db = temp_db("empty");
+        run_migrations(&db).unwrap();
+
+        let conn = Connection::open(&db).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+        let tables = tables(&conn);
+        for table in [
+            "batches",
+            "files",
+            "operations",
+            "scan_files",
+            "scans",
+            "settings",
+            "trashed_copies",
+            "undo_stack",
+        ] {
+            assert!(tables.iter().any(|t| t == table), "missing {}", table);
+        }
+
+        // A new database has nothing to back up, and a second run is a no-op
+        assert!(!db.with_file_name("fileog.db.v0.bak").exists());
+        run_migrations(&db).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn test_migrates_v0_operations_table() {
+        let db = temp_db("v0");
+        {
+            let conn = Connection::open(&db).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE operations (
+                    id TEXT PRIMARY KEY,
+                    batch_id TEXT,
+                    operation_type TEXT NOT NULL,
+                    source_path TEXT NOT NULL,
+                    destination_path TEXT,
+                    original_name TEXT,
+                    new_name TEXT,
+                    timestamp INTEGER NOT NULL,
+                    status TEXT NOT NULL,
+                    backup_path TEXT
+                );
+                INSERT INTO operations VALUES
+                    ('a', 'b1', 'move', '/x/a', '/y/a', 'a', NULL, 100, 'completed', NULL),
+                    ('b', 'b1', 'move', '/x/b', '/y/b', 'b', NULL, 101, 'failed:disk full', NULL),
+                    ('c', 'b2', 'copy', '/x/c', '/y/c', 'c', NULL, 102, 'undone', '/t/1_c');",
+            )
+            .unwrap();
+        }
+
+        run_migrations(&db).unwrap();
+
+        let conn = Connection::open(&db).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), latest_version());
+        let operation_columns = columns(&conn, "operations");
+        for column in ["result_hash", "error_code", "error_message"] {
+            assert!(operation_columns.iter().any(|c| c == column));
+        }
+
+        let (status, message): (String, Option<String>) = conn
+            .query_row(
+                "SELECT status, error_message FROM operations WHERE id = 'b'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(status, "failed");
+        assert_eq!(message.as_deref(), Some("disk full"));
+
+        let counts: (i64, i64, i64) = conn
+            .query_row(
+                "SELECT operation_count, failed_count, created_at FROM batches WHERE id = 'b1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(counts, (2, 1, 100));
+
+        // A copy trashed by undo moves from backup_path to its own record
+        let trashed: (String, String, String) = conn
+            .query_row(
+                "SELECT trash_path, operation_id, original_path FROM trashed_copies",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            trashed,
+            ("/t/1_c".to_string(), "c".to_string(), "/y/c".to_string())
+        );
+        let backup_path: Option<String> = conn
+            .query_row(
+                "SELECT backup_path FROM operations WHERE id = 'c'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(backup_path, None);
+
+        // The backup is the database as it was before migrating
+        let backup = Connection::open(db.with_file_name("fileog.db.v0.bak")).unwrap();
+        assert_eq!(schema_version(&backup).unwrap(), 0);
+        assert_eq!(tables(&backup), vec!["operations".to_string()]);
+        let status: String = backup
+            .query_row("SELECT status FROM operations WHERE id = 'b'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(status, "failed:disk full");
+    }
+
+    #[test]
+    fn test_refuses_newer_schema() {
+        let db = temp_db("newer");
+        {
+            let conn = Connection::open(&db).unwrap();
+            conn.pragma_update(None, "user_version", latest_version() + 1)
+                .unwrap();
+        }
+
+        assert!(run_migrations(&db).is_err());
+    }
+}
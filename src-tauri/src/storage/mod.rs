@@ -1 +1,69 @@
-// Storage module - to be implemented
+pub mod batches;
+pub mod files;
+pub mod migrations;
+pub mod operations;
+pub mod scans;
+pub mod settings;
+pub mod trashed_copies;
+
+use crate::error::AppError;
+use r2d2_sqlite::SqliteConnectionManager;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::Manager;
+
+pub type DbPool = r2d2::Pool<SqliteConnectionManager>;
+pub type DbConnection = r2d2::PooledConnection<SqliteConnectionManager>;
+
+/// How long a connection waits on a locked database before giving up
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Shared handle to `fileog.db`, managed as Tauri state. Clones share the pool.
+#[derive(Clone)]
+pub struct Storage {
+    pool: DbPool,
+    db_path: PathBuf,
+}
+
+impl Storage {
+    /// Migrates the database to the latest schema and opens the pool
+    pub fn open(db_path: &Path) -> Result<Self, AppError> {
+        // Ensure parent directory exists
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        migrations::run_migrations(db_path)?;
+
+        let manager = SqliteConnectionManager::file(db_path).with_init(|conn| {
+            conn.pragma_update(None, "journal_mode", "WAL")?;
+            conn.pragma_update(None, "synchronous", "NORMAL")?;
+            conn.busy_timeout(BUSY_TIMEOUT)
+        });
+
+        let pool = r2d2::Pool::builder().max_size(8).build(manager)?;
+
+        Ok(Self {
+            pool,
+            db_path: db_path.to_path_buf(),
+        })
+    }
+
+    pub fn conn(&self) -> Result<DbConnection, AppError> {
+        Ok(self.pool.get()?)
+    }
+
+    pub fn db_path(&self) -> &Path {
+        &self.db_path
+    }
+}
+
+pub fn app_data_dir(app: &tauri::AppHandle) -> PathBuf {
+    app.path()
+        .app_data_dir()
+        .unwrap_or_else(|_| PathBuf::from("."))
+}
+
+pub fn get_db_path(app: &tauri::AppHandle) -> PathBuf {
+    app_data_dir(app).join("fileog.db")
+}
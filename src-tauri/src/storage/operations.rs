@@ -0,0 +1,248 @@
+use crate::error::AppError;
+use crate::models::{FileFingerprint, Operation, OperationStatus, OperationType};
+use rusqlite::{Connection, OptionalExtension};
+use std::path::{Path, PathBuf};
+
+const SELECT_OPERATIONS: &str =
+    "SELECT id, batch_id, operation_type, source_path, destination_path,
+        original_name, new_name, timestamp, status, backup_path,
+        result_size, result_mtime, result_hash, error_code, error_message, purged
+    FROM operations";
+
+/// Maps a row selected with `SELECT_OPERATIONS` into an `Operation`
+fn row_to_operation(row: &rusqlite::Row) -> rusqlite::Result<Operation> {
+    let fingerprint = match (
+        row.get::<_, Option<i64>>(10)?,
+        row.get::<_, Option<i64>>(11)?,
+    ) {
+        (Some(size), Some(modified_at)) => Some(FileFingerprint {
+            size: size as u64,
+            modified_at,
+            hash: row.get(12)?,
+        }),
+        _ => None,
+    };
+
+    Ok(Operation {
+        id: row.get(0)?,
+        operation_type: OperationType::from_db_str(&row.get::<_, String>(2)?),
+        source_path: PathBuf::from(row.get::<_, String>(3)?),
+        destination_path: row.get::<_, Option<String>>(4)?.map(PathBuf::from),
+        original_name: row.get(5)?,
+        new_name: row.get(6)?,
+        timestamp: row.get(7)?,
+        status: OperationStatus::from_db(&row.get::<_, String>(8)?, row.get(14)?),
+        batch_id: row.get(1)?,
+        backup_path: row.get::<_, Option<String>>(9)?.map(PathBuf::from),
+        fingerprint,
+        error_code: row.get(13)?,
+        purged: row.get(15)?,
+    })
+}
+
+fn query_operations<P: rusqlite::Params>(
+    conn: &Connection,
+    clause: &str,
+    params: P,
+) -> Result<Vec<Operation>, AppError> {
+    let mut stmt = conn.prepare_cached(&format!("{} {}", SELECT_OPERATIONS, clause))?;
+
+    let operations = stmt
+        .query_map(params, row_to_operation)?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(operations)
+}
+
+pub fn insert(conn: &Connection, operation: &Operation) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO operations (id, batch_id, operation_type, source_path, destination_path, original_name, new_name, timestamp, status, backup_path, error_code, error_message, purged)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        (
+            &operation.id,
+            &operation.batch_id,
+            operation.operation_type.as_str(),
+            operation.source_path.to_string_lossy().to_string(),
+            operation.destination_path.as_ref().map(|p| p.to_string_lossy().to_string()),
+            &operation.original_name,
+            &operation.new_name,
+            operation.timestamp,
+            operation.status.as_db_str(),
+            operation.backup_path.as_ref().map(|p| p.to_string_lossy().to_string()),
+            &operation.error_code,
+            operation.status.error_message(),
+            operation.purged,
+        ),
+    )?;
+
+    Ok(())
+}
+
+/// Writes back status, error and fingerprint once an operation has settled
+pub fn update_result(conn: &Connection, operation: &Operation) -> Result<(), AppError> {
+    let fingerprint = operation.fingerprint.as_ref();
+
+    conn.execute(
+        "UPDATE operations
+         SET status = ?1, error_code = ?2, error_message = ?3,
+             result_size = ?4, result_mtime = ?5, result_hash = ?6
+         WHERE id = ?7",
+        (
+            operation.status.as_db_str(),
+            &operation.error_code,
+            operation.status.error_message(),
+            fingerprint.map(|f| f.size as i64),
+            fingerprint.map(|f| f.modified_at),
+            fingerprint.and_then(|f| f.hash.clone()),
+            &operation.id,
+        ),
+    )?;
+
+    Ok(())
+}
+
+pub fn set_status(
+    conn: &Connection,
+    operation_id: &str,
+    status: &OperationStatus,
+) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE operations SET status = ?1, error_message = ?2 WHERE id = ?3",
+        (status.as_db_str(), status.error_message(), operation_id),
+    )?;
+
+    Ok(())
+}
+
+pub fn exists(conn: &Connection, operation_id: &str) -> Result<bool, AppError> {
+    Ok(conn
+        .query_row(
+            "SELECT 1 FROM operations WHERE id = ?",
+            [operation_id],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+}
+
+pub fn get(conn: &Connection, operation_id: &str) -> Result<Option<Operation>, AppError> {
+    Ok(conn
+        .query_row(
+            &format!("{} WHERE id = ?", SELECT_OPERATIONS),
+            [operation_id],
+            row_to_operation,
+        )
+        .optional()?)
+}
+
+/// The `limit` most recent completed operations across all batches that can
+/// still be undone, newest first
+pub fn list_recent_completed(conn: &Connection, limit: usize) -> Result<Vec<Operation>, AppError> {
+    query_operations(
+        conn,
+        "WHERE status = 'completed' AND purged = 0 ORDER BY timestamp DESC, rowid DESC LIMIT ?",
+        [limit as i64],
+    )
+}
+
+/// Operations of a batch in execution order, optionally only those in `status`
+pub fn list_by_batch(
+    conn: &Connection,
+    batch_id: &str,
+    status: Option<&OperationStatus>,
+    newest_first: bool,
+) -> Result<Vec<Operation>, AppError> {
+    let clause = format!(
+        "WHERE batch_id = ?1 AND (?2 IS NULL OR status = ?2) ORDER BY rowid {}",
+        if newest_first { "DESC" } else { "ASC" }
+    );
+
+    query_operations(conn, &clause, (batch_id, status.map(|s| s.as_db_str())))
+}
+
+/// Journal entries that never got finalized
+pub fn list_incomplete(conn: &Connection) -> Result<Vec<Operation>, AppError> {
+    query_operations(
+        conn,
+        "WHERE status IN ('pending', 'in_progress') ORDER BY rowid",
+        [],
+    )
+}
+
+/// Finds a live operation from a later batch that picked up where `op` left
+/// the file, which has to be undone before `op` can be
+pub fn find_dependent(conn: &Connection, op: &Operation) -> Result<Option<Operation>, AppError> {
+    let Some(dest) = &op.destination_path else {
+        return Ok(None);
+    };
+
+    Ok(conn
+        .query_row(
+            &format!(
+                "{} WHERE status = 'completed'
+                   AND source_path = ?1
+                   AND batch_id IS NOT ?2
+                   AND rowid > (SELECT rowid FROM operations WHERE id = ?3)
+                 ORDER BY rowid
+                 LIMIT 1",
+                SELECT_OPERATIONS
+            ),
+            (dest.to_string_lossy().to_string(), &op.batch_id, &op.id),
+            row_to_operation,
+        )
+        .optional()?)
+}
+
+/// The operation whose trashed file lives at `backup_path`: a delete or a
+/// file replaced by a link
+pub fn find_by_backup_path(
+    conn: &Connection,
+    backup_path: &Path,
+) -> Result<Option<Operation>, AppError> {
+    Ok(conn
+        .query_row(
+            &format!(
+                "{} WHERE backup_path = ? ORDER BY rowid DESC LIMIT 1",
+                SELECT_OPERATIONS
+            ),
+            [backup_path.to_string_lossy().to_string()],
+            row_to_operation,
+        )
+        .optional()?)
+}
+
+/// Marks the operations whose trashed file at `backup_path` was permanently
+/// deleted, so they can't be undone any more
+pub fn mark_purged(conn: &Connection, backup_path: &Path) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE operations SET purged = 1 WHERE backup_path = ?",
+        [backup_path.to_string_lossy().to_string()],
+    )?;
+    Ok(())
+}
+
+/// Backup paths of every delete or replacement by a link still in effect
+pub fn list_backup_paths(conn: &Connection) -> Result<Vec<PathBuf>, AppError> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT backup_path FROM operations
+         WHERE backup_path IS NOT NULL
+           AND operation_type IN ('delete', 'hardlink', 'symlink')
+           AND status = 'completed'
+           AND purged = 0
+         ORDER BY rowid DESC",
+    )?;
+
+    let paths = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .filter_map(|r| r.ok())
+        .map(PathBuf::from)
+        .collect();
+
+    Ok(paths)
+}
+
+pub fn clear(conn: &Connection) -> Result<(), AppError> {
+    conn.execute("DELETE FROM operations", [])?;
+    Ok(())
+}
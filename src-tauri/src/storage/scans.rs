@@ -0,0 +1,123 @@
+use crate::error::AppError;
+use crate::models::{FileItem, ScanCheckpoint, ScanOptions};
+use chrono::Utc;
+use rusqlite::types::Type;
+use rusqlite::{Connection, OptionalExtension, Row};
+
+pub const STATUS_RUNNING: &str = "running";
+pub const STATUS_CANCELLED: &str = "cancelled";
+
+const SELECT_SCANS: &str =
+    "SELECT id, options, status, file_count, created_at, updated_at FROM scans";
+
+fn row_to_checkpoint(row: &Row) -> rusqlite::Result<ScanCheckpoint> {
+    let options: String = row.get(1)?;
+
+    Ok(ScanCheckpoint {
+        scan_id: row.get(0)?,
+        options: serde_json::from_str(&options)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, Type::Text, Box::new(e)))?,
+        status: row.get(2)?,
+        file_count: row.get::<_, i64>(3)? as usize,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+    })
+}
+
+pub fn insert(conn: &Connection, scan_id: &str, options: &ScanOptions) -> Result<(), AppError> {
+    let now = Utc::now().timestamp();
+
+    conn.execute(
+        "INSERT INTO scans (id, options, status, file_count, created_at, updated_at)
+         VALUES (?1, ?2, ?3, 0, ?4, ?4)",
+        (
+            scan_id,
+            serde_json::to_string(options)?,
+            STATUS_RUNNING,
+            now,
+        ),
+    )?;
+
+    Ok(())
+}
+
+pub fn get(conn: &Connection, scan_id: &str) -> Result<Option<ScanCheckpoint>, AppError> {
+    Ok(conn
+        .query_row(
+            &format!("{} WHERE id = ?", SELECT_SCANS),
+            [scan_id],
+            row_to_checkpoint,
+        )
+        .optional()?)
+}
+
+/// Unfinished scans, most recently active first
+pub fn list(conn: &Connection) -> Result<Vec<ScanCheckpoint>, AppError> {
+    let mut stmt = conn.prepare_cached(&format!("{} ORDER BY updated_at DESC", SELECT_SCANS))?;
+
+    let checkpoints = stmt
+        .query_map([], row_to_checkpoint)?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(checkpoints)
+}
+
+pub fn set_status(conn: &Connection, scan_id: &str, status: &str) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE scans SET status = ?1, updated_at = ?2 WHERE id = ?3",
+        (status, Utc::now().timestamp(), scan_id),
+    )?;
+    Ok(())
+}
+
+/// Appends found files to the checkpoint in one transaction
+pub fn save_files(
+    conn: &mut Connection,
+    scan_id: &str,
+    files: &[FileItem],
+) -> Result<(), AppError> {
+    let tx = conn.transaction()?;
+
+    {
+        let mut stmt = tx.prepare_cached(
+            "INSERT OR REPLACE INTO scan_files (scan_id, path, item) VALUES (?1, ?2, ?3)",
+        )?;
+        for file in files {
+            stmt.execute((
+                scan_id,
+                file.path.to_string_lossy().to_string(),
+                serde_json::to_string(file)?,
+            ))?;
+        }
+    }
+
+    tx.execute(
+        "UPDATE scans
+         SET file_count = (SELECT COUNT(*) FROM scan_files WHERE scan_id = ?1), updated_at = ?2
+         WHERE id = ?1",
+        (scan_id, Utc::now().timestamp()),
+    )?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+pub fn load_files(conn: &Connection, scan_id: &str) -> Result<Vec<FileItem>, AppError> {
+    let mut stmt = conn.prepare_cached("SELECT item FROM scan_files WHERE scan_id = ?")?;
+
+    let files = stmt
+        .query_map([scan_id], |row| row.get::<_, String>(0))?
+        .filter_map(|r| r.ok())
+        .filter_map(|json| serde_json::from_str(&json).ok())
+        .collect();
+
+    Ok(files)
+}
+
+/// Drops a checkpoint once its scan has finished
+pub fn delete(conn: &Connection, scan_id: &str) -> Result<(), AppError> {
+    conn.execute("DELETE FROM scan_files WHERE scan_id = ?", [scan_id])?;
+    conn.execute("DELETE FROM scans WHERE id = ?", [scan_id])?;
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use crate::error::AppError;
+use rusqlite::{Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+pub const APP_SETTINGS: &str = "app_settings";
+pub const CATEGORIES: &str = "categories";
+
+/// Reads a JSON-encoded value, `None` if the key was never written
+pub fn get<T: DeserializeOwned>(conn: &Connection, key: &str) -> Result<Option<T>, AppError> {
+    let value: Option<String> = conn
+        .query_row("SELECT value FROM settings WHERE key = ?", [key], |row| {
+            row.get(0)
+        })
+        .optional()?;
+
+    match value {
+        Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn set<T: Serialize>(conn: &Connection, key: &str, value: &T) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+        (key, serde_json::to_string(value)?),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use crate::error::AppError;
+use rusqlite::{Connection, OptionalExtension, Row};
+use std::path::{Path, PathBuf};
+
+const SELECT_TRASHED_COPIES: &str =
+    "SELECT trash_path, operation_id, original_path, trashed_at FROM trashed_copies";
+
+/// An edited copy or extraction that undo moved to the trash. Every undo
+/// gets its own record, so a copy that is redone and undone again keeps
+/// track of both trashed files.
+#[derive(Debug, Clone)]
+pub struct TrashedCopy {
+    pub trash_path: PathBuf,
+    pub operation_id: String,
+    /// Where the copy was, and where restoring puts it back
+    pub original_path: PathBuf,
+    pub trashed_at: i64,
+}
+
+fn row_to_trashed_copy(row: &Row) -> rusqlite::Result<TrashedCopy> {
+    Ok(TrashedCopy {
+        trash_path: PathBuf::from(row.get::<_, String>(0)?),
+        operation_id: row.get(1)?,
+        original_path: PathBuf::from(row.get::<_, String>(2)?),
+        trashed_at: row.get(3)?,
+    })
+}
+
+pub fn insert(conn: &Connection, copy: &TrashedCopy) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO trashed_copies (trash_path, operation_id, original_path, trashed_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        (
+            copy.trash_path.to_string_lossy().to_string(),
+            &copy.operation_id,
+            copy.original_path.to_string_lossy().to_string(),
+            copy.trashed_at,
+        ),
+    )?;
+    Ok(())
+}
+
+pub fn find(conn: &Connection, trash_path: &Path) -> Result<Option<TrashedCopy>, AppError> {
+    Ok(conn
+        .query_row(
+            &format!("{} WHERE trash_path = ?", SELECT_TRASHED_COPIES),
+            [trash_path.to_string_lossy().to_string()],
+            row_to_trashed_copy,
+        )
+        .optional()?)
+}
+
+/// Trash paths of every recorded copy, newest first
+pub fn list_paths(conn: &Connection) -> Result<Vec<PathBuf>, AppError> {
+    let mut stmt =
+        conn.prepare_cached("SELECT trash_path FROM trashed_copies ORDER BY trashed_at DESC")?;
+
+    let paths = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .filter_map(|r| r.ok())
+        .map(PathBuf::from)
+        .collect();
+
+    Ok(paths)
+}
+
+/// Forgets a trashed copy once it has been restored
+pub fn delete(conn: &Connection, trash_path: &Path) -> Result<(), AppError> {
+    conn.execute(
+        "DELETE FROM trashed_copies WHERE trash_path = ?",
+        [trash_path.to_string_lossy().to_string()],
+    )?;
+    Ok(())
+}
+
+pub fn clear(conn: &Connection) -> Result<(), AppError> {
+    conn.execute("DELETE FROM trashed_copies", [])?;
+    Ok(())
+}
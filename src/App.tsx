@@ -1,4 +1,4 @@
-import { useState, useCallback } from "react";
+import { useState, useCallback, useRef } from "react";
 import { MainLayout } from "@/components/layout/MainLayout";
 import { FileList, FileItemData } from "@/components/file/FileList";
 import { ProgressPanel } from "@/components/operation/ProgressPanel";
@@ -9,13 +9,23 @@ import { invoke, Channel } from "@tauri-apps/api/core";
 import { open, confirm } from "@tauri-apps/plugin-dialog";
 import { classifyFiles, getFileExtension } from "@/services/llm";
 import { toast } from "sonner";
-import type { PlannedOperation } from "@/types";
+import type { PlannedOperation, ScanChanges, ScanError } from "@/types";
 
 interface ScanProgress {
+  scan_id: string;
   event: string;
   current_file?: string;
   scanned_count: number;
   total_count?: number;
+  error?: ScanError;
+}
+
+interface ScanReport {
+  scan_id: string;
+  files: FileItemData[];
+  errors: ScanError[];
+  cancelled: boolean;
+  changes: ScanChanges;
 }
 
 interface OperationProgress {
@@ -24,6 +34,9 @@ interface OperationProgress {
   completed_count: number;
   total_count: number;
   percentage: number;
+  task_id?: string;
+  processed_bytes?: number;
+  total_bytes?: number;
 }
 
 interface DuplicateGroup {
@@ -42,6 +55,7 @@ function App() {
   const [activeFilter, setActiveFilter] = useState<string | null>(null);
   const [duplicates, setDuplicates] = useState<DuplicateGroup[]>([]);
   const [showDuplicates, setShowDuplicates] = useState(false);
+  const scanIdRef = useRef<string | null>(null);
 
   const handleScan = useCallback(async () => {
     const selected = await open({ directory: true });
@@ -53,6 +67,8 @@ function App() {
 
     const onProgress = new Channel<ScanProgress>();
     onProgress.onmessage = (msg) => {
+      scanIdRef.current = msg.scan_id;
+      if (msg.event === "error") return;
       setProgress({
         current: msg.scanned_count,
         total: msg.total_count || 0,
@@ -61,15 +77,22 @@ function App() {
     };
 
     try {
-      const result = await invoke<FileItemData[]>("scan_directory", {
+      const result = await invoke<ScanReport>("scan_directory", {
         options: { path: selected, recursive: true, include_hidden: false },
         onProgress,
       });
-      setFiles(result);
+      setFiles(result.files);
+      if (result.cancelled) {
+        toast.info("扫描已取消，可稍后继续");
+      }
+      if (result.errors.length > 0) {
+        toast.warning(`${result.errors.length} 个文件无法读取`);
+      }
     } catch (e) {
       console.error("Scan failed:", e);
       toast.error(`扫描失败: ${e}`);
     } finally {
+      scanIdRef.current = null;
       setIsProcessing(false);
     }
   }, []);
@@ -446,6 +469,9 @@ function App() {
         completed={progress.current}
         total={progress.total}
         onCancel={() => {
+          if (scanIdRef.current) {
+            invoke("cancel_scan", { scanId: scanIdRef.current });
+          }
           setIsProcessing(false);
           setIsClassifying(false);
         }}
@@ -1,4 +1,4 @@
-import { FileText, Image, Video, Music, Code, Archive, File } from "lucide-react";
+import { FileText, Image, Video, Music, Code, Archive, File, Link } from "lucide-react";
 import { ScrollArea } from "@/components/ui/scroll-area";
 import { Checkbox } from "@/components/ui/checkbox";
 import { cn } from "@/lib/utils";
@@ -26,6 +26,7 @@ const fileTypeIcons: Record<string, React.ElementType> = {
   audio: Music,
   code: Code,
   archive: Archive,
+  symlink: Link,
   other: File,
 };
 
@@ -36,6 +37,7 @@ const fileTypeColors: Record<string, string> = {
   audio: "text-amber-500",
   code: "text-pink-500",
   archive: "text-cyan-500",
+  symlink: "text-violet-500",
   other: "text-zinc-500",
 };
 
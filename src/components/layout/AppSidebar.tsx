@@ -74,6 +74,15 @@ interface OperationBatch {
   description: string;
 }
 
+interface OperationUndoResult {
+  success: boolean;
+  error?: string;
+  conflict?: { kind: string; path: string; message: string };
+  copy_action?:
+    | { action: "deleted" }
+    | { action: "trashed"; trash_path: string; reason: string };
+}
+
 interface AppSidebarProps {
   onScan?: () => void;
   onOpenSettings?: () => void;
@@ -120,10 +129,20 @@ export function AppSidebar({
 
   const handleUndo = async () => {
     try {
-      const result = await invoke<unknown[]>("undo_operations", { steps: 1 });
-      if (result.length > 0) {
-        toast.success(`已撤销 ${result.length} 个操作`);
+      const result = await invoke<OperationUndoResult[]>("undo_operations", { steps: 1 });
+      const undone = result.filter((r) => r.success);
+      const conflicts = result.filter((r) => r.conflict);
+      const trashed = result.filter((r) => r.copy_action?.action === "trashed");
+      if (undone.length > 0) {
+        toast.success(`已撤销 ${undone.length} 个操作`);
+        if (trashed.length > 0) {
+          toast.info(`${trashed.length} 个已修改的副本已移至回收站`);
+        }
         loadHistoryCount();
+      } else if (conflicts.length > 0) {
+        toast.warning(`撤销被阻止: ${conflicts[0].conflict?.message}`);
+      } else if (result.length > 0) {
+        toast.error(`撤销失败: ${result[0].error}`);
       } else {
         toast.info("没有可撤销的操作");
       }
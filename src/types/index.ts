@@ -9,6 +9,7 @@ export interface FileToClassify {
   name: string;
   extension: string;
   size: number;
+  preview_text?: string | null;
 }
 
 export interface ClassificationResult {
@@ -25,6 +26,13 @@ export interface AppSettings {
   language: string;
   llm: LlmSettings;
   prompts: PromptSettings;
+  trash: TrashSettings;
+}
+
+export interface TrashSettings {
+  max_age_days: number | null;
+  max_total_bytes: number | null;
+  use_system_trash: boolean;
 }
 
 export interface LlmSettings {
@@ -67,7 +75,14 @@ export interface CategoryRule {
 export interface PlannedOperation {
   file_id: string;
   file_name: string;
-  operation_type: "move" | "copy" | "rename" | "delete";
+  operation_type:
+    | "move"
+    | "copy"
+    | "rename"
+    | "delete"
+    | "extract"
+    | "hardlink"
+    | "symlink";
   source: string;
   destination: string;
   category?: string;
@@ -75,15 +90,24 @@ export interface PlannedOperation {
 
 export interface Operation {
   id: string;
-  operation_type: "move" | "copy" | "rename" | "delete";
+  operation_type:
+    | "move"
+    | "copy"
+    | "rename"
+    | "delete"
+    | "extract"
+    | "hardlink"
+    | "symlink";
   source_path: string;
   destination_path?: string;
   original_name?: string;
   new_name?: string;
   timestamp: number;
-  status: "pending" | "in_progress" | "completed" | "undone" | string;
+  status: "pending" | "inprogress" | "completed" | "undone" | { failed: string };
   batch_id?: string;
   backup_path?: string;
+  error_code?: string;
+  purged: boolean;
 }
 
 export interface OperationBatch {
@@ -91,4 +115,198 @@ export interface OperationBatch {
   operations: Operation[];
   created_at: number;
   description: string;
+  source: "manual" | "rule" | "watcher" | "llm";
+  root_folder?: string;
+  operation_count: number;
+  failed_count: number;
+  total_bytes: number;
+}
+
+export interface HistoryFilter {
+  from?: number;
+  to?: number;
+  operation_type?: Operation["operation_type"];
+  path?: string;
+  batch_id?: string;
+}
+
+export type ExportFormat = "csv" | "jsonl" | "html";
+
+export interface PathRemap {
+  from: string;
+  to: string;
+}
+
+export interface ExportSummary {
+  path: string;
+  batch_count: number;
+  operation_count: number;
+}
+
+export interface ImportSummary {
+  batches_imported: number;
+  batches_skipped: number;
+  operations_imported: number;
+  operations_skipped: number;
+  missing_backups: string[];
+}
+
+// Trash types (matches backend commands/trash.rs)
+export interface TrashEntry {
+  path: string;
+  name: string;
+  original_path: string | null;
+  size: number;
+  deleted_at: number;
+  operation_id: string | null;
+  batch_id: string | null;
+}
+
+// Scan types (matches backend models/scan.rs)
+export type FileType =
+  | "document"
+  | "image"
+  | "video"
+  | "audio"
+  | "archive"
+  | "code"
+  | "symlink"
+  | "other";
+
+export interface ScanFilters {
+  include?: string[];
+  exclude?: string[];
+  respect_gitignore?: boolean;
+  min_size?: number;
+  max_size?: number;
+  modified_from?: number;
+  modified_to?: number;
+  created_from?: number;
+  created_to?: number;
+  file_types?: FileType[];
+}
+
+// File metadata (matches backend file_item.rs)
+export interface GpsPosition {
+  latitude: number;
+  longitude: number;
+  altitude: number | null;
+}
+
+export interface PhotoMetadata {
+  taken_at: string | null;
+  camera_make: string | null;
+  camera_model: string | null;
+  gps: GpsPosition | null;
+  orientation: number | null;
+}
+
+export interface AudioTags {
+  title: string | null;
+  artist: string | null;
+  album_artist: string | null;
+  album: string | null;
+  track: number | null;
+  track_total: number | null;
+  disc: number | null;
+  year: number | null;
+  genre: string | null;
+}
+
+export interface DocumentMetadata {
+  title: string | null;
+  author: string | null;
+  page_count: number | null;
+  created_at: string | null;
+}
+
+export type ArchiveFormat = "zip" | "tar" | "tar_gz" | "tar_bz2" | "tar_xz" | "7z";
+
+export type ArchiveKind =
+  | "photo_album"
+  | "music_album"
+  | "videos"
+  | "documents"
+  | "source_code";
+
+export interface ArchiveContentType {
+  file_type: FileType;
+  count: number;
+  size: number;
+}
+
+export interface ArchiveSummary {
+  format: ArchiveFormat;
+  entry_count: number;
+  uncompressed_size: number;
+  content_types: ArchiveContentType[];
+  kind: ArchiveKind | null;
+}
+
+export interface FileMetadata {
+  mime_type: string | null;
+  dimensions: [number, number] | null;
+  duration: number | null;
+  preview_text: string | null;
+  photo?: PhotoMetadata | null;
+  audio?: AudioTags | null;
+  document?: DocumentMetadata | null;
+  archive?: ArchiveSummary | null;
+}
+
+export interface ScanOptions {
+  path: string;
+  recursive: boolean;
+  include_hidden: boolean;
+  threads?: number;
+  max_depth?: number;
+  filters?: ScanFilters;
+  follow_symlinks?: boolean;
+  same_file_system?: boolean;
+  skip_special_files?: boolean;
+  extract_metadata?: boolean;
+  detect_file_types?: boolean;
+}
+
+export type ScanErrorKind =
+  | "permission_denied"
+  | "broken_symlink"
+  | "not_found"
+  | "symlink_loop"
+  | "other";
+
+export interface ScanError {
+  path: string | null;
+  kind: ScanErrorKind;
+  message: string;
+}
+
+export interface DuplicateOptions {
+  verify?: boolean;
+  threads?: number;
+}
+
+export type KeepStrategy =
+  | { strategy: "oldest" }
+  | { strategy: "newest" }
+  | { strategy: "shortest_path" }
+  | { strategy: "preferred_directory"; path: string }
+  | { strategy: "category_folder" };
+
+export type DuplicateAction = "delete" | "hardlink" | "symlink";
+
+export interface ScanChanges {
+  added: string[];
+  modified: string[];
+  removed: string[];
+  unchanged: number;
+}
+
+export interface ScanCheckpoint {
+  scan_id: string;
+  options: ScanOptions;
+  status: "running" | "cancelled";
+  file_count: number;
+  created_at: number;
+  updated_at: number;
 }